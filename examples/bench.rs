@@ -30,35 +30,159 @@ struct Opt {
     /// Bitshift cell size (powers of 2)
     #[structopt(short, long)]
     cell_size: u32,
+
+    /// Number of ids a single cell can hold
+    #[structopt(long, default_value = "32")]
+    bucket_capacity: usize,
+
+    /// How cells are mapped to slots: hashed, mixed, salted, morton or sparse
+    #[structopt(long, default_value = "hashed", parse(try_from_str = parse_layout))]
+    layout: supergrid::CellLayout,
+
+    /// Number of simulated frames after the insert and probe passes. Every frame moves each entity, then probes each one
+    #[structopt(long, default_value = "0")]
+    ticks: usize,
+
+    /// Largest distance an entity moves along each axis per frame
+    #[structopt(long, default_value = "8")]
+    delta: u32,
+
+    /// How frames move entities: update, move-by or delete-insert
+    #[structopt(long, default_value = "update", parse(try_from_str = parse_movement))]
+    movement: Movement,
+
+    /// Output format: text, or csv or json for scripts. CSV prints a header line and one row per run
+    #[structopt(long, default_value = "text", parse(try_from_str = parse_output))]
+    output: Output,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Output {
+    Text,
+    Csv,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Movement {
+    Update,
+    MoveBy,
+    DeleteInsert,
+}
+
+fn parse_movement(s: &str) -> Result<Movement, String> {
+    match s {
+        "update" => Ok(Movement::Update),
+        "move-by" => Ok(Movement::MoveBy),
+        "delete-insert" => Ok(Movement::DeleteInsert),
+        _ => Err(format!("unknown movement {}", s)),
+    }
+}
+
+fn parse_output(s: &str) -> Result<Output, String> {
+    match s {
+        "text" => Ok(Output::Text),
+        "csv" => Ok(Output::Csv),
+        "json" => Ok(Output::Json),
+        _ => Err(format!("unknown output format {}", s)),
+    }
+}
+
+/// Measurements of a run, in the order they are printed as CSV columns or JSON fields.
+#[derive(Default)]
+struct Stats {
+    fields: Vec<(&'static str, String)>,
+}
+
+impl Stats {
+    fn number(&mut self, name: &'static str, value: impl ToString) {
+        self.fields.push((name, value.to_string()));
+    }
+
+    fn text(&mut self, name: &'static str, value: &str) {
+        self.fields
+            .push((name, format!("\"{}\"", value.replace('"', "\\\""))));
+    }
+
+    fn print(&self, output: Output) {
+        match output {
+            Output::Text => {}
+            Output::Csv => {
+                let names: Vec<&str> = self.fields.iter().map(|(name, _)| *name).collect();
+                let values: Vec<&str> = self
+                    .fields
+                    .iter()
+                    .map(|(_, value)| value.as_str())
+                    .collect();
+                println!("{}", names.join(","));
+                println!("{}", values.join(","));
+            }
+            Output::Json => {
+                let fields: Vec<String> = self
+                    .fields
+                    .iter()
+                    .map(|(name, value)| format!("\"{}\":{}", name, value))
+                    .collect();
+                println!("{{{}}}", fields.join(","));
+            }
+        }
+    }
+}
+
+fn parse_layout(s: &str) -> Result<supergrid::CellLayout, String> {
+    match s {
+        "hashed" => Ok(supergrid::CellLayout::Hashed),
+        "mixed" => Ok(supergrid::CellLayout::Mixed),
+        "salted" => Ok(supergrid::CellLayout::salted()),
+        "morton" => Ok(supergrid::CellLayout::Morton),
+        "sparse" => Ok(supergrid::CellLayout::Sparse),
+        _ => Err(format!("unknown layout {}", s)),
+    }
 }
 
 fn main() {
     let opt = Opt::from_args();
-    let mut grid = supergrid::Grid::new(2048, opt.cell_size);
-    println!("Setup:");
-    println!(
-        "\tArena width:         {}",
-        opt.width.to_formatted_string(&Locale::en)
-    );
-    println!(
-        "\tArena height:        {}",
-        opt.height.to_formatted_string(&Locale::en)
-    );
-    println!(
-        "\tArena max size:      {}",
-        grid.count().to_formatted_string(&Locale::en)
-    );
-    println!(
-        "\tCell size:           {}x{}",
-        1 << opt.cell_size,
-        1 << opt.cell_size
-    );
-    println!(
-        "\tEntity count:        {}",
-        opt.count.to_formatted_string(&Locale::en)
-    );
-    println!("\tMinimum entity size: {}x{}", opt.min_size, opt.min_size);
-    println!("\tMaximum entity size: {}x{}", opt.max_size, opt.max_size);
+    let text = opt.output == Output::Text;
+    let mut grid =
+        supergrid::Grid::with_cell_layout(2048, opt.cell_size, opt.bucket_capacity, opt.layout);
+    let mut stats = Stats::default();
+    stats.number("width", opt.width);
+    stats.number("height", opt.height);
+    stats.number("slots", grid.count());
+    stats.number("cell_size", 1u64 << opt.cell_size);
+    stats.number("bucket_capacity", opt.bucket_capacity);
+    stats.text("layout", &format!("{:?}", grid.cell_layout()));
+    stats.number("count", opt.count);
+    stats.number("min_size", opt.min_size);
+    stats.number("max_size", opt.max_size);
+    if text {
+        println!("Setup:");
+        println!(
+            "\tArena width:         {}",
+            opt.width.to_formatted_string(&Locale::en)
+        );
+        println!(
+            "\tArena height:        {}",
+            opt.height.to_formatted_string(&Locale::en)
+        );
+        println!(
+            "\tArena max size:      {}",
+            grid.count().to_formatted_string(&Locale::en)
+        );
+        println!(
+            "\tCell size:           {}x{}",
+            1 << opt.cell_size,
+            1 << opt.cell_size
+        );
+        println!("\tBucket capacity:     {}", opt.bucket_capacity);
+        println!("\tCell layout:         {:?}", grid.cell_layout());
+        println!(
+            "\tEntity count:        {}",
+            opt.count.to_formatted_string(&Locale::en)
+        );
+        println!("\tMinimum entity size: {}x{}", opt.min_size, opt.min_size);
+        println!("\tMaximum entity size: {}x{}", opt.max_size, opt.max_size);
+    }
 
     let mut rng = rand::thread_rng();
     let mut entities = vec![];
@@ -83,26 +207,183 @@ fn main() {
         grid.insert(&ent).expect("too many entities in cell");
         entities.push(ent);
     }
-    println!(
-        "Took {:?} to insert {} entities; average: {:?}",
-        now.elapsed(),
-        opt.count.to_formatted_string(&Locale::en),
-        now.elapsed() / opt.count as u32
-    );
+    let elapsed = now.elapsed();
+    stats.number("insert_ns", elapsed.as_nanos());
+    stats.number("insert_avg_ns", (elapsed / opt.count as u32).as_nanos());
+    if text {
+        println!(
+            "Took {:?} to insert {} entities; average: {:?}",
+            elapsed,
+            opt.count.to_formatted_string(&Locale::en),
+            elapsed / opt.count as u32
+        );
+    }
     let mut hits = 0;
     let now = Instant::now();
     for ent in entities.iter() {
         hits += grid.query(&ent.clone().into()).len();
     }
+    let elapsed = now.elapsed();
+    stats.number("probe_ns", elapsed.as_nanos());
+    stats.number("probe_avg_ns", (elapsed / opt.count as u32).as_nanos());
+    stats.number("collisions", hits);
+    stats.number("collisions_avg", hits as f32 / opt.count as f32);
+    if text {
+        println!(
+            "Took {:?} to probe {} entities; average: {:?}",
+            elapsed,
+            opt.count.to_formatted_string(&Locale::en),
+            elapsed / opt.count as u32
+        );
+        println!(
+            "Collisions: {}; average: {}",
+            hits.to_formatted_string(&Locale::en),
+            hits as f32 / opt.count as f32
+        );
+    }
+
+    if opt.ticks > 0 {
+        let mut moves = Vec::with_capacity(opt.ticks);
+        let mut probes = Vec::with_capacity(opt.ticks);
+        let delta = opt.delta as i64;
+        for _ in 0..opt.ticks {
+            let now = Instant::now();
+            for ent in entities.iter_mut() {
+                let x =
+                    (ent.x as i64 + rng.gen_range(-delta..=delta)).clamp(0, opt.width as i64 - 1);
+                let y =
+                    (ent.y as i64 + rng.gen_range(-delta..=delta)).clamp(0, opt.height as i64 - 1);
+                let (dx, dy) = ((x - ent.x as i64) as i32, (y - ent.y as i64) as i32);
+                ent.x = x as u32;
+                ent.y = y as u32;
+                match opt.movement {
+                    Movement::Update => grid.update(ent),
+                    Movement::MoveBy => grid.move_by(ent.id, dx, dy),
+                    Movement::DeleteInsert => {
+                        grid.delete(ent.id);
+                        grid.insert(ent)
+                    }
+                }
+                .expect("too many entities in cell");
+            }
+            moves.push(now.elapsed());
+
+            let now = Instant::now();
+            for ent in entities.iter() {
+                hits += grid.query(&ent.clone().into()).len();
+            }
+            probes.push(now.elapsed());
+        }
+        std::hint::black_box(hits);
+
+        if text {
+            println!(
+                "Churn: {} frames moving {} entities by up to {} with {:?}",
+                opt.ticks,
+                opt.count.to_formatted_string(&Locale::en),
+                opt.delta,
+                opt.movement
+            );
+        }
+        let phases = [
+            (
+                "move",
+                ["move_p50_ns", "move_p90_ns", "move_p99_ns", "move_max_ns"],
+                moves,
+            ),
+            (
+                "probe",
+                [
+                    "probe_p50_ns",
+                    "probe_p90_ns",
+                    "probe_p99_ns",
+                    "probe_max_ns",
+                ],
+                probes,
+            ),
+        ];
+        for (phase, names, mut times) in phases {
+            times.sort_unstable();
+            let percentile = |p: usize| times[(times.len() - 1) * p / 100];
+            let (p50, p90, p99, max) = (
+                percentile(50),
+                percentile(90),
+                percentile(99),
+                percentile(100),
+            );
+            if text {
+                println!(
+                    "\t{:<6} p50: {:?}; p90: {:?}; p99: {:?}; max: {:?}",
+                    format!("{}:", phase),
+                    p50,
+                    p90,
+                    p99,
+                    max
+                );
+            }
+            for (name, time) in names.into_iter().zip([p50, p90, p99, max]) {
+                stats.number(name, time.as_nanos());
+            }
+        }
+    }
+
+    // compare the entity tables on the same ids: direct indexing sizes the table by the grid's size.
+    if text {
+        println!("Tables:");
+    }
+    let mut table = supergrid::Table::<u32>::new(2048);
+    let now = Instant::now();
+    for ent in entities.iter() {
+        *table.get_scalar_mut(ent.id) = ent.x;
+    }
+    let mut sum = 0u64;
+    for ent in entities.iter() {
+        sum += *table.get_scalar(ent.id) as u64;
+    }
+    let elapsed = now.elapsed();
+    stats.number("direct_index_ns", elapsed.as_nanos());
+    if text {
+        report("Direct index", elapsed, table.count(), opt.count);
+    }
+
+    let mut table = supergrid::RobinHoodTable::<u32>::new(opt.count);
+    let now = Instant::now();
+    for ent in entities.iter() {
+        table.insert_scalar(ent.id, ent.x);
+    }
+    for ent in entities.iter() {
+        sum += *table.get_scalar(ent.id).unwrap() as u64;
+    }
+    let elapsed = now.elapsed();
+    stats.number("robin_hood_ns", elapsed.as_nanos());
+    if text {
+        report("Robin Hood", elapsed, table.count(), opt.count);
+    }
+
+    let mut table = supergrid::CuckooTable::<u32>::new(opt.count);
+    let now = Instant::now();
+    for ent in entities.iter() {
+        table.insert_scalar(ent.id, ent.x);
+    }
+    for ent in entities.iter() {
+        sum += *table.get_scalar(ent.id).unwrap() as u64;
+    }
+    let elapsed = now.elapsed();
+    stats.number("cuckoo_ns", elapsed.as_nanos());
+    if text {
+        report("Cuckoo", elapsed, table.count(), opt.count);
+    }
+    std::hint::black_box(sum);
+    stats.print(opt.output);
+}
+
+fn report(name: &str, elapsed: std::time::Duration, slots: usize, count: usize) {
     println!(
-        "Took {:?} to probe {} entities; average: {:?}",
-        now.elapsed(),
-        opt.count.to_formatted_string(&Locale::en),
-        now.elapsed() / opt.count as u32
-    );
-    println!(
-        "Collisions: {}; average: {}",
-        hits.to_formatted_string(&Locale::en),
-        hits as f32 / opt.count as f32
+        "\t{:<13} {:?} to write and read {} ids; average: {:?}; slots: {}",
+        format!("{}:", name),
+        elapsed,
+        count.to_formatted_string(&Locale::en),
+        elapsed / count as u32,
+        slots.to_formatted_string(&Locale::en)
     );
 }
@@ -0,0 +1,189 @@
+//! Renders grid occupancy, entities and a query to a PPM image. The crate has no windowing dependency, so instead of
+//! a live window with mouse input, `--interactive` reads clicks as pixel coordinates from stdin and rewrites the image
+//! after each one; open it in a viewer that reloads changed files to follow along.
+
+use std::fs::File;
+use std::io::{BufRead, BufWriter, Write};
+
+use rand::prelude::*;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "visualize",
+    about = "Render grid occupancy, entities and a query to a PPM image."
+)]
+struct Opt {
+    /// Width and height of arena
+    #[structopt(short, long, default_value = "4096")]
+    size: u32,
+
+    /// Number of entities
+    #[structopt(short, long, default_value = "500")]
+    count: u32,
+
+    /// Maximum size of squares
+    #[structopt(short, long, default_value = "200")]
+    max_size: u32,
+
+    /// Bitshift cell size (powers of 2)
+    #[structopt(long, default_value = "8")]
+    cell_size: u32,
+
+    /// Size of the output image in pixels
+    #[structopt(short, long, default_value = "1024")]
+    pixels: u32,
+
+    /// Output file
+    #[structopt(short, long, default_value = "grid.ppm")]
+    output: String,
+
+    /// Read clicks as `x y` pixel coordinates from stdin, moving the query and rendering again after each
+    #[structopt(short, long)]
+    interactive: bool,
+}
+
+struct Image {
+    size: u32,
+    pixels: Vec<[u8; 3]>,
+}
+
+impl Image {
+    fn fill(&mut self, x0: u32, y0: u32, x1: u32, y1: u32, color: [u8; 3]) {
+        for y in y0.min(self.size - 1)..=y1.min(self.size - 1) {
+            for x in x0.min(self.size - 1)..=x1.min(self.size - 1) {
+                self.pixels[(y * self.size + x) as usize] = color;
+            }
+        }
+    }
+
+    fn outline(&mut self, x0: u32, y0: u32, x1: u32, y1: u32, color: [u8; 3]) {
+        self.fill(x0, y0, x1, y0, color);
+        self.fill(x0, y1, x1, y1, color);
+        self.fill(x0, y0, x0, y1, color);
+        self.fill(x1, y0, x1, y1, color);
+    }
+}
+
+fn main() -> std::io::Result<()> {
+    let opt = Opt::from_args();
+    // enough table entries for every cell of the arena and every entity.
+    let cells = ((opt.size >> opt.cell_size) as usize + 1).pow(2);
+    let size = cells.max(opt.count as usize).div_ceil(1000);
+    let mut grid = supergrid::Grid::new(size, opt.cell_size);
+    let mut rng = rand::thread_rng();
+    let mut entities = vec![];
+    for id in 0..opt.count {
+        let ent = supergrid::Entity {
+            id,
+            x: rng.gen_range(0..opt.size),
+            y: rng.gen_range(0..opt.size),
+            width: rng.gen_range(1..opt.max_size),
+            height: rng.gen_range(1..opt.max_size),
+        };
+        if grid.try_insert(&ent).is_ok() {
+            entities.push(ent);
+        }
+    }
+
+    let mut query = supergrid::Query {
+        x: rng.gen_range(0..opt.size / 2),
+        y: rng.gen_range(0..opt.size / 2),
+        width: opt.size / 4,
+        height: opt.size / 4,
+    };
+    render(&opt, &grid, &entities, &query)?;
+    if !opt.interactive {
+        return Ok(());
+    }
+
+    // every line read is a click: the query is centered on it and the image rendered again.
+    let scale = opt.size as f64 / opt.pixels as f64;
+    for line in std::io::stdin().lock().lines() {
+        let line = line?;
+        let mut coords = line.split_whitespace().map(str::parse::<u32>);
+        let (x, y) = match (coords.next(), coords.next()) {
+            (Some(Ok(x)), Some(Ok(y))) => (x, y),
+            _ => {
+                eprintln!("expected two pixel coordinates, got {:?}", line);
+                continue;
+            }
+        };
+        let (x, y) = ((x as f64 * scale) as u32, (y as f64 * scale) as u32);
+        query.x = x.saturating_sub(query.width / 2);
+        query.y = y.saturating_sub(query.height / 2);
+        render(&opt, &grid, &entities, &query)?;
+    }
+    Ok(())
+}
+
+/// Render the grid, its entities and a query to the output file.
+fn render(
+    opt: &Opt,
+    grid: &supergrid::Grid,
+    entities: &[supergrid::Entity],
+    query: &supergrid::Query,
+) -> std::io::Result<()> {
+    let hits = grid.query(query);
+
+    let scale = opt.size as f64 / opt.pixels as f64;
+    let px = |v: u32| (v as f64 / scale) as u32;
+    let mut image = Image {
+        size: opt.pixels,
+        pixels: vec![[16, 16, 16]; (opt.pixels * opt.pixels) as usize],
+    };
+
+    // cells, shaded from green to red as they approach bucket capacity.
+    let cell = 1 << opt.cell_size;
+    for cy in 0..opt.size.div_ceil(cell) {
+        for cx in 0..opt.size.div_ceil(cell) {
+            let occupancy = grid.query_point(cx * cell, cy * cell).len();
+            let load = (occupancy as f64 / grid.bucket_capacity() as f64).min(1.0);
+            let color = if occupancy == 0 {
+                [16, 16, 16]
+            } else {
+                [(255.0 * load) as u8, (255.0 * (1.0 - load)) as u8 / 2, 32]
+            };
+            let (x0, y0) = (px(cx * cell), px(cy * cell));
+            let x1 = px((cx + 1) * cell).saturating_sub(1).max(x0);
+            let y1 = px((cy + 1) * cell).saturating_sub(1).max(y0);
+            image.fill(x0, y0, x1, y1, color);
+            image.outline(x0, y0, x1, y1, [48, 48, 48]);
+        }
+    }
+
+    for ent in entities.iter() {
+        let color = if hits.contains(&ent.id) {
+            [255, 230, 0]
+        } else {
+            [220, 220, 220]
+        };
+        image.outline(
+            px(ent.x),
+            px(ent.y),
+            px(ent.x + ent.width),
+            px(ent.y + ent.height),
+            color,
+        );
+    }
+    image.outline(
+        px(query.x),
+        px(query.y),
+        px(query.x + query.width),
+        px(query.y + query.height),
+        [64, 160, 255],
+    );
+
+    let mut out = BufWriter::new(File::create(&opt.output)?);
+    writeln!(out, "P6 {} {} 255", image.size, image.size)?;
+    for pixel in image.pixels.iter() {
+        out.write_all(pixel)?;
+    }
+    println!(
+        "Wrote {} ({} entities, {} query hits)",
+        opt.output,
+        entities.len(),
+        hits.len()
+    );
+    Ok(())
+}
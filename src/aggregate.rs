@@ -0,0 +1,105 @@
+//! Per-entity numeric values and queries folding them over a region, such as the total threat near a position.
+
+use std::collections::HashSet;
+
+use crate::{Error, Grid, Query};
+
+/// How `Grid::query_aggregate` combines the values of the entities in a region.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Aggregate {
+    /// The sum of the values.
+    Sum,
+    /// The largest value, or negative infinity for an empty region.
+    Max,
+    /// The number of entities, ignoring their values.
+    Count,
+}
+
+impl Grid {
+    /// Set an entity's value, such as its mass or threat. Entities start at 0; like priorities, `update` keeps the value
+    /// and `delete` resets it. Pinned entities always have the value 0.
+    pub fn set_value(&mut self, id: u32, value: f32) -> Result<(), Error> {
+        let map = self.maps.get_scalar_mut(id);
+        if !map.is_registered() || map.id != id {
+            return Err(Error::NotFound(id));
+        }
+        map.meta.value = value;
+        Ok(())
+    }
+
+    /// Get an entity's value.
+    pub fn value(&self, id: u32) -> f32 {
+        let map = self.maps.get_scalar(id);
+        if !map.is_registered() || map.id != id {
+            return 0.0;
+        }
+        map.meta.value
+    }
+
+    /// Combine the values of the unique entities in a region while visiting its cells, without collecting their ids.
+    /// Entities are found like with `query`, each counted once, and values are summed in `f64`.
+    pub fn query_aggregate(&self, query: &Query, aggregate: Aggregate) -> f64 {
+        let mut total = match aggregate {
+            Aggregate::Max => f64::NEG_INFINITY,
+            Aggregate::Sum | Aggregate::Count => 0.0,
+        };
+        self.visit_unique(query, |id| match aggregate {
+            Aggregate::Sum => total += self.value(id) as f64,
+            Aggregate::Max => total = total.max(self.value(id) as f64),
+            Aggregate::Count => total += 1.0,
+        });
+        total
+    }
+
+    /// Call `visit` once with each unique entity in a region, found like with `query`, without collecting them.
+    pub(crate) fn visit_unique(&self, query: &Query, mut visit: impl FnMut(u32)) {
+        let sx = query.x >> self.shift;
+        let sy = query.y >> self.shift;
+
+        let ex = query.x.saturating_add(query.width) >> self.shift;
+        let ey = query.y.saturating_add(query.height) >> self.shift;
+
+        let layers = if self.has_statics {
+            &[&self.grid, &self.statics][..]
+        } else {
+            &[&self.grid][..]
+        };
+        let mut seen = self.seen.lock();
+        if let Some(seen) = &mut seen {
+            seen.begin();
+        }
+        // the dedup table is disabled, or busy while another thread queries, so fall back to a set.
+        let mut fallback = HashSet::new();
+        for buckets in layers {
+            for y in sy..=ey {
+                for x in sx..=ex {
+                    let slot = buckets.slot(x, y);
+                    if buckets.is_empty(slot) {
+                        continue;
+                    }
+                    for &id in buckets.iter(slot) {
+                        // every id is deduplicated: under the hashed layouts, a bucket can hold the same id for
+                        // several cells sharing it, even in a single-cell region.
+                        let id = id & !(1 << 31);
+                        let fresh = if let Some(seen) = &mut seen {
+                            seen.insert(id)
+                        } else {
+                            fallback.insert(id)
+                        };
+                        if fresh {
+                            visit(id);
+                        }
+                    }
+                }
+            }
+        }
+        drop(seen);
+
+        // large entities are in no bucket, so they cannot repeat the ids found so far.
+        if !self.large.is_empty() {
+            for id in self.large_in(sx, sy, ex, ey) {
+                visit(id);
+            }
+        }
+    }
+}
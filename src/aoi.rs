@@ -0,0 +1,238 @@
+//! Area-of-interest subscriptions and moving observers for interest management.
+
+use std::collections::HashMap;
+
+use crate::falloff::distance_squared;
+use crate::{overlaps, Entity, Grid, Query};
+
+/// Which entities entered or left each subscriber's region or observer's view since the previous update.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AoiEvents {
+    /// `(subscriber, id)` pairs for entities that started overlapping a region or came into an observer's view.
+    pub entered: Vec<(u32, u32)>,
+    /// `(subscriber, id)` pairs for entities that stopped overlapping a region, left a view or were deleted.
+    pub left: Vec<(u32, u32)>,
+}
+
+#[derive(Debug, Clone)]
+struct Subscription {
+    region: Query,
+    /// Ids overlapping the region at the last update, sorted.
+    visible: Vec<u32>,
+}
+
+#[derive(Debug, Clone)]
+struct View {
+    center: (u32, u32),
+    enter: u32,
+    exit: u32,
+    /// Ids in view at the last update, sorted.
+    visible: Vec<u32>,
+}
+
+/// Circular views of moving observers such as players, with separate enter and exit radii.
+/// Feed it to `Grid::update_observers` once per tick to get enter and leave events.
+#[derive(Debug, Clone, Default)]
+pub struct Observers {
+    observers: HashMap<u32, View>,
+}
+
+impl Observers {
+    /// Create an empty set of observers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register an observer, or change its radii and move it if it already exists. Entities enter the view within
+    /// `enter_radius` of the center and leave beyond `exit_radius`, which is raised to `enter_radius` if smaller.
+    pub fn observe(
+        &mut self,
+        observer: u32,
+        center: (u32, u32),
+        enter_radius: u32,
+        exit_radius: u32,
+    ) {
+        let exit = exit_radius.max(enter_radius);
+        self.observers
+            .entry(observer)
+            .and_modify(|view| {
+                view.center = center;
+                view.enter = enter_radius;
+                view.exit = exit;
+            })
+            .or_insert(View {
+                center,
+                enter: enter_radius,
+                exit,
+                visible: Vec::new(),
+            });
+    }
+
+    /// Move an observer's center, keeping the entities in view. Returns `false` if there is no such observer.
+    pub fn move_to(&mut self, observer: u32, center: (u32, u32)) -> bool {
+        match self.observers.get_mut(&observer) {
+            Some(view) => {
+                view.center = center;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove an observer, returning the ids it could see. No leave events are reported for them.
+    pub fn unobserve(&mut self, observer: u32) -> Option<Vec<u32>> {
+        self.observers.remove(&observer).map(|view| view.visible)
+    }
+
+    /// Get the ids an observer could see at the last update, sorted.
+    pub fn visible(&self, observer: u32) -> &[u32] {
+        self.observers
+            .get(&observer)
+            .map_or(&[], |view| &view.visible)
+    }
+
+    /// Get the number of observers.
+    pub fn len(&self) -> usize {
+        self.observers.len()
+    }
+
+    /// Check whether there are no observers.
+    pub fn is_empty(&self) -> bool {
+        self.observers.is_empty()
+    }
+}
+
+/// Rectangular interest regions registered by subscribers such as connected clients.
+/// Feed it to `Grid::update_interest` once per tick to get enter and leave events.
+#[derive(Debug, Clone, Default)]
+pub struct Subscriptions {
+    subscriptions: HashMap<u32, Subscription>,
+}
+
+impl Subscriptions {
+    /// Create an empty set of subscriptions.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a subscriber's region, or move it if the subscriber already has one.
+    /// Entities already in view are kept, so moving a region only reports the difference.
+    pub fn subscribe(&mut self, subscriber: u32, region: Query) {
+        self.subscriptions
+            .entry(subscriber)
+            .and_modify(|s| s.region = region.clone())
+            .or_insert(Subscription {
+                region,
+                visible: Vec::new(),
+            });
+    }
+
+    /// Remove a subscriber, returning the ids it could see. No leave events are reported for them.
+    pub fn unsubscribe(&mut self, subscriber: u32) -> Option<Vec<u32>> {
+        self.subscriptions.remove(&subscriber).map(|s| s.visible)
+    }
+
+    /// Get a subscriber's region.
+    pub fn region(&self, subscriber: u32) -> Option<&Query> {
+        self.subscriptions.get(&subscriber).map(|s| &s.region)
+    }
+
+    /// Get the ids a subscriber could see at the last update, sorted.
+    pub fn visible(&self, subscriber: u32) -> &[u32] {
+        self.subscriptions
+            .get(&subscriber)
+            .map_or(&[], |s| &s.visible)
+    }
+
+    /// Get the number of subscribers.
+    pub fn len(&self) -> usize {
+        self.subscriptions.len()
+    }
+
+    /// Check whether there are no subscribers.
+    pub fn is_empty(&self) -> bool {
+        self.subscriptions.is_empty()
+    }
+}
+
+impl Grid {
+    /// Re-evaluate every subscriber's region and report which entities entered or left it since the last call.
+    /// Entities count as visible when their last inserted rectangle overlaps the region, not just a shared cell.
+    /// Every region is queried on each call, so the cost grows with the total area subscribed.
+    pub fn update_interest(&self, subscriptions: &mut Subscriptions) -> AoiEvents {
+        let mut events = AoiEvents::default();
+        for (&subscriber, subscription) in subscriptions.subscriptions.iter_mut() {
+            let mut current: Vec<u32> = self
+                .query(&subscription.region)
+                .into_iter()
+                .filter(|&id| {
+                    self.rect(id)
+                        .is_some_and(|entity| overlaps(&entity, &subscription.region))
+                })
+                .collect();
+            current.sort_unstable();
+            current.dedup();
+
+            report(subscriber, &subscription.visible, &current, &mut events);
+            subscription.visible = current;
+        }
+        events.entered.sort_unstable();
+        events.left.sort_unstable();
+        events
+    }
+
+    /// Re-evaluate every observer and report which entities entered or left its view since the last call.
+    /// An entity enters once its last inserted rectangle comes within the enter radius of the observer's center,
+    /// and only leaves once it is farther than the exit radius, so entities near the edge do not flicker.
+    pub fn update_observers(&self, observers: &mut Observers) -> AoiEvents {
+        let mut events = AoiEvents::default();
+        for (&observer, view) in observers.observers.iter_mut() {
+            let (cx, cy) = view.center;
+            let region = Query::around_point(cx, cy, view.exit);
+            let mut current: Vec<u32> = self
+                .query(&region)
+                .into_iter()
+                .filter(|&id| {
+                    let radius = if view.visible.binary_search(&id).is_ok() {
+                        view.exit
+                    } else {
+                        view.enter
+                    };
+                    self.rect(id)
+                        .is_some_and(|entity| within(&entity, view.center, radius))
+                })
+                .collect();
+            current.sort_unstable();
+            current.dedup();
+
+            report(observer, &view.visible, &current, &mut events);
+            view.visible = current;
+        }
+        events.entered.sort_unstable();
+        events.left.sort_unstable();
+        events
+    }
+}
+
+/// Report the differences between a subscriber's previous and current sorted ids.
+fn report(subscriber: u32, previous: &[u32], current: &[u32], events: &mut AoiEvents) {
+    // both lists are sorted, so a merge finds the differences.
+    let (mut i, mut j) = (0, 0);
+    while i < previous.len() || j < current.len() {
+        if j == current.len() || (i < previous.len() && previous[i] < current[j]) {
+            events.left.push((subscriber, previous[i]));
+            i += 1;
+        } else if i == previous.len() || current[j] < previous[i] {
+            events.entered.push((subscriber, current[j]));
+            j += 1;
+        } else {
+            i += 1;
+            j += 1;
+        }
+    }
+}
+
+/// Check whether a rectangle comes within `radius` of a point.
+fn within(entity: &Entity, point: (u32, u32), radius: u32) -> bool {
+    distance_squared(entity, point) <= radius as u128 * radius as u128
+}
@@ -0,0 +1,112 @@
+//! Approximate queries over huge regions, visiting a fixed subset of cells and extrapolating counts from it.
+
+use std::collections::HashSet;
+
+use crate::{Grid, Query};
+
+/// The result of `Grid::query_approx`. Every id belongs to an entity in the region, but entities in unvisited cells are missing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApproxResults {
+    /// The unique entities found in the visited cells.
+    pub ids: Vec<u32>,
+    /// Number of cells visited.
+    pub cells_visited: u64,
+    /// Number of cells in the region.
+    pub cells_total: u64,
+    /// Estimated number of entities in the region.
+    pub estimated_count: f64,
+}
+
+impl ApproxResults {
+    /// Get the estimated number of entities per cell of the region.
+    pub fn density(&self) -> f64 {
+        if self.cells_total == 0 {
+            0.0
+        } else {
+            self.estimated_count / self.cells_total as f64
+        }
+    }
+}
+
+impl Grid {
+    /// Visit about `sample_fraction` of the cells of a region and estimate how many entities it holds, such as for heat maps
+    /// over the whole world. The same region and grid always visit the same cells. An entity found in a visited cell counts
+    /// for the share of its cells that cell is, so the estimate does not favor large entities. Entities stored outside the
+    /// cells are always found and counted exactly. Panics if `sample_fraction` is not positive.
+    pub fn query_approx(&self, query: &Query, sample_fraction: f64) -> ApproxResults {
+        assert!(sample_fraction > 0.0, "sample fraction must be positive");
+        let sx = query.x >> self.shift;
+        let sy = query.y >> self.shift;
+
+        let ex = query.x.saturating_add(query.width) >> self.shift;
+        let ey = query.y.saturating_add(query.height) >> self.shift;
+
+        let columns = (ex - sx) as u128 + 1;
+        let total = columns * ((ey - sy) as u128 + 1);
+        // visit every `step`th cell in row order. A step sharing no factor with the row length shifts the visited
+        // cells by a column from one row to the next, instead of sampling the same columns in every row.
+        let mut step = (1.0 / sample_fraction.min(1.0)).round().max(1.0) as u128;
+        while step > 1 && gcd(step, columns) != 1 {
+            step += 1;
+        }
+
+        let layers = if self.has_statics {
+            &[&self.grid, &self.statics][..]
+        } else {
+            &[&self.grid][..]
+        };
+        let mut seen = HashSet::new();
+        let mut ids = Vec::new();
+        let mut weight = 0.0;
+        let mut visited = 0;
+        let mut index = 0;
+        while index < total {
+            let x = sx + (index % columns) as u32;
+            let y = sy + (index / columns) as u32;
+            visited += 1;
+            index += step;
+            for buckets in layers {
+                let slot = buckets.slot(x, y);
+                if buckets.is_empty(slot) {
+                    continue;
+                }
+                for &id in buckets.iter(slot) {
+                    let raw = id & !(1 << 31);
+                    // buckets can be shared with other cells, so check that the entity covers this one.
+                    let Some(entity) = self.rect(raw) else {
+                        continue;
+                    };
+                    let (esx, esy) = (entity.x >> self.shift, entity.y >> self.shift);
+                    let ex = entity.x.saturating_add(entity.width) >> self.shift;
+                    let ey = entity.y.saturating_add(entity.height) >> self.shift;
+                    if x < esx || x > ex || y < esy || y > ey {
+                        continue;
+                    }
+                    weight += 1.0 / ((ex - esx + 1) as f64 * (ey - esy + 1) as f64);
+                    if seen.insert(raw) {
+                        ids.push(raw);
+                    }
+                }
+            }
+        }
+
+        let mut estimated_count = weight * total as f64 / visited as f64;
+        for id in self.large_in(sx, sy, ex, ey) {
+            ids.push(id);
+            estimated_count += 1.0;
+        }
+        ApproxResults {
+            ids,
+            cells_visited: visited,
+            cells_total: total.min(u64::MAX as u128) as u64,
+            estimated_count,
+        }
+    }
+}
+
+fn gcd(mut a: u128, mut b: u128) -> u128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
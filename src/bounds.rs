@@ -0,0 +1,223 @@
+//! Optional world extents and maximum entity size, both enforced by every insert and update.
+
+use crate::{Entity, Error, Grid, Query};
+
+/// What inserts and updates do with rectangles reaching outside the world bounds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BoundsPolicy {
+    /// Fail with `Error::OutOfBounds`.
+    #[default]
+    Reject,
+    /// Cut the rectangle down to the part inside the bounds. Rectangles entirely outside collapse onto the nearest edge.
+    Clamp,
+}
+
+impl Grid {
+    /// Create a new grid whose inserts and updates only accept rectangles inside `bounds`.
+    pub fn with_bounds(size: usize, shift: u32, bounds: Query) -> Self {
+        let mut grid = Self::new(size, shift);
+        grid.bounds = Some(bounds);
+        grid
+    }
+
+    /// Get the world bounds, if any.
+    pub fn bounds(&self) -> Option<&Query> {
+        self.bounds.as_ref()
+    }
+
+    /// Set or remove the world bounds. Entities already in the grid are not checked.
+    /// Every insert and update applies the bounds policy; among the queries, only `try_query` applies it,
+    /// while the others visit the requested cells as they are.
+    pub fn set_bounds(&mut self, bounds: Option<Query>) {
+        self.bounds = bounds;
+    }
+
+    /// Get what happens to rectangles outside the bounds.
+    pub fn bounds_policy(&self) -> BoundsPolicy {
+        self.bounds_policy
+    }
+
+    /// Set what happens to rectangles outside the bounds.
+    pub fn set_bounds_policy(&mut self, policy: BoundsPolicy) {
+        self.bounds_policy = policy;
+    }
+
+    /// Get the maximum width and height of an entity, if any.
+    pub fn max_entity_size(&self) -> Option<(u32, u32)> {
+        self.max_size
+    }
+
+    /// Set or remove the maximum width and height of an entity. Inserts and updates beyond it fail with
+    /// `Error::EntityTooLarge` before the grid is touched, instead of visiting every cell of a runaway rectangle.
+    /// Entities that legitimately cover large areas belong in the large entity list or a grid with a bigger shift.
+    /// Entities already in the grid are checked when they are next inserted or updated.
+    pub fn set_max_entity_size(&mut self, size: Option<(u32, u32)>) {
+        self.max_size = size;
+    }
+
+    /// Check an entity against the maximum entity size.
+    #[inline(always)]
+    pub(crate) fn check_size(&self, entity: &Entity) -> Result<(), Error> {
+        match self.max_size {
+            Some((width, height)) if entity.width > width || entity.height > height => {
+                Err(Error::EntityTooLarge(entity.id))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Check an entity against the bounds, returning the clamped entity if its rectangle had to change.
+    #[inline(always)]
+    pub(crate) fn bound_entity(&self, entity: &Entity) -> Result<Option<Entity>, Error> {
+        if self.bounds.is_none() {
+            return Ok(None);
+        }
+        let (x, y, width, height) =
+            self.bounded(entity.x, entity.y, entity.width, entity.height)?;
+        if (x, y, width, height) == (entity.x, entity.y, entity.width, entity.height) {
+            return Ok(None);
+        }
+        Ok(Some(Entity {
+            id: entity.id,
+            x,
+            y,
+            width,
+            height,
+        }))
+    }
+
+    /// Check a rectangle against the bounds, returning it as it should be stored or queried.
+    pub(crate) fn bounded(
+        &self,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<(u32, u32, u32, u32), Error> {
+        let bounds = match &self.bounds {
+            Some(bounds) => bounds,
+            None => return Ok((x, y, width, height)),
+        };
+        let (min_x, min_y) = (bounds.x as u64, bounds.y as u64);
+        let max_x = min_x + bounds.width as u64;
+        let max_y = min_y + bounds.height as u64;
+        let (right, bottom) = (x as u64 + width as u64, y as u64 + height as u64);
+        if x as u64 >= min_x && y as u64 >= min_y && right <= max_x && bottom <= max_y {
+            return Ok((x, y, width, height));
+        }
+
+        match self.bounds_policy {
+            BoundsPolicy::Reject => Err(Error::OutOfBounds),
+            BoundsPolicy::Clamp => {
+                let x = (x as u64).clamp(min_x, max_x);
+                let y = (y as u64).clamp(min_y, max_y);
+                let right = right.clamp(min_x, max_x);
+                let bottom = bottom.clamp(min_y, max_y);
+                Ok((x as u32, y as u32, (right - x) as u32, (bottom - y) as u32))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Entity, Error, Grid, LayerMask, Query};
+
+    fn bounded_grid() -> Grid {
+        let bounds = Query {
+            x: 0,
+            y: 0,
+            width: 1000,
+            height: 1000,
+        };
+        let mut grid = Grid::with_bounds(64, 4, bounds);
+        grid.insert(&entity(1, 10)).unwrap();
+        grid
+    }
+
+    fn entity(id: u32, x: u32) -> Entity {
+        Entity {
+            id,
+            x,
+            y: 10,
+            width: 5,
+            height: 5,
+        }
+    }
+
+    fn everything() -> Query {
+        Query {
+            x: 0,
+            y: 0,
+            width: 1000,
+            height: 1000,
+        }
+    }
+
+    #[test]
+    fn out_of_bounds_guarded_insert_keeps_existing_entity() {
+        let mut grid = bounded_grid();
+        assert!(matches!(
+            grid.insert_guarded(&entity(1, 5000)),
+            Err(Error::OutOfBounds)
+        ));
+        assert_eq!(grid.query(&everything()).into_vec(), [1]);
+    }
+
+    #[test]
+    fn out_of_bounds_layer_insert_keeps_existing_layer() {
+        let mut grid = bounded_grid();
+        assert_eq!(
+            grid.insert_in_layer(&entity(1, 5000), 3),
+            Err(Error::OutOfBounds)
+        );
+        assert_eq!(grid.layer(1), 0);
+        assert_eq!(
+            grid.query_layers(&everything(), LayerMask::layer(0))
+                .into_vec(),
+            [1]
+        );
+    }
+
+    #[test]
+    fn out_of_bounds_expiring_insert_keeps_existing_entity() {
+        let mut grid = bounded_grid();
+        assert_eq!(
+            grid.insert_expiring(&entity(1, 5000), 5),
+            Err(Error::OutOfBounds)
+        );
+        assert_eq!(grid.expiry(1), None);
+        assert!(grid.expire(10).is_empty());
+        assert_eq!(grid.query(&everything()).into_vec(), [1]);
+    }
+
+    #[test]
+    fn out_of_bounds_template_insert_keeps_existing_tags() {
+        let mut grid = bounded_grid();
+        let template = grid.register_template(5, 5, 0b101);
+        assert_eq!(
+            grid.insert_from_template(1, 5000, 10, template),
+            Err(Error::OutOfBounds)
+        );
+        assert_eq!(grid.tags(1), 0);
+    }
+
+    #[test]
+    fn out_of_bounds_absorb_keeps_existing_metadata() {
+        let mut source = Grid::new(64, 4);
+        source.insert(&entity(1, 5000)).unwrap();
+        source.set_tags(1, 0b11).unwrap();
+        let snapshot = source.extract_region(&Query {
+            x: 4096,
+            y: 0,
+            width: 2000,
+            height: 1000,
+        });
+        assert_eq!(snapshot.len(), 1);
+
+        let mut grid = bounded_grid();
+        assert_eq!(grid.absorb_region(snapshot), Err(Error::OutOfBounds));
+        assert_eq!(grid.tags(1), 0);
+        assert_eq!(grid.query(&everything()).into_vec(), [1]);
+    }
+}
@@ -0,0 +1,116 @@
+//! Queries that stop after a time or cell budget and hand back a token to continue them later.
+
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+use crate::{Grid, Query};
+
+/// How much work a budgeted query may do before returning.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Budget {
+    /// Stop once this much time has passed. The clock is read every few cells, so the budget can be overshot slightly.
+    Time(Duration),
+    /// Stop after visiting this many cells, empty or not.
+    Cells(usize),
+}
+
+/// Where a budgeted query stopped. Unlike `QueryCursor` it does not borrow the grid, so it can be kept across frames
+/// while the grid changes; entities moving during the scan may then be missed or returned again.
+#[derive(Debug, Clone)]
+pub struct QueryToken {
+    sx: u32,
+    sy: u32,
+    ex: u32,
+    ey: u32,
+    layer: usize,
+    x: u32,
+    y: u32,
+    /// Ids that span several cells and were already returned.
+    seen: HashSet<u32>,
+}
+
+/// Number of cells visited between reads of the clock.
+const CLOCK_INTERVAL: usize = 64;
+
+impl Grid {
+    /// Retrieve entities in a region, stopping once `budget` is spent. Returns the ids found so far and a token to continue
+    /// with `resume_query`, or `None` if the region was fully scanned. At least one cell is visited per call.
+    pub fn query_budgeted(&self, query: &Query, budget: Budget) -> (Vec<u32>, Option<QueryToken>) {
+        let sx = query.x >> self.shift;
+        let sy = query.y >> self.shift;
+
+        let ex = query.x.saturating_add(query.width) >> self.shift;
+        let ey = query.y.saturating_add(query.height) >> self.shift;
+
+        let token = QueryToken {
+            sx,
+            sy,
+            ex,
+            ey,
+            layer: 0,
+            x: sx,
+            y: sy,
+            seen: HashSet::new(),
+        };
+        self.resume_query(token, budget)
+    }
+
+    /// Continue a budgeted query where it stopped, like `query_budgeted`. Ids spanning several cells are not returned twice
+    /// across calls, but the token must be resumed on the same grid with the same shift.
+    pub fn resume_query(
+        &self,
+        mut token: QueryToken,
+        budget: Budget,
+    ) -> (Vec<u32>, Option<QueryToken>) {
+        let start = Instant::now();
+        let mut result = Vec::new();
+        let layers = if self.has_statics { 2 } else { 1 };
+        let is_ideal = token.sx == token.ex && token.sy == token.ey;
+
+        let mut visited = 0;
+        while token.layer < layers {
+            let spent = match budget {
+                Budget::Time(limit) => {
+                    visited > 0 && visited % CLOCK_INTERVAL == 0 && start.elapsed() >= limit
+                }
+                Budget::Cells(limit) => visited > 0 && visited >= limit,
+            };
+            if spent {
+                return (result, Some(token));
+            }
+
+            let buckets = if token.layer == 0 {
+                &self.grid
+            } else {
+                &self.statics
+            };
+            let slot = buckets.slot(token.x, token.y);
+            for &id in buckets.iter(slot) {
+                if id & (1 << 31) != 0 || is_ideal {
+                    result.push(id & !(1 << 31));
+                } else if token.seen.insert(id) {
+                    result.push(id);
+                }
+            }
+            visited += 1;
+
+            // advance to the next cell, then to the next layer.
+            if token.x < token.ex {
+                token.x += 1;
+            } else if token.y < token.ey {
+                token.x = token.sx;
+                token.y += 1;
+            } else {
+                token.layer += 1;
+                token.x = token.sx;
+                token.y = token.sy;
+            }
+        }
+
+        // large entities are in no bucket, so they are checked once the cells are done.
+        if !self.large.is_empty() {
+            result.extend(self.large_in(token.sx, token.sy, token.ex, token.ey));
+        }
+        (result, None)
+    }
+}
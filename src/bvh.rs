@@ -0,0 +1,157 @@
+//! Bounding volume hierarchy for geometry that never moves.
+
+use crate::{overlaps, Entity, Query};
+
+const LEAF_SIZE: usize = 4;
+
+#[derive(Debug, Clone, Copy)]
+struct Bounds {
+    min_x: u64,
+    min_y: u64,
+    max_x: u64,
+    max_y: u64,
+}
+
+impl Bounds {
+    const EMPTY: Bounds = Bounds {
+        min_x: u64::MAX,
+        min_y: u64::MAX,
+        max_x: 0,
+        max_y: 0,
+    };
+
+    fn of(entity: &Entity) -> Self {
+        Self {
+            min_x: entity.x as u64,
+            min_y: entity.y as u64,
+            max_x: entity.x as u64 + entity.width as u64,
+            max_y: entity.y as u64 + entity.height as u64,
+        }
+    }
+
+    fn union(self, other: Bounds) -> Self {
+        Self {
+            min_x: self.min_x.min(other.min_x),
+            min_y: self.min_y.min(other.min_y),
+            max_x: self.max_x.max(other.max_x),
+            max_y: self.max_y.max(other.max_y),
+        }
+    }
+
+    fn overlaps(&self, query: &Query) -> bool {
+        self.min_x <= query.x as u64 + query.width as u64
+            && query.x as u64 <= self.max_x
+            && self.min_y <= query.y as u64 + query.height as u64
+            && query.y as u64 <= self.max_y
+    }
+}
+
+/// Nodes are laid out depth-first: a node's left child directly follows it.
+#[derive(Debug, Clone, Copy)]
+struct Node {
+    bounds: Bounds,
+    /// Index of the right child for inner nodes, or of the first entity for leaves.
+    offset: usize,
+    /// Number of entities in a leaf; zero for inner nodes.
+    count: usize,
+}
+
+/// Immutable bounding volume hierarchy. Built once from static geometry such as walls and terrain,
+/// it needs no per-frame maintenance and keeps that geometry out of the grid's buckets.
+/// Queries return only entities whose rectangles actually overlap the query.
+#[derive(Debug, Clone, Default)]
+pub struct StaticBvh {
+    nodes: Vec<Node>,
+    entities: Vec<Entity>,
+}
+
+impl StaticBvh {
+    /// Build a hierarchy over a set of rectangles.
+    pub fn build(entities: &[Entity]) -> Self {
+        let mut bvh = Self {
+            nodes: Vec::with_capacity(entities.len() / LEAF_SIZE * 2 + 1),
+            entities: entities.to_vec(),
+        };
+        if !bvh.entities.is_empty() {
+            bvh.build_node(0, bvh.entities.len());
+        }
+        bvh
+    }
+
+    fn build_node(&mut self, start: usize, end: usize) {
+        let bounds = self.entities[start..end]
+            .iter()
+            .fold(Bounds::EMPTY, |b, e| b.union(Bounds::of(e)));
+        let index = self.nodes.len();
+        self.nodes.push(Node {
+            bounds,
+            offset: start,
+            count: end - start,
+        });
+        if end - start <= LEAF_SIZE {
+            return;
+        }
+
+        // split at the median center along the longest side.
+        let mid = (start + end) / 2;
+        let slice = &mut self.entities[start..end];
+        if bounds.max_x - bounds.min_x >= bounds.max_y - bounds.min_y {
+            slice.select_nth_unstable_by_key(mid - start, |e| 2 * e.x as u64 + e.width as u64);
+        } else {
+            slice.select_nth_unstable_by_key(mid - start, |e| 2 * e.y as u64 + e.height as u64);
+        }
+
+        self.build_node(start, mid);
+        let right = self.nodes.len();
+        self.build_node(mid, end);
+        self.nodes[index].offset = right;
+        self.nodes[index].count = 0;
+    }
+
+    /// Get the number of entities in the hierarchy.
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    /// Check whether the hierarchy holds no entities.
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    /// Retrieve entities whose rectangles overlap a region.
+    pub fn query(&self, query: &Query) -> Vec<u32> {
+        let mut result = Vec::new();
+        if self.nodes.is_empty() {
+            return result;
+        }
+
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !node.bounds.overlaps(query) {
+                continue;
+            }
+            if node.count > 0 {
+                for entity in &self.entities[node.offset..node.offset + node.count] {
+                    if overlaps(entity, query) {
+                        result.push(entity.id);
+                    }
+                }
+            } else {
+                stack.push(node.offset);
+                stack.push(index + 1);
+            }
+        }
+        result
+    }
+
+    /// Retrieve entities whose rectangles contain a point.
+    pub fn query_point(&self, x: u32, y: u32) -> Vec<u32> {
+        self.query(&Query {
+            x,
+            y,
+            width: 0,
+            height: 0,
+        })
+    }
+}
@@ -0,0 +1,107 @@
+//! A list of an entity's cells without `unsafe` code, kept inline until it outgrows `FIXED_SIZE` and then moved to the heap.
+
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+
+use crate::FIXED_SIZE;
+
+/// The cells an entity is registered in, replacing `ArrayVec` with the `spill-cells` feature.
+/// The inline variant is large on purpose: it is what keeps small entities off the heap.
+#[derive(Clone)]
+#[allow(clippy::large_enum_variant)]
+pub(crate) enum CellList {
+    Inline {
+        cells: [(u32, u32); FIXED_SIZE],
+        len: usize,
+    },
+    Heap(Vec<(u32, u32)>),
+}
+
+impl CellList {
+    pub(crate) fn push(&mut self, cell: (u32, u32)) {
+        match self {
+            CellList::Inline { cells, len } if *len < FIXED_SIZE => {
+                cells[*len] = cell;
+                *len += 1;
+            }
+            CellList::Inline { cells, .. } => {
+                let mut heap = Vec::with_capacity(FIXED_SIZE * 2);
+                heap.extend_from_slice(cells);
+                heap.push(cell);
+                *self = CellList::Heap(heap);
+            }
+            CellList::Heap(heap) => heap.push(cell),
+        }
+    }
+
+    /// Empty the list, giving back its heap memory.
+    pub(crate) fn clear(&mut self) {
+        match self {
+            CellList::Inline { len, .. } => *len = 0,
+            CellList::Heap(_) => *self = Self::default(),
+        }
+    }
+
+    pub(crate) fn remove(&mut self, index: usize) -> (u32, u32) {
+        match self {
+            CellList::Inline { cells, len } => {
+                let cell = cells[..*len][index];
+                cells.copy_within(index + 1..*len, index);
+                *len -= 1;
+                cell
+            }
+            CellList::Heap(heap) => heap.remove(index),
+        }
+    }
+
+    pub(crate) fn retain(&mut self, mut keep: impl FnMut(&mut (u32, u32)) -> bool) {
+        match self {
+            CellList::Inline { cells, len } => {
+                let mut kept = 0;
+                for i in 0..*len {
+                    if keep(&mut cells[i]) {
+                        cells[kept] = cells[i];
+                        kept += 1;
+                    }
+                }
+                *len = kept;
+            }
+            CellList::Heap(heap) => heap.retain_mut(keep),
+        }
+    }
+}
+
+impl Default for CellList {
+    fn default() -> Self {
+        CellList::Inline {
+            cells: [(0, 0); FIXED_SIZE],
+            len: 0,
+        }
+    }
+}
+
+impl Deref for CellList {
+    type Target = [(u32, u32)];
+
+    fn deref(&self) -> &[(u32, u32)] {
+        match self {
+            CellList::Inline { cells, len } => &cells[..*len],
+            CellList::Heap(heap) => heap,
+        }
+    }
+}
+
+impl DerefMut for CellList {
+    fn deref_mut(&mut self) -> &mut [(u32, u32)] {
+        match self {
+            CellList::Inline { cells, len } => &mut cells[..*len],
+            CellList::Heap(heap) => heap,
+        }
+    }
+}
+
+impl fmt::Debug for CellList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
@@ -0,0 +1,183 @@
+//! A coarse occupancy level over blocks of cells, letting huge queries skip empty regions.
+
+use std::collections::HashMap;
+
+use crate::memory::map_bytes;
+use crate::{Grid, Query, QueryResults};
+
+/// Blocks are `1 << COARSE_SHIFT` cells wide and tall.
+const COARSE_SHIFT: u32 = 3;
+
+/// The number of ids stored in the cells of each non-empty block, counted separately for the dynamic and static layers.
+/// Counts may run high when an evicted id's cell is unknown, which only costs a visit to a block that is empty.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Coarse {
+    blocks: HashMap<(u32, u32), [u32; 2]>,
+}
+
+impl Coarse {
+    #[inline]
+    pub(crate) fn add(&mut self, (x, y): (u32, u32), is_static: bool) {
+        let block = (x >> COARSE_SHIFT, y >> COARSE_SHIFT);
+        self.blocks.entry(block).or_default()[is_static as usize] += 1;
+    }
+
+    #[inline]
+    pub(crate) fn remove(&mut self, (x, y): (u32, u32), is_static: bool) {
+        let block = (x >> COARSE_SHIFT, y >> COARSE_SHIFT);
+        if let Some(counts) = self.blocks.get_mut(&block) {
+            counts[is_static as usize] -= 1;
+            if *counts == [0, 0] {
+                self.blocks.remove(&block);
+            }
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.blocks.clear();
+    }
+
+    pub(crate) fn bytes_used(&self) -> usize {
+        map_bytes(&self.blocks)
+    }
+
+    pub(crate) fn shrink_to_fit(&mut self) {
+        self.blocks.shrink_to_fit();
+    }
+
+    pub(crate) fn clear_dynamic(&mut self) {
+        self.blocks.retain(|_, counts| {
+            counts[0] = 0;
+            counts[1] > 0
+        });
+    }
+}
+
+impl Grid {
+    /// Enable or disable the coarse occupancy level used by `query_coarse`. Enabling it counts the ids in every block of
+    /// 8×8 cells, iterating over every entry of the internal tables. While enabled, inserts and deletes keep the counts up to date.
+    pub fn set_coarse_level(&mut self, enabled: bool) {
+        if !enabled {
+            self.coarse = None;
+            return;
+        }
+        let mut coarse = Coarse::default();
+        for map in self.maps.entries.iter() {
+            for &cell in map.cells.iter() {
+                coarse.add(cell, map.is_static);
+            }
+        }
+        for entity in self.pinned.values() {
+            let sx = entity.x >> self.shift;
+            let sy = entity.y >> self.shift;
+            let ex = entity.x.saturating_add(entity.width) >> self.shift;
+            let ey = entity.y.saturating_add(entity.height) >> self.shift;
+            for y in sy..=ey {
+                for x in sx..=ex {
+                    coarse.add((x, y), true);
+                }
+            }
+        }
+        self.coarse = Some(coarse);
+    }
+
+    /// Retrieve entities in a region like `query`, skipping blocks of 8×8 cells the coarse level knows to be empty.
+    /// Meant for map-wide scans of sparse worlds. Falls back to `query` if the coarse level is disabled.
+    /// Since empty blocks are never read, ids of other cells sharing their buckets are not returned either.
+    pub fn query_coarse(&self, query: &Query) -> QueryResults {
+        let coarse = match &self.coarse {
+            Some(coarse) => coarse,
+            None => return self.query(query),
+        };
+        let sx = query.x >> self.shift;
+        let sy = query.y >> self.shift;
+
+        let ex = query.x.saturating_add(query.width) >> self.shift;
+        let ey = query.y.saturating_add(query.height) >> self.shift;
+
+        let mut result = self.results.take();
+        let mut visited = 0;
+        for by in sy >> COARSE_SHIFT..=ey >> COARSE_SHIFT {
+            for bx in sx >> COARSE_SHIFT..=ex >> COARSE_SHIFT {
+                if !coarse.blocks.contains_key(&(bx, by)) {
+                    continue;
+                }
+                visited += 1;
+                let (x0, y0) = (bx << COARSE_SHIFT, by << COARSE_SHIFT);
+                let (x1, y1) = (
+                    x0 | ((1 << COARSE_SHIFT) - 1),
+                    y0 | ((1 << COARSE_SHIFT) - 1),
+                );
+                self.collect_cells(
+                    sx.max(x0),
+                    sy.max(y0),
+                    ex.min(x1),
+                    ey.min(y1),
+                    usize::MAX,
+                    |_| true,
+                    &mut result,
+                );
+            }
+        }
+        // large entities are in no block, so look for them even if every block was skipped.
+        if !self.large.is_empty() {
+            result.extend(self.large_in(sx, sy, ex, ey));
+        }
+        // entities spanning several blocks were collected once per block, and large ones once more.
+        if visited > 1 || !self.large.is_empty() {
+            result.sort_unstable();
+            result.dedup();
+        }
+        QueryResults::new(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CellLayout, Entity, Grid, Query, FIXED_SIZE};
+
+    fn sorted(mut ids: Vec<u32>) -> Vec<u32> {
+        ids.sort_unstable();
+        ids
+    }
+
+    fn square(id: u32, x: u32, y: u32) -> Entity {
+        Entity {
+            id,
+            x,
+            y,
+            width: 10,
+            height: 10,
+        }
+    }
+
+    #[test]
+    fn coarse_queries_match_plain_queries() {
+        let mut grid = Grid::with_cell_layout(64, 4, FIXED_SIZE, CellLayout::Sparse);
+        grid.insert(&square(1, 10, 10)).unwrap();
+        grid.insert_static(&square(2, 3000, 40)).unwrap();
+        // enabling the level counts the entities inserted so far.
+        grid.set_coarse_level(true);
+        grid.insert(&square(3, 5000, 5000)).unwrap();
+        grid.insert_pinned(&square(4, 9000, 100)).unwrap();
+        grid.update(&square(1, 7000, 7000)).unwrap();
+        grid.delete(3);
+
+        let everything = Query {
+            x: 0,
+            y: 0,
+            width: 10_000,
+            height: 10_000,
+        };
+        assert_eq!(sorted(grid.query_coarse(&everything).into_vec()), [1, 2, 4]);
+        let corner = Query {
+            x: 0,
+            y: 0,
+            width: 100,
+            height: 100,
+        };
+        assert!(grid.query_coarse(&corner).is_empty());
+        grid.clear_dynamic();
+        assert_eq!(sorted(grid.query_coarse(&everything).into_vec()), [2, 4]);
+    }
+}
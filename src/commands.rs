@@ -0,0 +1,83 @@
+//! Deferred mutations for code that iterates query results.
+
+use crate::{Entity, Error, Grid};
+
+#[derive(Debug, Clone)]
+enum Command {
+    Insert(Entity),
+    InsertStatic(Entity),
+    Delete(u32),
+    Update(Entity),
+}
+
+/// A queue of inserts, deletes and updates recorded while the grid is borrowed, executed later in order by `apply`.
+#[derive(Debug, Clone, Default)]
+pub struct GridCommands {
+    commands: Vec<Command>,
+}
+
+impl GridCommands {
+    /// Create an empty buffer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue an insert.
+    pub fn insert(&mut self, entity: Entity) {
+        self.commands.push(Command::Insert(entity));
+    }
+
+    /// Queue an insert into the static layer.
+    pub fn insert_static(&mut self, entity: Entity) {
+        self.commands.push(Command::InsertStatic(entity));
+    }
+
+    /// Queue a delete.
+    pub fn delete(&mut self, id: u32) {
+        self.commands.push(Command::Delete(id));
+    }
+
+    /// Queue an update.
+    pub fn update(&mut self, entity: Entity) {
+        self.commands.push(Command::Update(entity));
+    }
+
+    /// Get the number of queued commands.
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    /// Check whether no commands are queued.
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Drop every queued command.
+    pub fn clear(&mut self) {
+        self.commands.clear();
+    }
+
+    /// Execute the queued commands in the order they were recorded, leaving the buffer empty.
+    /// If one fails, it is dropped and the commands after it stay queued.
+    pub fn apply(&mut self, grid: &mut Grid) -> Result<(), Error> {
+        let mut applied = 0;
+        let mut result = Ok(());
+        for command in self.commands.iter() {
+            applied += 1;
+            result = match command {
+                Command::Insert(entity) => grid.insert(entity),
+                Command::InsertStatic(entity) => grid.insert_static(entity),
+                Command::Delete(id) => {
+                    grid.delete(*id);
+                    Ok(())
+                }
+                Command::Update(entity) => grid.update(entity),
+            };
+            if result.is_err() {
+                break;
+            }
+        }
+        self.commands.drain(..applied);
+        result
+    }
+}
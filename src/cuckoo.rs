@@ -0,0 +1,173 @@
+//! Cuckoo-hashed alternative to `Table` that never lets two keys share an entry.
+
+use crate::{hash_u64, mix, vector_hash};
+
+/// Evictions tried before an insert gives up on the current layout and grows the table.
+const MAX_KICKS: usize = 64;
+
+/// A hash map with the same keys as `Table`, but which stores its keys so distinct keys never share an entry.
+/// Every key lives in one of two slots, so lookups check at most two entries and never allocate. Inserts that cannot
+/// find room after a bounded number of evictions double the table.
+/// 2D and scalar keys share one key space, so a table should only be used with one kind of key.
+#[derive(Debug, Clone)]
+pub struct CuckooTable<T> {
+    entries: Vec<Option<(u64, T)>>,
+    len: usize,
+}
+
+impl<T> CuckooTable<T> {
+    /// Create a new table with room for about `size` entries.
+    pub fn new(size: usize) -> Self {
+        let half = size.max(1).next_power_of_two();
+        Self {
+            entries: (0..half * 2).map(|_| None).collect(),
+            len: 0,
+        }
+    }
+
+    /// Get the number of stored entries.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Check whether the table is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Get the number of slots.
+    pub fn count(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// The two slots a key can live in, one in each half of the table.
+    #[inline(always)]
+    fn slots(&self, key: u64) -> [usize; 2] {
+        let half = self.entries.len() / 2;
+        let hash = mix(key);
+        [
+            (hash as u32 as usize) & (half - 1),
+            half + ((hash >> 32) as usize & (half - 1)),
+        ]
+    }
+
+    #[inline(always)]
+    fn find(&self, key: u64) -> Option<usize> {
+        self.slots(key)
+            .into_iter()
+            .find(|&slot| matches!(&self.entries[slot], Some((k, _)) if *k == key))
+    }
+
+    fn get(&self, key: u64) -> Option<&T> {
+        let slot = self.find(key)?;
+        self.entries[slot].as_ref().map(|(_, value)| value)
+    }
+
+    fn get_mut(&mut self, key: u64) -> Option<&mut T> {
+        let slot = self.find(key)?;
+        self.entries[slot].as_mut().map(|(_, value)| value)
+    }
+
+    fn insert(&mut self, key: u64, value: T) -> Option<T> {
+        if let Some(slot) = self.find(key) {
+            let (_, old) = self.entries[slot].replace((key, value))?;
+            return Some(old);
+        }
+        self.len += 1;
+        if let Err(homeless) = self.place((key, value)) {
+            self.grow(homeless);
+        }
+        None
+    }
+
+    /// Place a new entry, evicting entries to their other slot as needed. Returns the entry left homeless on failure.
+    fn place(&mut self, mut entry: (u64, T)) -> Result<(), (u64, T)> {
+        let mut slot = self.slots(entry.0)[0];
+        for _ in 0..MAX_KICKS {
+            entry = match self.entries[slot].replace(entry) {
+                None => return Ok(()),
+                Some(evicted) => evicted,
+            };
+            let [first, second] = self.slots(entry.0);
+            slot = if slot == first { second } else { first };
+        }
+        Err(entry)
+    }
+
+    /// Double the table and re-place every entry along with a homeless one, doubling again if that fails.
+    fn grow(&mut self, homeless: (u64, T)) {
+        let mut pending = vec![homeless];
+        loop {
+            let size = self.entries.len() * 2;
+            let old = std::mem::replace(&mut self.entries, (0..size).map(|_| None).collect());
+            pending.extend(old.into_iter().flatten());
+            let mut failed = None;
+            while let Some(entry) = pending.pop() {
+                if let Err(entry) = self.place(entry) {
+                    failed = Some(entry);
+                    break;
+                }
+            }
+            match failed {
+                None => return,
+                Some(entry) => pending.push(entry),
+            }
+        }
+    }
+
+    fn remove(&mut self, key: u64) -> Option<T> {
+        let slot = self.find(key)?;
+        self.len -= 1;
+        self.entries[slot].take().map(|(_, value)| value)
+    }
+
+    /// Get a reference to the value of a 2D key.
+    #[inline(always)]
+    pub fn get_vector(&self, x: u32, y: u32) -> Option<&T> {
+        self.get(vector_hash(x, y))
+    }
+
+    /// Get a mutable reference to the value of a 2D key.
+    #[inline(always)]
+    pub fn get_vector_mut(&mut self, x: u32, y: u32) -> Option<&mut T> {
+        self.get_mut(vector_hash(x, y))
+    }
+
+    /// Get a reference to the value of a scalar key.
+    #[inline(always)]
+    pub fn get_scalar(&self, s: u32) -> Option<&T> {
+        self.get(hash_u64(s as u64))
+    }
+
+    /// Get a mutable reference to the value of a scalar key.
+    #[inline(always)]
+    pub fn get_scalar_mut(&mut self, s: u32) -> Option<&mut T> {
+        self.get_mut(hash_u64(s as u64))
+    }
+
+    /// Set the value of a 2D key, returning the previous value.
+    pub fn insert_vector(&mut self, x: u32, y: u32, value: T) -> Option<T> {
+        self.insert(vector_hash(x, y), value)
+    }
+
+    /// Set the value of a scalar key, returning the previous value.
+    pub fn insert_scalar(&mut self, s: u32, value: T) -> Option<T> {
+        self.insert(hash_u64(s as u64), value)
+    }
+
+    /// Remove a 2D key, returning its value.
+    pub fn remove_vector(&mut self, x: u32, y: u32) -> Option<T> {
+        self.remove(vector_hash(x, y))
+    }
+
+    /// Remove a scalar key, returning its value.
+    pub fn remove_scalar(&mut self, s: u32) -> Option<T> {
+        self.remove(hash_u64(s as u64))
+    }
+
+    /// Remove every entry, keeping the allocated slots.
+    pub fn clear(&mut self) {
+        self.entries.iter_mut().for_each(|entry| *entry = None);
+        self.len = 0;
+    }
+}
@@ -0,0 +1,114 @@
+//! Resumable queries for scanning huge regions a batch at a time.
+
+use std::collections::HashSet;
+
+use crate::{Grid, Query};
+
+/// A query over a region that remembers how far it got, so a scan can be spread across several frames.
+/// Created by `Grid::query_cursor`.
+#[derive(Debug, Clone)]
+pub struct QueryCursor<'a> {
+    grid: &'a Grid,
+    sx: u32,
+    sy: u32,
+    ex: u32,
+    ey: u32,
+    is_ideal: bool,
+    layer: usize,
+    x: u32,
+    y: u32,
+    /// Index of the next id to read in the current cell.
+    offset: usize,
+    done: bool,
+    /// Number of large entities checked after the cells were scanned.
+    large: usize,
+    /// Ids that span several cells and were already returned.
+    seen: HashSet<u32>,
+}
+
+impl<'a> QueryCursor<'a> {
+    /// Get up to `max` more ids, continuing where the previous batch stopped.
+    /// Returns an empty batch once the whole region was scanned.
+    pub fn next_batch(&mut self, max: usize) -> Vec<u32> {
+        let mut batch = Vec::new();
+        let layers = if self.grid.has_statics { 2 } else { 1 };
+
+        while !self.done && batch.len() < max {
+            let buckets = if self.layer == 0 {
+                &self.grid.grid
+            } else {
+                &self.grid.statics
+            };
+            let slot = buckets.slot(self.x, self.y);
+            for id in buckets.iter(slot).skip(self.offset) {
+                if batch.len() == max {
+                    return batch;
+                }
+                self.offset += 1;
+                if id & (1 << 31) != 0 || self.is_ideal {
+                    batch.push(id & !(1 << 31));
+                } else if self.seen.insert(*id) {
+                    batch.push(*id);
+                }
+            }
+
+            // advance to the next cell, then to the next layer.
+            self.offset = 0;
+            if self.x < self.ex {
+                self.x += 1;
+            } else if self.y < self.ey {
+                self.x = self.sx;
+                self.y += 1;
+            } else if self.layer + 1 < layers {
+                self.layer += 1;
+                self.x = self.sx;
+                self.y = self.sy;
+            } else {
+                self.done = true;
+            }
+        }
+
+        let large = &self.grid.large;
+        while self.done && self.large < large.len() && batch.len() < max {
+            let id = large[self.large];
+            self.large += 1;
+            let (sx, sy, ex, ey) = self.grid.large_cells(id);
+            if sx <= self.ex && self.sx <= ex && sy <= self.ey && self.sy <= ey {
+                batch.push(id);
+            }
+        }
+        batch
+    }
+
+    /// Check whether the whole region was scanned.
+    pub fn is_done(&self) -> bool {
+        self.done && self.large == self.grid.large.len()
+    }
+}
+
+impl Grid {
+    /// Start a resumable query over a region. The cursor borrows the grid, so the grid cannot change mid-scan.
+    pub fn query_cursor(&self, query: &Query) -> QueryCursor<'_> {
+        let sx = query.x >> self.shift;
+        let sy = query.y >> self.shift;
+
+        let ex = query.x.saturating_add(query.width) >> self.shift;
+        let ey = query.y.saturating_add(query.height) >> self.shift;
+
+        QueryCursor {
+            grid: self,
+            sx,
+            sy,
+            ex,
+            ey,
+            is_ideal: sx == ex && sy == ey,
+            layer: 0,
+            x: sx,
+            y: sy,
+            offset: 0,
+            done: false,
+            large: 0,
+            seen: HashSet::new(),
+        }
+    }
+}
@@ -0,0 +1,85 @@
+//! Debug output for `Grid`: a one-line summary by default and an exhaustive dump on request.
+
+use std::fmt;
+
+use crate::{Buckets, Grid};
+
+impl Buckets {
+    /// Get the number of non-empty slots and the number of ids in the fullest one.
+    fn occupancy(&self) -> (usize, usize) {
+        self.occupied_slots()
+            .map(|slot| self.len(slot))
+            .fold((0, 0), |(slots, max), len| (slots + 1, max.max(len)))
+    }
+}
+
+impl fmt::Debug for Grid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let entities = self.entities().count();
+        let (mut occupied, mut max_bucket) = self.grid.occupancy();
+        if self.has_statics {
+            let (statics, max) = self.statics.occupancy();
+            occupied += statics;
+            max_bucket = max_bucket.max(max);
+        }
+        f.debug_struct("Grid")
+            .field("entities", &entities)
+            .field("occupied_cells", &occupied)
+            .field("max_bucket", &max_bucket)
+            .field("shift", &self.shift)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Every field of a grid, formatted like a derived `Debug`.
+struct Dump<'a>(&'a Grid);
+
+impl fmt::Debug for Dump<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let grid = self.0;
+        f.debug_struct("Grid")
+            .field("grid", &grid.grid)
+            .field("statics", &grid.statics)
+            .field("has_statics", &grid.has_statics)
+            .field("maps", &grid.maps)
+            .field("shift", &grid.shift)
+            .field("overflow", &grid.overflow)
+            .field("changes", &grid.changes)
+            .field("scratch", &grid.scratch)
+            .field("entered", &grid.entered)
+            .field("left", &grid.left)
+            .field("journal", &grid.journal)
+            .field("tick", &grid.tick)
+            .field("seen", &grid.seen)
+            .field("results", &grid.results)
+            .field("profile", &grid.profile)
+            .field("frames", &grid.frames)
+            .field("pinned", &grid.pinned)
+            .field("shapes", &grid.shapes)
+            .field("polygon_test", &grid.polygon_test)
+            .field("groups", &grid.groups)
+            .field("templates", &grid.templates)
+            .field("expiries", &grid.expiries)
+            .field("history", &grid.history)
+            .field("delta", &grid.delta)
+            .field("watermark", &grid.watermark)
+            .field("hot", &grid.hot)
+            .field("coarse", &grid.coarse)
+            .field("large", &grid.large)
+            .field("large_threshold", &grid.large_threshold)
+            .field("bounds", &grid.bounds)
+            .field("bounds_policy", &grid.bounds_policy)
+            .field("max_size", &grid.max_size)
+            .field("origin", &grid.origin)
+            .field("recorder", &grid.recorder)
+            .finish()
+    }
+}
+
+impl Grid {
+    /// Format every field and internal table of the grid in full.
+    /// This can be megabytes for large grids; `{:?}` only prints a summary.
+    pub fn dump(&self) -> String {
+        format!("{:#?}", Dump(self))
+    }
+}
@@ -0,0 +1,162 @@
+//! Generation-stamped hash set for deduplicating query results without scanning them.
+
+use std::sync::{Mutex, MutexGuard};
+
+use crate::memory::vec_bytes;
+use crate::{mix, Grid};
+
+/// Slots the set starts with; it doubles whenever a query fills half of them.
+const MIN_SLOTS: usize = 64;
+
+/// An open-addressing set of `(id, generation)` slots. A slot holds an id seen during the current query if its
+/// generation is the current one, so starting a query empties the set without touching the slots. The number of slots
+/// follows the most unique ids a query produced, not the range of the ids.
+#[derive(Debug, Default)]
+pub(crate) struct Seen {
+    slots: Vec<(u32, u32)>,
+    generation: u32,
+    len: usize,
+}
+
+impl Seen {
+    /// Start a new query, forgetting every id seen so far.
+    pub(crate) fn begin(&mut self) {
+        self.len = 0;
+        self.generation = self.generation.wrapping_add(1);
+        if self.generation == 0 {
+            self.slots.fill((0, 0));
+            self.generation = 1;
+        }
+    }
+
+    /// Mark an id as seen, returning whether it was not seen yet during this query.
+    #[inline]
+    pub(crate) fn insert(&mut self, id: u32) -> bool {
+        if (self.len + 1) * 2 > self.slots.len() {
+            self.grow((self.len + 1) * 2);
+        }
+        let mask = self.slots.len() - 1;
+        let mut slot = mix(id as u64) as usize & mask;
+        loop {
+            let (stored, generation) = self.slots[slot];
+            if generation != self.generation {
+                self.slots[slot] = (id, self.generation);
+                self.len += 1;
+                return true;
+            }
+            if stored == id {
+                return false;
+            }
+            slot = (slot + 1) & mask;
+        }
+    }
+
+    /// Grow to at least `slots` slots, moving the ids seen during the current query.
+    fn grow(&mut self, slots: usize) {
+        let slots = slots.max(MIN_SLOTS).next_power_of_two();
+        if slots <= self.slots.len() {
+            return;
+        }
+        let old = std::mem::replace(&mut self.slots, vec![(0, 0); slots]);
+        self.len = 0;
+        for (id, generation) in old {
+            if generation == self.generation {
+                self.insert(id);
+            }
+        }
+    }
+}
+
+/// The grid's dedup table. Queries only take `&self`, so the table sits behind a lock;
+/// a query that finds it busy falls back to deduplicating by scanning its results.
+#[derive(Debug, Default)]
+pub(crate) struct SeenTable(Option<Mutex<Seen>>);
+
+impl SeenTable {
+    pub(crate) fn enabled() -> Self {
+        Self(Some(Mutex::default()))
+    }
+
+    /// Grow the table to hold `ids` unique ids per query without growing mid-query.
+    pub(crate) fn reserve(&mut self, ids: usize) {
+        if let Some(seen) = &mut self.0 {
+            let seen = seen
+                .get_mut()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            seen.grow(ids.saturating_mul(2));
+        }
+    }
+
+    pub(crate) fn lock(&self) -> Option<MutexGuard<'_, Seen>> {
+        self.0.as_ref()?.try_lock().ok()
+    }
+
+    /// Get the heap memory taken by the slots, waiting for a running query to release them.
+    pub(crate) fn bytes_used(&self) -> usize {
+        self.0.as_ref().map_or(0, |seen| {
+            let seen = seen.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            vec_bytes(&seen.slots)
+        })
+    }
+}
+
+// the table holds no state that outlives a query, so a clone starts with an empty one.
+impl Clone for SeenTable {
+    fn clone(&self) -> Self {
+        match self.0 {
+            Some(_) => Self::enabled(),
+            None => Self(None),
+        }
+    }
+}
+
+impl Grid {
+    /// Enable or disable the dedup table, which is enabled by default. While enabled, queries deduplicate their results
+    /// through a hash set sized for the most unique ids a query returned. Disabled, they scan or sort their results
+    /// instead, which saves the table's memory on grids whose queries return few entities.
+    pub fn set_dedup_table(&mut self, enabled: bool) {
+        self.seen = if enabled {
+            SeenTable::enabled()
+        } else {
+            SeenTable::default()
+        };
+    }
+
+    /// Check whether the dedup table is enabled.
+    pub fn dedup_table(&self) -> bool {
+        self.seen.0.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Entity, Grid, Query};
+
+    #[test]
+    fn queries_deduplicate_with_and_without_the_table() {
+        let mut grid = Grid::new(64, 4);
+        assert!(grid.dedup_table());
+        for id in 0..10 {
+            grid.insert(&Entity {
+                id,
+                x: id * 3,
+                y: 0,
+                width: 40,
+                height: 40,
+            })
+            .unwrap();
+        }
+        let query = Query {
+            x: 0,
+            y: 0,
+            width: 64,
+            height: 64,
+        };
+        for enabled in [true, false] {
+            grid.set_dedup_table(enabled);
+            let mut ids = grid.query(&query).into_vec();
+            ids.sort_unstable();
+            assert_eq!(ids, (0..10).collect::<Vec<_>>());
+        }
+    }
+}
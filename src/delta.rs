@@ -0,0 +1,238 @@
+//! Compact encoding of the spatial changes since a tick, for relaying grid state over the network.
+
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+
+use crate::memory::map_bytes;
+use crate::{Entity, Grid};
+
+/// Opcodes, each followed by its fields as little-endian `u32`s. A delta opens with the sender's tick as a `u64`.
+mod code {
+    pub(crate) const UPSERT: u8 = 1;
+    pub(crate) const UPSERT_STATIC: u8 = 2;
+    pub(crate) const DELETE: u8 = 3;
+    pub(crate) const CLEAR: u8 = 4;
+}
+
+/// The last tick each entity was inserted, moved or deleted at, and the last tick the grid was cleared at.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct DeltaLog {
+    ticks: HashMap<u32, u64>,
+    cleared: Option<u64>,
+}
+
+impl DeltaLog {
+    #[inline]
+    pub(crate) fn touch(&mut self, id: u32, tick: u64) {
+        self.ticks.insert(id, tick);
+    }
+
+    pub(crate) fn clear(&mut self, tick: u64) {
+        self.ticks.clear();
+        self.cleared = Some(tick);
+    }
+
+    pub(crate) fn bytes_used(&self) -> usize {
+        map_bytes(&self.ticks)
+    }
+}
+
+impl Grid {
+    /// Enable or disable delta tracking. While enabled, the grid remembers the tick at which each entity last changed,
+    /// so `encode_delta` can describe the changes since any tick. Disabling it forgets them.
+    pub fn set_delta_tracking(&mut self, enabled: bool) {
+        self.delta = enabled.then(DeltaLog::default);
+    }
+
+    /// Forget entities that last changed at or before `tick`, once no receiver is that far behind. Deleted entities are
+    /// remembered until then, so their deletion can be sent.
+    pub fn forget_deltas_before(&mut self, tick: u64) {
+        if let Some(delta) = &mut self.delta {
+            delta.ticks.retain(|_, &mut changed| changed > tick);
+        }
+    }
+
+    /// Write the rectangles of the entities inserted, moved or deleted after `since_tick`, followed by the current `tick`.
+    /// Each entity is written once with its current rectangle, or as deleted, in 21 bytes or fewer. If the grid was cleared
+    /// since, the delta resets the receiver and carries every entity. Pinned entities, layers and tags are not sent.
+    /// Without delta tracking the changes are unknown, so nothing is written and an `io::ErrorKind::Unsupported` error is returned.
+    pub fn encode_delta(&self, since_tick: u64, mut writer: impl Write) -> io::Result<()> {
+        let delta = match &self.delta {
+            Some(delta) => delta,
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "delta tracking is not enabled",
+                ))
+            }
+        };
+        let mut out = Vec::new();
+        out.extend_from_slice(&self.tick.to_le_bytes());
+
+        let mut ids: Vec<u32> = if delta.cleared.is_some_and(|tick| tick > since_tick) {
+            out.push(code::CLEAR);
+            self.entities()
+                .filter(|(entity, _)| !self.pinned.contains_key(&entity.id))
+                .map(|(entity, _)| entity.id)
+                .collect()
+        } else {
+            delta
+                .ticks
+                .iter()
+                .filter(|&(_, &changed)| changed > since_tick)
+                .map(|(&id, _)| id)
+                .collect()
+        };
+        ids.sort_unstable();
+
+        for id in ids {
+            let map = self.maps.get_scalar(id);
+            if !map.is_registered() || map.id != id {
+                out.push(code::DELETE);
+                out.extend_from_slice(&id.to_le_bytes());
+                continue;
+            }
+            out.push(if map.is_static {
+                code::UPSERT_STATIC
+            } else {
+                code::UPSERT
+            });
+            for field in [id, map.x, map.y, map.width, map.height] {
+                out.extend_from_slice(&field.to_le_bytes());
+            }
+        }
+        writer.write_all(&out)
+    }
+
+    /// Apply a delta written by `encode_delta`, inserting, moving and deleting entities to match the sender,
+    /// and return the sender's tick to pass as `since_tick` next time. Entities keep their layer and tags when they move.
+    /// Fails on an unknown opcode or a truncated delta, or with the grid's error if an insert fails; the operations read
+    /// before the failure stay applied.
+    pub fn apply_delta(&mut self, mut reader: impl Read) -> io::Result<u64> {
+        let mut tick = [0; 8];
+        reader.read_exact(&mut tick)?;
+        let read_u32 = |reader: &mut dyn Read| -> io::Result<u32> {
+            let mut bytes = [0; 4];
+            reader.read_exact(&mut bytes)?;
+            Ok(u32::from_le_bytes(bytes))
+        };
+
+        loop {
+            let mut op = [0];
+            if reader.read(&mut op)? == 0 {
+                return Ok(u64::from_le_bytes(tick));
+            }
+            match op[0] {
+                code::UPSERT | code::UPSERT_STATIC => {
+                    let mut fields = [0; 5];
+                    for field in fields.iter_mut() {
+                        *field = read_u32(&mut reader)?;
+                    }
+                    let [id, x, y, width, height] = fields;
+                    let entity = Entity {
+                        id,
+                        x,
+                        y,
+                        width,
+                        height,
+                    };
+                    let is_static = op[0] == code::UPSERT_STATIC;
+                    let map = self.maps.get_scalar(id);
+                    let known = map.is_registered() && map.id == id;
+                    let result = if known && map.is_static == is_static {
+                        self.update(&entity)
+                    } else {
+                        if known {
+                            self.delete(id);
+                        }
+                        if is_static {
+                            self.insert_static(&entity)
+                        } else {
+                            self.insert(&entity)
+                        }
+                    };
+                    result.map_err(io::Error::other)?;
+                }
+                code::DELETE => {
+                    let id = read_u32(&mut reader)?;
+                    let map = self.maps.get_scalar(id);
+                    if map.is_registered() && map.id == id {
+                        self.delete(id);
+                    }
+                }
+                code::CLEAR => self.clear(),
+                code => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("unknown delta opcode {}", code),
+                    ))
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use crate::{Entity, Grid, Query};
+
+    fn square(id: u32, x: u32) -> Entity {
+        Entity {
+            id,
+            x,
+            y: 0,
+            width: 4,
+            height: 4,
+        }
+    }
+
+    fn sorted_ids(grid: &Grid) -> Vec<u32> {
+        let mut ids = grid
+            .query(&Query {
+                x: 0,
+                y: 0,
+                width: 1000,
+                height: 100,
+            })
+            .into_vec();
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    }
+
+    #[test]
+    fn encode_without_tracking_fails() {
+        let grid = Grid::new(64, 4);
+        let mut bytes = Vec::new();
+        let err = grid.encode_delta(0, &mut bytes).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+        assert!(bytes.is_empty());
+    }
+
+    #[test]
+    fn deltas_replay_inserts_moves_and_deletes() {
+        let mut sender = Grid::new(64, 4);
+        sender.set_delta_tracking(true);
+        let mut receiver = Grid::new(64, 4);
+
+        sender.set_tick(1);
+        sender.insert(&square(1, 10)).unwrap();
+        sender.insert(&square(2, 20)).unwrap();
+        sender.insert_static(&square(3, 30)).unwrap();
+        let mut bytes = Vec::new();
+        sender.encode_delta(0, &mut bytes).unwrap();
+        assert_eq!(receiver.apply_delta(&bytes[..]).unwrap(), 1);
+        assert_eq!(sorted_ids(&receiver), [1, 2, 3]);
+
+        sender.set_tick(2);
+        sender.update(&square(1, 500)).unwrap();
+        sender.delete(2);
+        bytes.clear();
+        sender.encode_delta(1, &mut bytes).unwrap();
+        assert_eq!(receiver.apply_delta(&bytes[..]).unwrap(), 2);
+        assert_eq!(sorted_ids(&receiver), [1, 3]);
+        assert_eq!(receiver.query_point(501, 1).into_vec(), [1]);
+    }
+}
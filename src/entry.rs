@@ -0,0 +1,81 @@
+//! Entry and iteration APIs for using `Table` as a fixed-size map of per-cell or per-entity data.
+
+use crate::{at_mut, hash_u64, vector_hash, Table};
+
+/// A handle to one entry of a `Table`. Entries equal to `T::default()` count as vacant.
+/// Distinct keys can share an entry, so the handle is to the entry a key maps to rather than to the key itself.
+#[derive(Debug)]
+pub struct TableEntry<'a, T> {
+    entry: &'a mut T,
+}
+
+impl<T: Default + Clone + PartialEq> Table<T> {
+    /// Get the entry a 2D key maps to.
+    pub fn entry_vector(&mut self, x: u32, y: u32) -> TableEntry<'_, T> {
+        let idx = self.index(vector_hash(x, y));
+        TableEntry {
+            entry: at_mut(&mut self.entries, idx),
+        }
+    }
+
+    /// Get the entry a scalar key maps to.
+    pub fn entry_scalar(&mut self, s: u32) -> TableEntry<'_, T> {
+        let idx = self.index(hash_u64(s as u64));
+        TableEntry {
+            entry: at_mut(&mut self.entries, idx),
+        }
+    }
+
+    /// Iterate over the occupied entries, those other than `T::default()`, with their slot indices.
+    /// This visits every slot of the table.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &T)> + '_ {
+        let vacant = T::default();
+        self.entries
+            .iter()
+            .enumerate()
+            .filter(move |(_, entry)| **entry != vacant)
+    }
+
+    /// Mutably iterate over the occupied entries with their slot indices. This visits every slot of the table.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (usize, &mut T)> + '_ {
+        let vacant = T::default();
+        self.entries
+            .iter_mut()
+            .enumerate()
+            .filter(move |(_, entry)| **entry != vacant)
+    }
+}
+
+impl<'a, T: Default + PartialEq> TableEntry<'a, T> {
+    /// Check whether the entry holds a value other than the default.
+    pub fn is_occupied(&self) -> bool {
+        *self.entry != T::default()
+    }
+
+    /// Fill the entry with `f()` if it is vacant, then get a reference to its value.
+    pub fn or_insert_with(self, f: impl FnOnce() -> T) -> &'a mut T {
+        if !self.is_occupied() {
+            *self.entry = f();
+        }
+        self.entry
+    }
+
+    /// Get a reference to the entry's value, which is the default if the entry is vacant.
+    pub fn or_default(self) -> &'a mut T {
+        self.entry
+    }
+
+    /// Modify the value if the entry is occupied.
+    pub fn and_modify(self, f: impl FnOnce(&mut T)) -> Self {
+        if self.is_occupied() {
+            f(self.entry);
+        }
+        self
+    }
+
+    /// Reset the entry to the default value, returning the previous value if it was occupied.
+    pub fn remove(self) -> Option<T> {
+        let value = std::mem::take(self.entry);
+        (value != T::default()).then_some(value)
+    }
+}
@@ -0,0 +1,105 @@
+//! Lifetimes for short-lived entities such as projectiles, decals and pings, expired in one sweep.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::mem::size_of;
+
+use crate::memory::map_bytes;
+use crate::{Entity, Error, Grid};
+
+/// The tick each expiring entity lapses at, with a heap of deadlines to find the lapsed ones without a scan.
+/// Deadlines whose entity was deleted or given another expiry stay in the heap until they are popped.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Expiries {
+    ticks: HashMap<u32, u64>,
+    deadlines: BinaryHeap<Reverse<(u64, u32)>>,
+}
+
+impl Expiries {
+    #[inline]
+    pub(crate) fn remove(&mut self, id: u32) {
+        if !self.ticks.is_empty() {
+            self.ticks.remove(&id);
+        }
+    }
+
+    /// Give back an expiry taken by deleting the entity, whose deadline is still in the heap.
+    pub(crate) fn restore(&mut self, id: u32, tick: u64) {
+        self.ticks.insert(id, tick);
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.ticks.clear();
+        self.deadlines.clear();
+    }
+
+    /// Keep the expiries of the entities `keep` passes.
+    pub(crate) fn retain(&mut self, mut keep: impl FnMut(u32) -> bool) {
+        self.ticks.retain(|&id, _| keep(id));
+    }
+
+    /// Drop the deadlines that no longer apply and release spare capacity.
+    pub(crate) fn compact(&mut self) {
+        let ticks = &self.ticks;
+        self.deadlines
+            .retain(|&Reverse((tick, id))| ticks.get(&id) == Some(&tick));
+        self.ticks.shrink_to_fit();
+        self.deadlines.shrink_to_fit();
+    }
+
+    pub(crate) fn bytes_used(&self) -> usize {
+        map_bytes(&self.ticks) + self.deadlines.capacity() * size_of::<Reverse<(u64, u32)>>()
+    }
+}
+
+impl Grid {
+    /// Insert an entity that is removed by the first `expire` call at or after tick `expires_at`.
+    /// Ticks are the caller's own clock, unrelated to the undo journal's ticks.
+    pub fn insert_expiring(&mut self, entity: &Entity, expires_at: u64) -> Result<(), Error> {
+        let result = self.insert(entity);
+        // an insert that failed part way can still leave the entity in some cells, which then expire too.
+        if !result.as_ref().is_err_and(Error::is_rejection) {
+            self.set_expiry(entity.id, Some(expires_at)).ok();
+        }
+        result
+    }
+
+    /// Give an entity a tick to expire at, or `None` to keep it until it is deleted. Like tags, `update` keeps the expiry
+    /// and `delete` removes it. Pinned entities cannot expire.
+    pub fn set_expiry(&mut self, id: u32, expires_at: Option<u64>) -> Result<(), Error> {
+        let map = self.maps.get_scalar(id);
+        if !map.is_registered() || map.id != id {
+            return Err(Error::NotFound(id));
+        }
+        match expires_at {
+            Some(tick) => {
+                self.expiries.ticks.insert(id, tick);
+                self.expiries.deadlines.push(Reverse((tick, id)));
+            }
+            None => self.expiries.remove(id),
+        }
+        Ok(())
+    }
+
+    /// Get the tick an entity expires at.
+    pub fn expiry(&self, id: u32) -> Option<u64> {
+        self.expiries.ticks.get(&id).copied()
+    }
+
+    /// Delete every entity whose expiry is at or before `now`, returning their ids in order of expiry.
+    /// Only the lapsed deadlines are visited, so this is cheap when few entities expire.
+    pub fn expire(&mut self, now: u64) -> Vec<u32> {
+        let mut expired = Vec::new();
+        while let Some(&Reverse((tick, id))) = self.expiries.deadlines.peek() {
+            if tick > now {
+                break;
+            }
+            self.expiries.deadlines.pop();
+            if self.expiries.ticks.get(&id) == Some(&tick) {
+                self.delete(id);
+                expired.push(id);
+            }
+        }
+        expired
+    }
+}
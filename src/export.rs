@@ -0,0 +1,188 @@
+//! Streaming export and import of a grid's entities in a framed binary format, independent of any serialization crate.
+
+use std::io::{self, Read, Write};
+
+use crate::{Entity, Grid, Layer, Meta, MAX_LAYERS};
+
+const MAGIC: &[u8; 4] = b"SGRD";
+const VERSION: u8 = 1;
+const PAYLOAD: usize = 40;
+/// Flag marking an entity in a group.
+const IN_GROUP: u8 = 1;
+
+impl Grid {
+    /// Write every entity with its rectangle, table, layer, priority, group, tags and value, returning how many were written.
+    /// Shapes, expiry ticks and the grid's configuration are not written. Each frame is written as it is encoded,
+    /// so wrap unbuffered writers in a `BufWriter`.
+    ///
+    /// The stream opens with the bytes `SGRD` and a format version byte, currently 1. Each entity follows as a frame:
+    /// its payload length as a little-endian `u32`, then the payload. A frame of length zero ends the stream, so more
+    /// data can follow it on the same pipe. Numbers in the 40-byte version 1 payload are little-endian:
+    ///
+    /// | bytes  | field                                               |
+    /// |--------|-----------------------------------------------------|
+    /// | 0..20  | id, x, y, width and height as `u32`s                |
+    /// | 20     | table: 0 dynamic, 1 static, 2 pinned                |
+    /// | 21     | layer                                               |
+    /// | 22     | priority                                            |
+    /// | 23     | flags: bit 0 is set if the entity is in a group     |
+    /// | 24..28 | group as a `u32`, zero without one                  |
+    /// | 28..36 | tags as a `u64`                                     |
+    /// | 36..40 | value as an `f32`                                   |
+    ///
+    /// Readers skip payload bytes past the fields they know, so later versions can append fields.
+    pub fn export(&self, mut writer: impl Write) -> io::Result<usize> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION])?;
+        let mut count = 0;
+        let mut frame = Vec::with_capacity(4 + PAYLOAD);
+        for (entity, layer) in self.entities() {
+            let meta = match layer {
+                Layer::Pinned => Meta::default(),
+                _ => self.maps.get_scalar(entity.id).meta,
+            };
+            frame.clear();
+            frame.extend_from_slice(&(PAYLOAD as u32).to_le_bytes());
+            for field in [entity.id, entity.x, entity.y, entity.width, entity.height] {
+                frame.extend_from_slice(&field.to_le_bytes());
+            }
+            let table = match layer {
+                Layer::Dynamic => 0,
+                Layer::Static => 1,
+                Layer::Pinned => 2,
+            };
+            let flags = meta.group.is_some() as u8 * IN_GROUP;
+            frame.extend_from_slice(&[table, meta.layer, meta.priority, flags]);
+            frame.extend_from_slice(&meta.group.unwrap_or(0).to_le_bytes());
+            frame.extend_from_slice(&meta.tags.to_le_bytes());
+            frame.extend_from_slice(&meta.value.to_le_bytes());
+            writer.write_all(&frame)?;
+            count += 1;
+        }
+        writer.write_all(&0u32.to_le_bytes())?;
+        Ok(count)
+    }
+
+    /// Insert the entities of a stream written by `export`, reading up to its end frame, and return how many were read.
+    /// Fails on a bad header, a truncated frame or an unknown table, or with the grid's error if an insert fails;
+    /// the entities read before the failure stay inserted.
+    pub fn import(&mut self, mut reader: impl Read) -> io::Result<usize> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+        let mut header = [0; 5];
+        reader.read_exact(&mut header)?;
+        if &header[..4] != MAGIC {
+            return Err(invalid("not a grid export".to_string()));
+        }
+        if header[4] == 0 || header[4] > VERSION {
+            return Err(invalid(format!("unsupported export version {}", header[4])));
+        }
+
+        let mut count = 0;
+        let mut payload = [0; PAYLOAD];
+        loop {
+            let mut length = [0; 4];
+            reader.read_exact(&mut length)?;
+            let length = u32::from_le_bytes(length) as usize;
+            if length == 0 {
+                return Ok(count);
+            }
+            if length < PAYLOAD {
+                return Err(invalid(format!("frame of {} bytes is too short", length)));
+            }
+            // only the known fields are buffered, so a corrupt length cannot make the reader allocate it.
+            reader.read_exact(&mut payload)?;
+            let rest = (length - PAYLOAD) as u64;
+            if io::copy(&mut (&mut reader).take(rest), &mut io::sink())? < rest {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+
+            let field =
+                |i: usize| u32::from_le_bytes(payload[i * 4..i * 4 + 4].try_into().unwrap());
+            let entity = Entity {
+                id: field(0),
+                x: field(1),
+                y: field(2),
+                width: field(3),
+                height: field(4),
+            };
+            let [table, layer, priority, flags] = payload[20..24] else {
+                unreachable!()
+            };
+            let group = (flags & IN_GROUP != 0).then(|| field(6));
+            let tags = u64::from_le_bytes(payload[28..36].try_into().unwrap());
+            let value = f32::from_bits(field(9));
+            if layer >= MAX_LAYERS {
+                return Err(invalid(format!("layer {} out of range", layer)));
+            }
+
+            let result = match table {
+                0 => self.insert(&entity),
+                1 => self.insert_static(&entity),
+                2 => self.insert_pinned(&entity),
+                table => return Err(invalid(format!("unknown table {}", table))),
+            };
+            result.map_err(io::Error::other)?;
+            if table != 2 {
+                let map = self.maps.get_scalar_mut(entity.id);
+                if map.is_registered() && map.id == entity.id {
+                    map.meta.layer = layer;
+                    map.meta.priority = priority;
+                    map.meta.tags = tags;
+                    map.meta.value = value;
+                    if group.is_some() {
+                        self.set_group(entity.id, group).map_err(io::Error::other)?;
+                    }
+                }
+            }
+            count += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use crate::{Entity, Grid, Query};
+
+    fn exported() -> Vec<u8> {
+        let mut grid = Grid::new(64, 4);
+        grid.insert(&Entity {
+            id: 1,
+            x: 10,
+            y: 10,
+            width: 5,
+            height: 5,
+        })
+        .unwrap();
+        let mut bytes = Vec::new();
+        grid.export(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn import_skips_unknown_payload_bytes() {
+        let mut bytes = exported();
+        // grow the frame by eight bytes a later version could have appended.
+        bytes[5..9].copy_from_slice(&48u32.to_le_bytes());
+        bytes.splice(49..49, [0xff; 8]);
+        let mut grid = Grid::new(64, 4);
+        assert_eq!(grid.import(&bytes[..]).unwrap(), 1);
+        let query = Query {
+            x: 0,
+            y: 0,
+            width: 20,
+            height: 20,
+        };
+        assert_eq!(grid.query(&query).into_vec(), [1]);
+    }
+
+    #[test]
+    fn import_rejects_truncated_huge_frame() {
+        let mut bytes = exported();
+        bytes[5..9].copy_from_slice(&u32::MAX.to_le_bytes());
+        bytes.truncate(49);
+        let err = Grid::new(64, 4).import(&bytes[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
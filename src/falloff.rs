@@ -0,0 +1,72 @@
+//! Range queries weighting each entity by its distance, such as for sound attenuation or area damage.
+
+use crate::{Entity, Grid, Query};
+
+/// How a weight drops from 1 at the center to 0 at the edge of the radius.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Falloff {
+    /// `1 - d / radius`.
+    Linear,
+    /// `(1 - d / radius)²`, dropping quickly near the center and flattening towards the edge.
+    Quadratic,
+}
+
+impl Falloff {
+    /// Get the weight at a fraction of the radius, from 0 at the center to 1 at the edge.
+    pub fn weight(self, fraction: f32) -> f32 {
+        let linear = (1.0 - fraction).clamp(0.0, 1.0);
+        match self {
+            Falloff::Linear => linear,
+            Falloff::Quadratic => linear * linear,
+        }
+    }
+}
+
+impl Grid {
+    /// Retrieve the entities within `radius` of a point, each with a weight from its distance, in no particular order.
+    /// The distance is measured to the closest point of the entity's rectangle, so an entity containing the point weighs 1.
+    pub fn query_weighted(
+        &self,
+        cx: u32,
+        cy: u32,
+        radius: u32,
+        falloff: Falloff,
+    ) -> Vec<(u32, f32)> {
+        let region = Query::around_point(cx, cy, radius);
+        let mut ids = self.query(&region).into_vec();
+        // repeated ids from cells sharing a bucket are weighed once.
+        ids.sort_unstable();
+        ids.dedup();
+
+        let limit = radius as u128 * radius as u128;
+        ids.into_iter()
+            .filter_map(|id| {
+                let distance = distance_squared(&self.rect(id)?, (cx, cy));
+                if distance > limit {
+                    return None;
+                }
+                let fraction = if radius == 0 {
+                    0.0
+                } else {
+                    ((distance as f64).sqrt() / radius as f64) as f32
+                };
+                Some((id, falloff.weight(fraction)))
+            })
+            .collect()
+    }
+}
+
+/// Get the squared distance from a point to the closest point of a rectangle, which is zero inside it.
+pub(crate) fn distance_squared(entity: &Entity, (x, y): (u32, u32)) -> u128 {
+    let gap = |point: u32, start: u32, size: u32| {
+        let end = start as u64 + size as u64;
+        (start as u64)
+            .saturating_sub(point as u64)
+            .max((point as u64).saturating_sub(end)) as u128
+    };
+    let (dx, dy) = (
+        gap(x, entity.x, entity.width),
+        gap(y, entity.y, entity.height),
+    );
+    dx * dx + dy * dy
+}
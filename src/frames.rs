@@ -0,0 +1,188 @@
+//! Counts of grid operations per frame, kept for a window of recent frames, such as to graph them in a debug overlay.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::Grid;
+
+/// The operations counted during one frame.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FrameStats {
+    /// The number of the frame, counting the calls to `Grid::end_frame` since collection was enabled.
+    pub frame: u64,
+    /// Number of entities inserted.
+    pub inserts: u64,
+    /// Number of entities moved or resized.
+    pub updates: u64,
+    /// Number of entities deleted.
+    pub deletes: u64,
+    /// Number of queries.
+    pub queries: u64,
+    /// Number of ids returned by the queries.
+    pub results: u64,
+}
+
+impl FrameStats {
+    /// Get the average number of ids returned per query, or zero without queries.
+    pub fn results_per_query(&self) -> f64 {
+        if self.queries == 0 {
+            0.0
+        } else {
+            self.results as f64 / self.queries as f64
+        }
+    }
+}
+
+/// Counters of the running frame. Queries only take `&self`, so they count with relaxed atomics.
+#[derive(Debug, Default)]
+struct Counters {
+    frame: u64,
+    inserts: u64,
+    updates: u64,
+    deletes: u64,
+    queries: AtomicU64,
+    results: AtomicU64,
+}
+
+impl Counters {
+    fn stats(&self) -> FrameStats {
+        FrameStats {
+            frame: self.frame,
+            inserts: self.inserts,
+            updates: self.updates,
+            deletes: self.deletes,
+            queries: self.queries.load(Ordering::Relaxed),
+            results: self.results.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// The running frame and the finished frames kept, present while frame statistics are enabled.
+#[derive(Debug)]
+struct Window {
+    frames: usize,
+    current: Counters,
+    finished: VecDeque<FrameStats>,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Frames(Option<Box<Window>>);
+
+impl Frames {
+    /// Count an insert.
+    #[inline]
+    pub(crate) fn insert(&mut self) {
+        if let Some(window) = &mut self.0 {
+            window.current.inserts += 1;
+        }
+    }
+
+    /// Count an update that kept the entity's cells or rewrote only some of them.
+    #[inline]
+    pub(crate) fn update(&mut self) {
+        if let Some(window) = &mut self.0 {
+            window.current.updates += 1;
+        }
+    }
+
+    /// Count a delete.
+    #[inline]
+    pub(crate) fn delete(&mut self) {
+        if let Some(window) = &mut self.0 {
+            window.current.deletes += 1;
+        }
+    }
+
+    /// Count an update done by deleting and inserting an entity as one update instead.
+    #[inline]
+    pub(crate) fn reinsert(&mut self) {
+        if let Some(window) = &mut self.0 {
+            let current = &mut window.current;
+            current.inserts = current.inserts.saturating_sub(1);
+            current.deletes = current.deletes.saturating_sub(1);
+            current.updates += 1;
+        }
+    }
+
+    /// Count a query returning `results` ids.
+    #[inline]
+    pub(crate) fn query(&self, results: usize) {
+        if let Some(window) = &self.0 {
+            window.current.queries.fetch_add(1, Ordering::Relaxed);
+            window
+                .current
+                .results
+                .fetch_add(results as u64, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn bytes_used(&self) -> usize {
+        self.0.as_ref().map_or(0, |window| {
+            size_of::<Window>() + window.finished.capacity() * size_of::<FrameStats>()
+        })
+    }
+}
+
+impl Clone for Frames {
+    fn clone(&self) -> Self {
+        Self(self.0.as_ref().map(|window| {
+            let stats = window.current.stats();
+            Box::new(Window {
+                frames: window.frames,
+                current: Counters {
+                    frame: stats.frame,
+                    inserts: stats.inserts,
+                    updates: stats.updates,
+                    deletes: stats.deletes,
+                    queries: AtomicU64::new(stats.queries),
+                    results: AtomicU64::new(stats.results),
+                },
+                finished: window.finished.clone(),
+            })
+        }))
+    }
+}
+
+impl Grid {
+    /// Count inserts, updates, deletes and queries per frame, keeping the counts of the last `frames` finished frames,
+    /// or stop counting with zero. Changing the window starts over. Queries are counted through `query`, `query_n`,
+    /// `query_point` and `try_query`, and the queries built on them like `query_exact`.
+    pub fn set_frame_window(&mut self, frames: usize) {
+        self.frames = Frames((frames > 0).then(|| {
+            Box::new(Window {
+                frames,
+                current: Counters::default(),
+                finished: VecDeque::with_capacity(frames),
+            })
+        }));
+    }
+
+    /// Get the counts of the running frame so far, or `None` if frame statistics are disabled.
+    pub fn current_frame(&self) -> Option<FrameStats> {
+        Some(self.frames.0.as_ref()?.current.stats())
+    }
+
+    /// Finish the running frame and start the next one, returning the finished frame's counts.
+    /// Returns `None` if frame statistics are disabled.
+    pub fn end_frame(&mut self) -> Option<FrameStats> {
+        let window = self.frames.0.as_mut()?;
+        let stats = window.current.stats();
+        window.current = Counters {
+            frame: stats.frame + 1,
+            ..Counters::default()
+        };
+        if window.finished.len() == window.frames {
+            window.finished.pop_front();
+        }
+        window.finished.push_back(stats);
+        Some(stats)
+    }
+
+    /// Iterate over the counts of the finished frames in the window, oldest first.
+    pub fn frame_stats(&self) -> impl Iterator<Item = &FrameStats> + '_ {
+        self.frames
+            .0
+            .iter()
+            .flat_map(|window| window.finished.iter())
+    }
+}
@@ -0,0 +1,202 @@
+//! Read-only compressed grids for data that is queried far more often than it changes.
+
+use crate::memory::vec_bytes;
+use crate::{scan, Grid, Query, SORT_DEDUP_THRESHOLD};
+
+/// An immutable snapshot of a grid with every occupied cell packed into one contiguous array.
+/// Cells are stored sorted by row, then column, with `offsets` marking where each cell's ids start in `ids`.
+/// Only occupied cells take memory, and unlike `Grid`, cells never share a bucket, so queries return no extra ids.
+/// Created by `Grid::freeze`.
+#[derive(Debug, Clone)]
+pub struct FrozenGrid {
+    shift: u32,
+    /// Occupied cells as `y << 32 | x`, sorted.
+    cells: Vec<u64>,
+    /// `ids[offsets[i]..offsets[i + 1]]` holds the ids of `cells[i]`.
+    offsets: Vec<u32>,
+    /// Ids tagged with the high bit if they occupy a single cell, like the grid's buckets.
+    ids: Vec<u32>,
+    entities: usize,
+}
+
+impl FrozenGrid {
+    /// Get the number of entities in the snapshot.
+    pub fn len(&self) -> usize {
+        self.entities
+    }
+
+    /// Check whether the snapshot holds no entities.
+    pub fn is_empty(&self) -> bool {
+        self.entities == 0
+    }
+
+    /// Get the number of occupied cells.
+    pub fn cell_count(&self) -> usize {
+        self.cells.len()
+    }
+
+    /// Get the bitshift cell size.
+    pub fn shift(&self) -> u32 {
+        self.shift
+    }
+
+    /// Release the spare capacity left over from building the snapshot, for example alongside `Grid::compact`.
+    pub fn compact(&mut self) {
+        self.cells.shrink_to_fit();
+        self.offsets.shrink_to_fit();
+        self.ids.shrink_to_fit();
+    }
+
+    /// Get the heap memory held by the snapshot in bytes.
+    pub(crate) fn bytes_used(&self) -> usize {
+        vec_bytes(&self.cells) + vec_bytes(&self.offsets) + vec_bytes(&self.ids)
+    }
+
+    /// Retrieve entities in a region. Large result sets are returned sorted by id.
+    pub fn query(&self, query: &Query) -> Vec<u32> {
+        let sx = query.x >> self.shift;
+        let sy = query.y >> self.shift;
+
+        let ex = query.x.saturating_add(query.width) >> self.shift;
+        let ey = query.y.saturating_add(query.height) >> self.shift;
+
+        self.query_cells(sx, sy, ex, ey)
+    }
+
+    /// Retrieve entities in the cell containing a point.
+    pub fn query_point(&self, x: u32, y: u32) -> Vec<u32> {
+        let cx = x >> self.shift;
+        let cy = y >> self.shift;
+        self.query_cells(cx, cy, cx, cy)
+    }
+
+    fn query_cells(&self, sx: u32, sy: u32, ex: u32, ey: u32) -> Vec<u32> {
+        let mut result = Vec::new();
+        let is_ideal = sx == ex && sy == ey;
+        let mut deferred = false;
+
+        for y in sy..=ey {
+            // each row of the region is a contiguous run of cells.
+            let row = (y as u64) << 32;
+            let start = self.cells.partition_point(|&cell| cell < row | sx as u64);
+            let end = start + self.cells[start..].partition_point(|&cell| cell <= row | ex as u64);
+            let ids = &self.ids[self.offsets[start] as usize..self.offsets[end] as usize];
+            for id in ids {
+                if id & (1 << 31) != 0 || is_ideal {
+                    result.push(id & !(1 << 31));
+                } else if deferred {
+                    result.push(*id);
+                } else if scan::position(&result, *id).is_none() {
+                    result.push(*id);
+                    deferred = result.len() >= SORT_DEDUP_THRESHOLD;
+                }
+            }
+        }
+
+        if deferred {
+            result.sort_unstable();
+            result.dedup();
+        }
+        result
+    }
+}
+
+impl Grid {
+    /// Pack every entity, static and dynamic, into a read-only `FrozenGrid`.
+    /// This iterates over every entry of the internal tables.
+    pub fn freeze(&self) -> FrozenGrid {
+        let mut entries = Vec::new();
+        let mut entities = 0;
+        for map in self.maps.entries.iter().filter(|map| !map.cells.is_empty()) {
+            let tagged = map.id | (((map.cells.len() == 1) as u32) << 31);
+            entries.extend(
+                map.cells
+                    .iter()
+                    .map(|&(x, y)| (((y as u64) << 32) | x as u64, tagged)),
+            );
+            entities += 1;
+        }
+        // pinned and large entities have no cells in their map, so use the cells their rectangle covers.
+        let unmapped = self.large.iter().filter_map(|&id| self.rect(id));
+        for entity in self.pinned.values().cloned().chain(unmapped) {
+            let sx = entity.x >> self.shift;
+            let sy = entity.y >> self.shift;
+            let ex = entity.x.saturating_add(entity.width) >> self.shift;
+            let ey = entity.y.saturating_add(entity.height) >> self.shift;
+            let tagged = entity.id | (((sx == ex && sy == ey) as u32) << 31);
+            for y in sy..=ey {
+                entries.extend((sx..=ex).map(|x| (((y as u64) << 32) | x as u64, tagged)));
+            }
+            entities += 1;
+        }
+        entries.sort_unstable();
+
+        let mut frozen = FrozenGrid {
+            shift: self.shift,
+            cells: Vec::new(),
+            offsets: Vec::new(),
+            ids: Vec::with_capacity(entries.len()),
+            entities,
+        };
+        for (cell, id) in entries {
+            if frozen.cells.last() != Some(&cell) {
+                frozen.cells.push(cell);
+                frozen.offsets.push(frozen.ids.len() as u32);
+            }
+            frozen.ids.push(id);
+        }
+        frozen.offsets.push(frozen.ids.len() as u32);
+        frozen
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CellLayout, Entity, Grid, Query, FIXED_SIZE};
+
+    fn sorted(mut ids: Vec<u32>) -> Vec<u32> {
+        ids.sort_unstable();
+        ids
+    }
+
+    #[test]
+    fn frozen_queries_match_the_grid() {
+        let mut grid = Grid::with_cell_layout(64, 4, FIXED_SIZE, CellLayout::Sparse);
+        for id in 0..200 {
+            let entity = Entity {
+                id,
+                x: id * 37 % 500,
+                y: id * 53 % 500,
+                width: id % 40,
+                height: id % 25,
+            };
+            if id % 3 == 0 {
+                grid.insert_static(&entity).unwrap();
+            } else {
+                grid.insert(&entity).unwrap();
+            }
+        }
+        let frozen = grid.freeze();
+        assert_eq!(frozen.len(), 200);
+        for (x, y, width, height) in [(0, 0, 500, 500), (100, 100, 30, 30), (250, 40, 0, 0)] {
+            let query = Query {
+                x,
+                y,
+                width,
+                height,
+            };
+            assert_eq!(
+                sorted(frozen.query(&query)),
+                sorted(grid.query(&query).into_vec())
+            );
+        }
+        assert_eq!(
+            sorted(frozen.query_point(37, 53)),
+            sorted(grid.query_point(37, 53).into_vec())
+        );
+
+        // the snapshot does not follow later changes.
+        grid.delete(1);
+        assert!(frozen.query_point(37, 53).contains(&1));
+    }
+}
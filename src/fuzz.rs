@@ -0,0 +1,30 @@
+//! Grid operations as values, so fuzzers and property tests can generate random sequences of them.
+
+use crate::{Entity, Error, Grid, Query, QueryResults};
+
+/// One operation on a grid, applied by `Grid::apply`.
+#[derive(Debug, Clone)]
+pub enum Op {
+    Insert(Entity),
+    Delete(u32),
+    Update(Entity),
+    Query(Query),
+    Clear,
+}
+
+impl Grid {
+    /// Apply an operation through the non-panicking `try_` methods, returning the ids found by a query and nothing otherwise.
+    /// Any sequence of operations must leave the grid consistent, so a panic here is a bug in the grid.
+    pub fn apply(&mut self, op: &Op) -> Result<Vec<u32>, Error> {
+        match op {
+            Op::Insert(entity) => self.try_insert(entity).map(|_| Vec::new()),
+            Op::Delete(id) => self.try_delete(*id).map(|_| Vec::new()),
+            Op::Update(entity) => self.try_update(entity).map(|_| Vec::new()),
+            Op::Query(query) => self.try_query(query).map(QueryResults::into_vec),
+            Op::Clear => {
+                self.clear();
+                Ok(Vec::new())
+            }
+        }
+    }
+}
@@ -0,0 +1,139 @@
+//! Grids over `u64` world coordinates, for worlds too large for `u32` positions.
+
+use crate::{Entity, Error, Grid, Query};
+
+/// A rectangular entity with `u64` coordinates. **Identifier must be unique.**
+#[derive(Debug, Clone)]
+pub struct Entity64 {
+    /// Identifier must be unique.
+    pub id: u32,
+
+    pub x: u64,
+    pub y: u64,
+    pub width: u64,
+    pub height: u64,
+}
+
+/// A rectangular query region with `u64` coordinates.
+#[derive(Debug, Clone)]
+pub struct Query64 {
+    pub x: u64,
+    pub y: u64,
+    pub width: u64,
+    pub height: u64,
+}
+
+/// A spatial hash grid over `u64` world coordinates. Positions are shifted down to `u32` cell coordinates
+/// exactly, so nothing is lost by pre-dividing them, and the cells are stored in a `Grid` with a shift of zero.
+/// Rectangles reaching cells past `u32::MAX` are rejected, so `shift` should be at least the number of bits
+/// positions use beyond 32. Errors describe cells and rectangles in these cell coordinates.
+#[derive(Debug, Clone)]
+pub struct Grid64 {
+    grid: Grid,
+    shift: u32,
+}
+
+impl Grid64 {
+    /// Create a new grid with a fixed bucket size and cell size.
+    pub fn new(size: usize, shift: u32) -> Self {
+        Self {
+            grid: Grid::new(size, 0),
+            shift,
+        }
+    }
+
+    /// Get the bitshift cell size.
+    pub fn shift(&self) -> u32 {
+        self.shift
+    }
+
+    /// Convert a rectangle to the cells it covers, as a rectangle in cell units.
+    fn cells(&self, x: u64, y: u64, width: u64, height: u64) -> Result<Query, Error> {
+        let right = x.checked_add(width).ok_or(Error::CoordinateOverflow)?;
+        let bottom = y.checked_add(height).ok_or(Error::CoordinateOverflow)?;
+        let cell = |v: u64| u32::try_from(v >> self.shift).map_err(|_| Error::CoordinateOverflow);
+        let (sx, sy) = (cell(x)?, cell(y)?);
+        Ok(Query {
+            x: sx,
+            y: sy,
+            width: cell(right)? - sx,
+            height: cell(bottom)? - sy,
+        })
+    }
+
+    fn entity(&self, entity: &Entity64) -> Result<Entity, Error> {
+        let cells = self.cells(entity.x, entity.y, entity.width, entity.height)?;
+        Ok(Entity {
+            id: entity.id,
+            x: cells.x,
+            y: cells.y,
+            width: cells.width,
+            height: cells.height,
+        })
+    }
+
+    /// Insert an entity. Full cells are handled according to the grid's `OverflowPolicy`.
+    pub fn insert(&mut self, entity: &Entity64) -> Result<(), Error> {
+        let entity = self.entity(entity)?;
+        self.grid.insert(&entity)
+    }
+
+    /// Insert an entity that never moves into the static layer, like `Grid::insert_static`.
+    pub fn insert_static(&mut self, entity: &Entity64) -> Result<(), Error> {
+        let entity = self.entity(entity)?;
+        self.grid.insert_static(&entity)
+    }
+
+    /// Move or resize an entity, keeping it in the same layer.
+    pub fn update(&mut self, entity: &Entity64) -> Result<(), Error> {
+        let entity = self.entity(entity)?;
+        self.grid.update(&entity)
+    }
+
+    /// Delete an entity by ID.
+    pub fn delete(&mut self, id: u32) {
+        self.grid.delete(id);
+    }
+
+    /// Delete an entity by ID without panicking.
+    pub fn try_delete(&mut self, id: u32) -> Result<(), Error> {
+        self.grid.try_delete(id)
+    }
+
+    /// Retrieve entities in a region, in no particular order. Regions reaching past the last cell are cut off there.
+    pub fn query(&self, query: &Query64) -> Vec<u32> {
+        let last = u32::MAX as u64;
+        let sx = (query.x >> self.shift).min(last) as u32;
+        let sy = (query.y >> self.shift).min(last) as u32;
+
+        let ex = (query.x.saturating_add(query.width) >> self.shift).min(last) as u32;
+        let ey = (query.y.saturating_add(query.height) >> self.shift).min(last) as u32;
+
+        self.grid
+            .query(&Query {
+                x: sx,
+                y: sy,
+                width: ex - sx,
+                height: ey - sy,
+            })
+            .into_vec()
+    }
+
+    /// Retrieve entities in the cell containing a point.
+    pub fn query_point(&self, x: u64, y: u64) -> Vec<u32> {
+        let last = u32::MAX as u64;
+        let cx = (x >> self.shift).min(last) as u32;
+        let cy = (y >> self.shift).min(last) as u32;
+        self.grid.query_point(cx, cy).into_vec()
+    }
+
+    /// Find every pair of entities sharing a cell, like `Grid::find_pairs`.
+    pub fn find_pairs(&self) -> Vec<(u32, u32)> {
+        self.grid.find_pairs()
+    }
+
+    /// Remove every entity.
+    pub fn clear(&mut self) {
+        self.grid.clear();
+    }
+}
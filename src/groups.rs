@@ -0,0 +1,58 @@
+//! Entity groups that can be deleted together, such as everything spawned by a dungeon instance.
+
+use crate::{Entity, Error, Grid};
+
+impl Grid {
+    /// Insert an entity as a member of a group.
+    pub fn insert_in_group(&mut self, entity: &Entity, group: u32) -> Result<(), Error> {
+        let result = self.insert(entity);
+        let _ = self.set_group(entity.id, Some(group));
+        result
+    }
+
+    /// Move an entity into a group, or out of any group with `None`. Like priorities, `update` keeps the group and `delete` resets it.
+    pub fn set_group(&mut self, id: u32, group: Option<u32>) -> Result<(), Error> {
+        let map = self.maps.get_scalar_mut(id);
+        if !map.is_registered() || map.id != id {
+            return Err(Error::NotFound(id));
+        }
+        map.meta.group = group;
+        if let Some(group) = group {
+            self.join_group(id, group);
+        }
+        Ok(())
+    }
+
+    /// Get the group an entity belongs to.
+    pub fn group(&self, id: u32) -> Option<u32> {
+        let map = self.maps.get_scalar(id);
+        if !map.is_registered() || map.id != id {
+            return None;
+        }
+        map.meta.group
+    }
+
+    /// Delete every member of a group, returning how many entities were deleted.
+    pub fn delete_group(&mut self, group: u32) -> usize {
+        let members = self.groups.remove(&group).unwrap_or_default();
+        let mut deleted = 0;
+        for id in members {
+            // the index keeps ids that left the group until it is compacted.
+            if self.group(id) == Some(group) {
+                self.delete(id);
+                deleted += 1;
+            }
+        }
+        deleted
+    }
+
+    /// Add an id to a group's index. Entries for entities that left are dropped whenever the index doubles.
+    pub(crate) fn join_group(&mut self, id: u32, group: u32) {
+        let mut members = self.groups.remove(&group).unwrap_or_default();
+        if members.len().is_power_of_two() {
+            members.retain(|&member| member != id && self.group(member) == Some(group));
+        }
+        members.push(id);
+        self.groups.insert(group, members);
+    }
+}
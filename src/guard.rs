@@ -0,0 +1,75 @@
+//! Scoped insertion for short-lived entities.
+
+use std::ops::{Deref, DerefMut};
+
+use crate::{Entity, Error, Grid};
+
+/// An entity that is deleted from the grid when the guard is dropped, so early returns cannot leak it.
+/// The guard borrows the grid mutably and derefs to it, so the grid is used through the guard while it lives.
+/// Created by `Grid::insert_guarded`.
+#[derive(Debug)]
+pub struct EntityGuard<'a> {
+    grid: &'a mut Grid,
+    id: u32,
+}
+
+impl EntityGuard<'_> {
+    /// Get the id of the guarded entity.
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Release the guard, leaving the entity in the grid.
+    pub fn keep(self) {
+        std::mem::forget(self);
+    }
+}
+
+impl Deref for EntityGuard<'_> {
+    type Target = Grid;
+
+    fn deref(&self) -> &Grid {
+        self.grid
+    }
+}
+
+impl DerefMut for EntityGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Grid {
+        self.grid
+    }
+}
+
+impl Drop for EntityGuard<'_> {
+    fn drop(&mut self) {
+        // the entity may have been deleted through the guard already.
+        let _ = self.grid.try_delete(self.id);
+    }
+}
+
+impl Grid {
+    /// Insert an entity like `insert` and get a guard that deletes it when dropped.
+    /// If the insert fails, the cells filled so far are cleared again and no guard is returned.
+    pub fn insert_guarded(&mut self, entity: &Entity) -> Result<EntityGuard<'_>, Error> {
+        if let Err(err) = self.insert(entity) {
+            if !err.is_rejection() {
+                let _ = self.try_delete(entity.id);
+            }
+            return Err(err);
+        }
+        Ok(EntityGuard {
+            grid: self,
+            id: entity.id,
+        })
+    }
+
+    /// Insert an entity, run `f` on the grid and delete the entity again, even if `f` panics.
+    /// Useful for previews such as checking what an ability would hit.
+    pub fn with_temp<R>(
+        &mut self,
+        entity: &Entity,
+        f: impl FnOnce(&mut Grid) -> R,
+    ) -> Result<R, Error> {
+        let mut guard = self.insert_guarded(entity)?;
+        Ok(f(&mut guard))
+    }
+}
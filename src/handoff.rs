@@ -0,0 +1,422 @@
+//! Moving every entity of a region from one grid to another, such as between the servers of a zoned world.
+
+use std::io::{self, Read, Write};
+
+use crate::{Entity, Error, Grid, Query, Shape, MAX_LAYERS};
+
+/// An entity removed from a grid with everything the grid stored about it.
+#[derive(Debug, Clone)]
+struct RegionEntity {
+    entity: Entity,
+    is_static: bool,
+    layer: u8,
+    priority: u8,
+    tags: u64,
+    value: f32,
+    group: Option<u32>,
+    expiry: Option<u64>,
+    shape: Option<Shape>,
+}
+
+/// The entities extracted from a region by `Grid::extract_region`, to be absorbed by another grid.
+/// Each keeps its rectangle, static layer, user layer, priority, tags, value, group, expiry and exact shape.
+#[derive(Debug, Clone)]
+pub struct RegionSnapshot {
+    region: Query,
+    entities: Vec<RegionEntity>,
+}
+
+/// Flags describing which optional fields follow an entity in the encoded snapshot.
+const STATIC: u8 = 1;
+const GROUP: u8 = 2;
+const EXPIRY: u8 = 4;
+const SHAPE: u8 = 8;
+
+impl RegionSnapshot {
+    /// Get the region the entities were extracted from.
+    pub fn region(&self) -> &Query {
+        &self.region
+    }
+
+    /// Get the number of entities.
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    /// Check whether the region held no entities.
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    /// Get the ids of the entities.
+    pub fn ids(&self) -> impl Iterator<Item = u32> + '_ {
+        self.entities.iter().map(|entry| entry.entity.id)
+    }
+
+    /// Write the snapshot in a little-endian binary format read by `decode`.
+    pub fn encode(&self, mut writer: impl Write) -> io::Result<()> {
+        let mut out = Vec::new();
+        let region = &self.region;
+        for field in [region.x, region.y, region.width, region.height] {
+            out.extend_from_slice(&field.to_le_bytes());
+        }
+        out.extend_from_slice(&(self.entities.len() as u32).to_le_bytes());
+        for entry in &self.entities {
+            let entity = &entry.entity;
+            for field in [entity.id, entity.x, entity.y, entity.width, entity.height] {
+                out.extend_from_slice(&field.to_le_bytes());
+            }
+            let flags = (entry.is_static as u8 * STATIC)
+                | (entry.group.is_some() as u8 * GROUP)
+                | (entry.expiry.is_some() as u8 * EXPIRY)
+                | (entry.shape.is_some() as u8 * SHAPE);
+            out.extend_from_slice(&[flags, entry.layer, entry.priority]);
+            out.extend_from_slice(&entry.tags.to_le_bytes());
+            out.extend_from_slice(&entry.value.to_le_bytes());
+            if let Some(group) = entry.group {
+                out.extend_from_slice(&group.to_le_bytes());
+            }
+            if let Some(expiry) = entry.expiry {
+                out.extend_from_slice(&expiry.to_le_bytes());
+            }
+            if let Some(shape) = &entry.shape {
+                encode_shape(shape, &mut out);
+            }
+        }
+        writer.write_all(&out)
+    }
+
+    /// Read a snapshot written by `encode`. Layers that are not below `MAX_LAYERS` fail with `io::ErrorKind::InvalidData`.
+    pub fn decode(mut reader: impl Read) -> io::Result<Self> {
+        let reader = &mut reader;
+        let region = Query {
+            x: read_u32(reader)?,
+            y: read_u32(reader)?,
+            width: read_u32(reader)?,
+            height: read_u32(reader)?,
+        };
+        let count = read_u32(reader)?;
+        let mut entities = Vec::new();
+        for _ in 0..count {
+            let entity = Entity {
+                id: read_u32(reader)?,
+                x: read_u32(reader)?,
+                y: read_u32(reader)?,
+                width: read_u32(reader)?,
+                height: read_u32(reader)?,
+            };
+            let mut bytes = [0; 3];
+            reader.read_exact(&mut bytes)?;
+            let [flags, layer, priority] = bytes;
+            if layer >= MAX_LAYERS {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("layer {} out of range", layer),
+                ));
+            }
+            let tags = read_u64(reader)?;
+            let value = f32::from_bits(read_u32(reader)?);
+            let group = (flags & GROUP != 0).then(|| read_u32(reader)).transpose()?;
+            let expiry = (flags & EXPIRY != 0)
+                .then(|| read_u64(reader))
+                .transpose()?;
+            let shape = (flags & SHAPE != 0)
+                .then(|| decode_shape(reader))
+                .transpose()?;
+            entities.push(RegionEntity {
+                entity,
+                is_static: flags & STATIC != 0,
+                layer,
+                priority,
+                tags,
+                value,
+                group,
+                expiry,
+                shape,
+            });
+        }
+        Ok(Self { region, entities })
+    }
+}
+
+fn encode_shape(shape: &Shape, out: &mut Vec<u8>) {
+    let (kind, fields): (u8, Vec<f32>) = match shape {
+        Shape::Circle { cx, cy, radius } => (0, vec![*cx, *cy, *radius]),
+        Shape::Capsule {
+            ax,
+            ay,
+            bx,
+            by,
+            radius,
+        } => (1, vec![*ax, *ay, *bx, *by, *radius]),
+        Shape::Obb {
+            cx,
+            cy,
+            half_width,
+            half_height,
+            angle,
+        } => (2, vec![*cx, *cy, *half_width, *half_height, *angle]),
+        Shape::Segment { x0, y0, x1, y1 } => (3, vec![*x0, *y0, *x1, *y1]),
+        Shape::Polygon(points) => {
+            out.push(4);
+            out.extend_from_slice(&(points.len() as u32).to_le_bytes());
+            for &(x, y) in points {
+                out.extend_from_slice(&x.to_le_bytes());
+                out.extend_from_slice(&y.to_le_bytes());
+            }
+            return;
+        }
+    };
+    out.push(kind);
+    for field in fields {
+        out.extend_from_slice(&field.to_le_bytes());
+    }
+}
+
+fn decode_shape(reader: &mut impl Read) -> io::Result<Shape> {
+    let mut kind = [0];
+    reader.read_exact(&mut kind)?;
+    let mut floats = |n: usize| -> io::Result<Vec<f32>> {
+        (0..n)
+            .map(|_| read_u32(reader).map(f32::from_bits))
+            .collect()
+    };
+    Ok(match kind[0] {
+        0 => {
+            let f = floats(3)?;
+            Shape::Circle {
+                cx: f[0],
+                cy: f[1],
+                radius: f[2],
+            }
+        }
+        1 => {
+            let f = floats(5)?;
+            Shape::Capsule {
+                ax: f[0],
+                ay: f[1],
+                bx: f[2],
+                by: f[3],
+                radius: f[4],
+            }
+        }
+        2 => {
+            let f = floats(5)?;
+            Shape::Obb {
+                cx: f[0],
+                cy: f[1],
+                half_width: f[2],
+                half_height: f[3],
+                angle: f[4],
+            }
+        }
+        3 => {
+            let f = floats(4)?;
+            Shape::Segment {
+                x0: f[0],
+                y0: f[1],
+                x1: f[2],
+                y1: f[3],
+            }
+        }
+        4 => {
+            let count = read_u32(reader)? as usize;
+            let f = (0..count * 2)
+                .map(|_| read_u32(reader).map(f32::from_bits))
+                .collect::<io::Result<Vec<f32>>>()?;
+            Shape::Polygon(f.chunks(2).map(|point| (point[0], point[1])).collect())
+        }
+        kind => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unknown shape kind {}", kind),
+            ))
+        }
+    })
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+impl Grid {
+    /// Remove every entity whose center lies in a region and return them with their metadata, such as to hand the region
+    /// over to another server. Using centers gives each entity to exactly one of several adjacent regions.
+    /// Pinned entities cannot be removed and stay behind.
+    pub fn extract_region(&mut self, query: &Query) -> RegionSnapshot {
+        let mut ids = self.query(query).into_vec();
+        ids.sort_unstable();
+        ids.dedup();
+
+        let mut entities = Vec::new();
+        for id in ids {
+            let map = self.maps.get_scalar(id);
+            if !map.is_registered() || map.id != id {
+                continue;
+            }
+            let entity = Entity {
+                id,
+                x: map.x,
+                y: map.y,
+                width: map.width,
+                height: map.height,
+            };
+            let (cx, cy) = entity.center();
+            let inside = |point: u32, start: u32, size: u32| {
+                start <= point && point as u64 <= start as u64 + size as u64
+            };
+            if !inside(cx, query.x, query.width) || !inside(cy, query.y, query.height) {
+                continue;
+            }
+            entities.push(RegionEntity {
+                entity,
+                is_static: map.is_static,
+                layer: map.meta.layer,
+                priority: map.meta.priority,
+                tags: map.meta.tags,
+                value: map.meta.value,
+                group: map.meta.group,
+                expiry: self.expiry(id),
+                shape: self.shapes.get(&id).cloned(),
+            });
+            self.delete(id);
+        }
+        RegionSnapshot {
+            region: query.clone(),
+            entities,
+        }
+    }
+
+    /// Insert the entities of a snapshot taken by `extract_region`, restoring their metadata. Every entity is attempted,
+    /// and the first error is returned, such as `Error::DuplicateId` for ids this grid already holds.
+    pub fn absorb_region(&mut self, snapshot: RegionSnapshot) -> Result<(), Error> {
+        let mut first = Ok(());
+        for entry in snapshot.entities {
+            let id = entry.entity.id;
+            let result = match entry.shape {
+                Some(shape) => self.insert_shape(id, shape),
+                None if entry.is_static => self.insert_static(&entry.entity),
+                None => self.insert(&entry.entity),
+            };
+            if !result.as_ref().is_err_and(Error::is_rejection) {
+                let map = self.maps.get_scalar_mut(id);
+                if map.is_registered() && map.id == id {
+                    map.meta.layer = entry.layer;
+                    map.meta.priority = entry.priority;
+                    map.meta.tags = entry.tags;
+                    map.meta.value = entry.value;
+                    self.set_group(id, entry.group).ok();
+                    self.set_expiry(id, entry.expiry).ok();
+                }
+            }
+            if first.is_ok() {
+                first = result;
+            }
+        }
+        first
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::RegionSnapshot;
+    use crate::{Entity, Grid, Query, Shape};
+
+    fn region() -> Query {
+        Query {
+            x: 0,
+            y: 0,
+            width: 100,
+            height: 100,
+        }
+    }
+
+    #[test]
+    fn decode_rejects_out_of_range_layer() {
+        let mut grid = Grid::new(64, 4);
+        grid.insert_in_layer(
+            &Entity {
+                id: 1,
+                x: 10,
+                y: 10,
+                width: 5,
+                height: 5,
+            },
+            2,
+        )
+        .unwrap();
+        let snapshot = grid.extract_region(&region());
+        let mut bytes = Vec::new();
+        snapshot.encode(&mut bytes).unwrap();
+        // the region and count, then the entity's rectangle and flags come before its layer.
+        assert_eq!(bytes[41], 2);
+        bytes[41] = 8;
+        let err = RegionSnapshot::decode(&bytes[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn snapshots_move_entities_with_their_metadata() {
+        let mut source = Grid::new(64, 4);
+        source
+            .insert(&Entity {
+                id: 1,
+                x: 10,
+                y: 10,
+                width: 5,
+                height: 5,
+            })
+            .unwrap();
+        source
+            .insert_static(&Entity {
+                id: 2,
+                x: 40,
+                y: 40,
+                width: 5,
+                height: 5,
+            })
+            .unwrap();
+        source.insert_circle(3, 70.0, 70.0, 4.0).unwrap();
+        source.set_layer(1, 4).unwrap();
+        source.set_priority(1, 9).unwrap();
+        source.set_tags(1, 0b1010).unwrap();
+        source.set_value(1, 2.5).unwrap();
+        source.set_group(1, Some(7)).unwrap();
+        source.set_expiry(1, Some(30)).unwrap();
+
+        let snapshot = source.extract_region(&region());
+        assert!(source.query(&region()).is_empty());
+        let mut bytes = Vec::new();
+        snapshot.encode(&mut bytes).unwrap();
+        let snapshot = RegionSnapshot::decode(&bytes[..]).unwrap();
+        let extracted = snapshot.region();
+        assert_eq!(
+            (extracted.x, extracted.y, extracted.width, extracted.height),
+            (0, 0, 100, 100)
+        );
+        let mut ids: Vec<u32> = snapshot.ids().collect();
+        ids.sort_unstable();
+        assert_eq!(ids, [1, 2, 3]);
+
+        let mut target = Grid::new(64, 4);
+        target.absorb_region(snapshot).unwrap();
+        assert_eq!(target.layer(1), 4);
+        assert_eq!(target.priority(1), 9);
+        assert_eq!(target.tags(1), 0b1010);
+        assert_eq!(target.value(1), 2.5);
+        assert_eq!(target.group(1), Some(7));
+        assert_eq!(target.expiry(1), Some(30));
+        assert_eq!(target.query_point(41, 41).into_vec(), [2]);
+        assert!(matches!(target.shape(3), Some(Shape::Circle { radius, .. }) if *radius == 4.0));
+    }
+}
@@ -0,0 +1,72 @@
+//! A ring buffer of past grid states for querying where entities were at an earlier tick, such as for lag compensation.
+
+use std::collections::VecDeque;
+
+use crate::{FrozenGrid, Grid, Query};
+
+/// Frozen copies of the grid, oldest first, each stamped with the tick it was captured at.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct History {
+    depth: usize,
+    snapshots: VecDeque<(u64, FrozenGrid)>,
+}
+
+impl History {
+    pub(crate) fn bytes_used(&self) -> usize {
+        self.snapshots.capacity() * std::mem::size_of::<(u64, FrozenGrid)>()
+            + self
+                .snapshots
+                .iter()
+                .map(|(_, frozen)| frozen.bytes_used())
+                .sum::<usize>()
+    }
+}
+
+impl Grid {
+    /// Keep the last `depth` states captured by `capture_history`, or none with a depth of 0, which also drops them.
+    pub fn set_history_depth(&mut self, depth: usize) {
+        let history = &mut self.history;
+        history.depth = depth;
+        while history.snapshots.len() > depth {
+            history.snapshots.pop_front();
+        }
+    }
+
+    /// Get the number of states kept.
+    pub fn history_depth(&self) -> usize {
+        self.history.depth
+    }
+
+    /// Capture the grid's current state at the current `tick`, dropping the oldest state once `history_depth` are kept.
+    /// Call it once per tick after the entities moved. Capturing the same tick again replaces its state, and ticks
+    /// must not decrease. Each state is a `FrozenGrid`, so this iterates over every entry of the internal tables.
+    pub fn capture_history(&mut self) {
+        if self.history.depth == 0 {
+            return;
+        }
+        let frozen = self.freeze();
+        let snapshots = &mut self.history.snapshots;
+        if snapshots.back().is_some_and(|&(tick, _)| tick == self.tick) {
+            snapshots.pop_back();
+        }
+        if snapshots.len() == self.history.depth {
+            snapshots.pop_front();
+        }
+        snapshots.push_back((self.tick, frozen));
+    }
+
+    /// Get the oldest and newest captured ticks.
+    pub fn history_range(&self) -> Option<(u64, u64)> {
+        let snapshots = &self.history.snapshots;
+        Some((snapshots.front()?.0, snapshots.back()?.0))
+    }
+
+    /// Retrieve the entities that were in a region at `tick`, like `query` on the newest state captured at or before it.
+    /// Returns `None` if `tick` is older than every captured state.
+    pub fn query_at(&self, tick: u64, query: &Query) -> Option<Vec<u32>> {
+        let snapshots = &self.history.snapshots;
+        let newer = snapshots.partition_point(|&(captured, _)| captured <= tick);
+        let (_, frozen) = snapshots.get(newer.checked_sub(1)?)?;
+        Some(frozen.query(query))
+    }
+}
@@ -0,0 +1,136 @@
+//! Query results as bitsets keyed by id, for intersecting and merging the results of several queries.
+
+use crate::{Grid, Query};
+
+/// A set of entity ids stored as one bit per id, growing to fit the largest id inserted.
+/// Memory is proportional to the largest id, so it suits grids with dense ids.
+#[derive(Debug, Clone, Default)]
+pub struct IdSet {
+    words: Vec<u64>,
+}
+
+impl IdSet {
+    /// Create an empty set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an empty set with room for ids below `ids` without reallocating.
+    pub fn with_capacity(ids: usize) -> Self {
+        Self {
+            words: vec![0; ids.div_ceil(64)],
+        }
+    }
+
+    /// Add an id, returning whether it was missing.
+    pub fn insert(&mut self, id: u32) -> bool {
+        let word = id as usize / 64;
+        if word >= self.words.len() {
+            self.words.resize(word + 1, 0);
+        }
+        let bit = 1 << (id % 64);
+        let missing = self.words[word] & bit == 0;
+        self.words[word] |= bit;
+        missing
+    }
+
+    /// Remove an id, returning whether it was present.
+    pub fn remove(&mut self, id: u32) -> bool {
+        match self.words.get_mut(id as usize / 64) {
+            Some(word) => {
+                let bit = 1 << (id % 64);
+                let present = *word & bit != 0;
+                *word &= !bit;
+                present
+            }
+            None => false,
+        }
+    }
+
+    /// Check whether an id is in the set.
+    pub fn contains(&self, id: u32) -> bool {
+        self.words
+            .get(id as usize / 64)
+            .is_some_and(|word| word & (1 << (id % 64)) != 0)
+    }
+
+    /// Remove every id, keeping the memory.
+    pub fn clear(&mut self) {
+        self.words.fill(0);
+    }
+
+    /// Count the ids in the set.
+    pub fn len(&self) -> usize {
+        self.words
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    /// Check whether the set holds no ids.
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|&word| word == 0)
+    }
+
+    /// Iterate over the ids in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = u32> + '_ {
+        self.words.iter().enumerate().flat_map(|(i, &word)| {
+            let mut bits = word;
+            std::iter::from_fn(move || {
+                if bits == 0 {
+                    return None;
+                }
+                let bit = bits.trailing_zeros();
+                bits &= bits - 1;
+                Some(i as u32 * 64 + bit)
+            })
+        })
+    }
+
+    /// Keep only the ids that are also in `other`.
+    pub fn intersect_with(&mut self, other: &IdSet) {
+        for (i, word) in self.words.iter_mut().enumerate() {
+            *word &= other.words.get(i).copied().unwrap_or(0);
+        }
+    }
+
+    /// Add every id of `other`.
+    pub fn union_with(&mut self, other: &IdSet) {
+        if self.words.len() < other.words.len() {
+            self.words.resize(other.words.len(), 0);
+        }
+        for (word, &other) in self.words.iter_mut().zip(other.words.iter()) {
+            *word |= other;
+        }
+    }
+
+    /// Remove every id of `other`.
+    pub fn difference_with(&mut self, other: &IdSet) {
+        for (word, &other) in self.words.iter_mut().zip(other.words.iter()) {
+            *word &= !other;
+        }
+    }
+}
+
+impl Grid {
+    /// Add the entities in a region to a set, without clearing it first. Setting bits needs no deduplication,
+    /// so this is cheaper than `query` for dense scenes, and sets of several queries combine with `IdSet::intersect_with`.
+    pub fn query_bitset(&self, query: &Query, set: &mut IdSet) {
+        self.visit_cells(query, |_, ids| {
+            for id in ids {
+                set.insert(id & !(1 << 31));
+            }
+        });
+        if !self.large.is_empty() {
+            let sx = query.x >> self.shift;
+            let sy = query.y >> self.shift;
+
+            let ex = query.x.saturating_add(query.width) >> self.shift;
+            let ey = query.y.saturating_add(query.height) >> self.shift;
+
+            for id in self.large_in(sx, sy, ex, ey) {
+                set.insert(id);
+            }
+        }
+    }
+}
@@ -0,0 +1,156 @@
+//! Undo journal for rewinding the grid in rollback netcode.
+
+use crate::{Entity, Error, Grid, Meta};
+
+/// An entity's state before a mutation, so the mutation can be reversed.
+#[derive(Debug, Clone)]
+pub(crate) struct JournalEntry {
+    tick: u64,
+    id: u32,
+    /// The entity's last inserted rectangle and whether it was static, or `None` if it was not in the grid.
+    previous: Option<(Entity, bool)>,
+    meta: Meta,
+}
+
+impl Grid {
+    /// Enable or disable the undo journal. While enabled, every insert, delete and update is recorded
+    /// with the current tick so `rollback_to` can reverse it. Disabling it discards the recorded operations.
+    pub fn set_journaling(&mut self, enabled: bool) {
+        self.journal = enabled.then(Vec::new);
+    }
+
+    /// Set the tick stamped on operations recorded from now on. Ticks must not decrease between rollbacks.
+    pub fn set_tick(&mut self, tick: u64) {
+        self.tick = tick;
+    }
+
+    /// Get the tick stamped on recorded operations.
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Reverse every recorded operation stamped after `tick`, restoring the grid as it was at the end of that tick,
+    /// and make `tick` the current tick. Entities are restored with their last inserted rectangle, so an entity built
+    /// from several inserts comes back as its last one, and cells taken by `OverflowPolicy::EvictOldest` are not given back.
+    /// If restoring an entity overflows a cell, the rollback stops and the older operations stay recorded.
+    pub fn rollback_to(&mut self, tick: u64) -> Result<(), Error> {
+        // undoing operations must not record new ones.
+        let mut journal = match self.journal.take() {
+            Some(journal) => journal,
+            None => return Ok(()),
+        };
+        self.tick = tick;
+
+        let mut result = Ok(());
+        while journal.last().is_some_and(|entry| entry.tick > tick) {
+            let entry = journal.pop().unwrap();
+            let map = self.maps.get_scalar(entry.id);
+            if map.is_registered() && map.id == entry.id {
+                self.delete(entry.id);
+            }
+            if let Some((entity, is_static)) = entry.previous {
+                let restored = if is_static {
+                    self.insert_static(&entity)
+                } else {
+                    self.insert(&entity)
+                };
+                if restored.is_err() {
+                    result = restored;
+                    break;
+                }
+                self.maps.get_scalar_mut(entry.id).meta = entry.meta;
+                if let Some(group) = entry.meta.group {
+                    self.join_group(entry.id, group);
+                }
+            }
+        }
+
+        self.journal = Some(journal);
+        result
+    }
+
+    /// Drop recorded operations stamped at or before `tick`, once the simulation can no longer rewind that far.
+    pub fn forget_before(&mut self, tick: u64) {
+        if let Some(journal) = &mut self.journal {
+            let confirmed = journal.partition_point(|entry| entry.tick <= tick);
+            journal.drain(..confirmed);
+        }
+    }
+
+    /// Record an entity's current state before it is mutated, and the tick it changed at for delta tracking.
+    pub(crate) fn record(&mut self, id: u32) {
+        if let Some(delta) = &mut self.delta {
+            delta.touch(id, self.tick);
+        }
+        let journal = match &mut self.journal {
+            Some(journal) => journal,
+            None => return,
+        };
+        let map = self.maps.get_scalar(id);
+        let previous = (map.is_registered() && map.id == id).then(|| {
+            let entity = Entity {
+                id,
+                x: map.x,
+                y: map.y,
+                width: map.width,
+                height: map.height,
+            };
+            (entity, map.is_static)
+        });
+        journal.push(JournalEntry {
+            tick: self.tick,
+            id,
+            previous,
+            meta: map.meta,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Entity, Grid, Tag};
+
+    fn square(id: u32, x: u32) -> Entity {
+        Entity {
+            id,
+            x,
+            y: 0,
+            width: 4,
+            height: 4,
+        }
+    }
+
+    #[test]
+    fn rollback_reverses_operations_after_the_tick() {
+        let mut grid = Grid::new(64, 4);
+        grid.set_journaling(true);
+        grid.set_tick(1);
+        grid.insert(&square(1, 10)).unwrap();
+        grid.insert_static(&square(2, 100)).unwrap();
+        grid.tag(1, Tag::new(2)).unwrap();
+
+        grid.set_tick(2);
+        grid.update(&square(1, 200)).unwrap();
+        grid.delete(2);
+        grid.insert(&square(3, 300)).unwrap();
+
+        grid.rollback_to(1).unwrap();
+        assert_eq!(grid.tick(), 1);
+        assert_eq!(grid.query_point(11, 1).into_vec(), [1]);
+        assert!(grid.query_point(201, 1).is_empty());
+        assert!(grid.has_tag(1, Tag::new(2)));
+        assert_eq!(grid.query_point(101, 1).into_vec(), [2]);
+        assert!(grid.query_point(301, 1).is_empty());
+    }
+
+    #[test]
+    fn forgotten_operations_are_not_rolled_back() {
+        let mut grid = Grid::new(64, 4);
+        grid.set_journaling(true);
+        grid.set_tick(1);
+        grid.insert(&square(1, 10)).unwrap();
+        grid.forget_before(1);
+        grid.rollback_to(0).unwrap();
+        assert_eq!(grid.query_point(11, 1).into_vec(), [1]);
+    }
+}
@@ -0,0 +1,79 @@
+//! Separate storage for entities covering many cells, which queries check directly instead of through the buckets.
+
+use crate::{Grid, Layer};
+
+impl Grid {
+    /// Set the number of cells above which dynamic and static entities are kept in a list of large entities instead of
+    /// the cell buckets, or `None` to store every entity in the buckets. Large entities never fill up cells and are not
+    /// limited to `FIXED_SIZE` cells, but every query checks each of them, so keep the list short.
+    /// Pinned entities always go into the buckets. Entities already in the grid move when they are next inserted or updated.
+    pub fn set_large_threshold(&mut self, cells: Option<usize>) {
+        self.large_threshold = cells;
+    }
+
+    /// Get the number of cells above which entities are stored as large entities, if any.
+    pub fn large_threshold(&self) -> Option<usize> {
+        self.large_threshold
+    }
+
+    /// Check whether an entity is stored in the large entity list.
+    pub fn is_large(&self, id: u32) -> bool {
+        let map = self.maps.get_scalar(id);
+        map.is_large && map.id == id
+    }
+
+    /// Check whether an entity covering this many cells of a layer goes into the large entity list.
+    #[inline(always)]
+    pub(crate) fn goes_large(&self, cells: u64, layer: Layer) -> bool {
+        layer != Layer::Pinned
+            && self
+                .large_threshold
+                .is_some_and(|threshold| cells > threshold as u64)
+    }
+
+    /// Get the range of cells a large entity covers.
+    pub(crate) fn large_cells(&self, id: u32) -> (u32, u32, u32, u32) {
+        let map = self.maps.get_scalar(id);
+        (
+            map.x >> self.shift,
+            map.y >> self.shift,
+            map.x.saturating_add(map.width) >> self.shift,
+            map.y.saturating_add(map.height) >> self.shift,
+        )
+    }
+
+    /// Iterate over the large entities covering any cell of a range.
+    pub(crate) fn large_in(
+        &self,
+        sx: u32,
+        sy: u32,
+        ex: u32,
+        ey: u32,
+    ) -> impl Iterator<Item = u32> + '_ {
+        self.large.iter().copied().filter(move |&id| {
+            let (lsx, lsy, lex, ley) = self.large_cells(id);
+            lsx <= ex && sx <= lex && lsy <= ey && sy <= ley
+        })
+    }
+
+    /// Get the ids sharing a cell with a large entity, excluding itself. Static entities are not paired with each other.
+    pub(crate) fn large_pairs_of(&self, id: u32) -> Vec<u32> {
+        let (sx, sy, ex, ey) = self.large_cells(id);
+        let is_static = self.maps.get_scalar(id).is_static;
+        let mut others = Vec::new();
+        self.collect_cells(sx, sy, ex, ey, usize::MAX, |other| other != id, &mut others);
+        if is_static {
+            others.retain(|&other| !self.is_static(other));
+        }
+        others
+    }
+
+    /// Check whether an entity lives in the static layer, pinned entities included.
+    fn is_static(&self, id: u32) -> bool {
+        let map = self.maps.get_scalar(id);
+        if map.is_registered() && map.id == id {
+            return map.is_static;
+        }
+        self.pinned.contains_key(&id)
+    }
+}
@@ -0,0 +1,167 @@
+//! User-facing layers sharing the grid's cell tables, such as ground, air and projectiles.
+
+use crate::{Entity, Error, Grid, Query, QueryResults};
+
+/// The number of layers an entity can be tagged with.
+pub const MAX_LAYERS: u8 = 8;
+
+/// A set of layers for queries to visit, one bit per layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LayerMask(pub u8);
+
+impl LayerMask {
+    /// Every layer.
+    pub const ALL: Self = Self(u8::MAX);
+
+    /// No layer.
+    pub const NONE: Self = Self(0);
+
+    /// A mask selecting a single layer.
+    pub const fn layer(layer: u8) -> Self {
+        assert!(layer < MAX_LAYERS, "layer out of range");
+        Self(1 << layer)
+    }
+
+    /// Add a layer to the mask.
+    pub const fn with(self, layer: u8) -> Self {
+        Self(self.0 | Self::layer(layer).0)
+    }
+
+    /// Check whether the mask selects a layer. Layers that are not below `MAX_LAYERS` are never selected.
+    pub const fn contains(self, layer: u8) -> bool {
+        layer < MAX_LAYERS && self.0 & (1 << layer) != 0
+    }
+}
+
+impl Grid {
+    /// Insert an entity into a layer. Layers are tags on the entity; every layer shares the same cells.
+    /// Panics if `layer` is not below `MAX_LAYERS`.
+    pub fn insert_in_layer(&mut self, entity: &Entity, layer: u8) -> Result<(), Error> {
+        assert!(layer < MAX_LAYERS, "layer out of range");
+        let result = self.insert(entity);
+        if result.as_ref().is_err_and(Error::is_rejection) {
+            return result;
+        }
+        let map = self.maps.get_scalar_mut(entity.id);
+        if map.is_registered() && map.id == entity.id {
+            map.meta.layer = layer;
+        }
+        result
+    }
+
+    /// Move an entity to another layer. Entities start in layer 0; `update` keeps the layer and `delete` resets it.
+    /// Pinned entities are always in layer 0. Panics if `layer` is not below `MAX_LAYERS`.
+    pub fn set_layer(&mut self, id: u32, layer: u8) -> Result<(), Error> {
+        assert!(layer < MAX_LAYERS, "layer out of range");
+        let map = self.maps.get_scalar_mut(id);
+        if !map.is_registered() || map.id != id {
+            return Err(Error::NotFound(id));
+        }
+        map.meta.layer = layer;
+        Ok(())
+    }
+
+    /// Get an entity's layer.
+    pub fn layer(&self, id: u32) -> u8 {
+        let map = self.maps.get_scalar(id);
+        if !map.is_registered() || map.id != id {
+            return 0;
+        }
+        map.meta.layer
+    }
+
+    /// Retrieve the unique entities in a region that belong to one of the layers in `mask`.
+    /// Entities of other layers are skipped while the cells are visited, before deduplication.
+    pub fn query_layers(&self, query: &Query, mask: LayerMask) -> QueryResults {
+        let sx = query.x >> self.shift;
+        let sy = query.y >> self.shift;
+
+        let ex = query.x.saturating_add(query.width) >> self.shift;
+        let ey = query.y.saturating_add(query.height) >> self.shift;
+
+        let mut result = self.results.take();
+        if mask == LayerMask::ALL {
+            self.collect_cells(sx, sy, ex, ey, usize::MAX, |_| true, &mut result);
+        } else {
+            let accept = |id| mask.contains(self.layer(id));
+            self.collect_cells(sx, sy, ex, ey, usize::MAX, accept, &mut result);
+        }
+        QueryResults::new(result)
+    }
+
+    /// Iterate over the ids of every entity in a layer. This iterates over every entry of the internal tables.
+    pub fn iter_layer(&self, layer: u8) -> impl Iterator<Item = u32> + '_ {
+        let pinned = self.pinned.keys().copied().filter(move |_| layer == 0);
+        self.maps
+            .entries
+            .iter()
+            .filter(move |map| map.is_registered() && map.meta.layer == layer)
+            .map(|map| map.id)
+            .chain(pinned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LayerMask, MAX_LAYERS};
+    use crate::{Entity, Error, Grid, Query};
+
+    fn square(id: u32, x: u32) -> Entity {
+        Entity {
+            id,
+            x,
+            y: 0,
+            width: 4,
+            height: 4,
+        }
+    }
+
+    fn sorted(mut ids: Vec<u32>) -> Vec<u32> {
+        ids.sort_unstable();
+        ids
+    }
+
+    #[test]
+    fn contains_ignores_out_of_range_layers() {
+        assert!(LayerMask::ALL.contains(MAX_LAYERS - 1));
+        assert!(!LayerMask::ALL.contains(MAX_LAYERS));
+        assert!(!LayerMask::ALL.contains(u8::MAX));
+    }
+
+    #[test]
+    fn queries_filter_by_layer() {
+        let mut grid = Grid::new(64, 4);
+        grid.insert(&square(1, 0)).unwrap();
+        grid.insert_in_layer(&square(2, 2), 1).unwrap();
+        grid.insert_in_layer(&square(3, 4), 2).unwrap();
+        let region = Query {
+            x: 0,
+            y: 0,
+            width: 8,
+            height: 8,
+        };
+        let ground_and_air = LayerMask::layer(0).with(1);
+        assert_eq!(
+            sorted(grid.query_layers(&region, ground_and_air).into_vec()),
+            [1, 2]
+        );
+        assert_eq!(
+            grid.query_layers(&region, LayerMask::layer(2)).into_vec(),
+            [3]
+        );
+        assert!(grid.query_layers(&region, LayerMask::NONE).is_empty());
+        assert_eq!(sorted(grid.iter_layer(1).collect()), [2]);
+    }
+
+    #[test]
+    fn update_keeps_and_delete_resets_the_layer() {
+        let mut grid = Grid::new(64, 4);
+        grid.insert(&square(1, 0)).unwrap();
+        grid.set_layer(1, 3).unwrap();
+        grid.update(&square(1, 500)).unwrap();
+        assert_eq!(grid.layer(1), 3);
+        grid.delete(1);
+        assert_eq!(grid.layer(1), 0);
+        assert_eq!(grid.set_layer(1, 3), Err(Error::NotFound(1)));
+    }
+}
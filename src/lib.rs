@@ -32,10 +32,147 @@
  * BE ADVISED: WE WILL DEFEND OUR RIGHTS VIGOROUSLY.
  */
 
-use arrayvec::{ArrayVec, CapacityError};
+#![cfg_attr(feature = "safe", forbid(unsafe_code))]
 
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::slice::SliceIndex;
+
+#[cfg(not(feature = "spill-cells"))]
+use arrayvec::ArrayVec;
+use arrayvec::CapacityError;
+
+mod aggregate;
+mod aoi;
+mod approx;
+mod bounds;
+mod budget;
+mod bvh;
+mod ccd;
+#[cfg(feature = "spill-cells")]
+mod cell_list;
+mod coarse;
+mod commands;
+mod cuckoo;
+mod cursor;
+mod debug;
+mod dedup;
+mod delta;
+mod entry;
+mod expiry;
+mod export;
+mod falloff;
+mod frames;
+mod frozen;
+mod fuzz;
+mod grid64;
+mod groups;
+mod guard;
+mod handoff;
+mod history;
+mod idset;
+mod journal;
+mod large;
+mod layers;
+mod memory;
+mod movement;
+pub mod naive;
+mod origin;
+mod overlap;
+mod pairs;
+mod pathfind;
+mod placement;
+mod priority;
+mod profile;
+mod publish;
+mod quadtree;
+mod raw;
+mod record;
+mod regions;
+mod render;
+mod results;
+mod robin_hood;
+#[cfg(feature = "rand")]
+mod sample;
+mod scan;
+mod select;
+mod shapes;
+mod sight;
+mod size_class;
+mod static_grid;
+mod stats;
+mod steering;
+#[cfg(feature = "async")]
+mod stream;
+mod sweep;
+mod tags;
+mod templates;
+mod tilemap;
+mod transition;
+mod tuner;
+mod watermark;
+
+pub use aggregate::Aggregate;
+pub use aoi::{AoiEvents, Observers, Subscriptions};
+pub use approx::ApproxResults;
+pub use bounds::BoundsPolicy;
+pub use budget::{Budget, QueryToken};
+pub use bvh::StaticBvh;
+pub use ccd::toi;
+pub use commands::GridCommands;
+pub use cuckoo::CuckooTable;
+pub use cursor::QueryCursor;
+pub use entry::TableEntry;
+pub use falloff::Falloff;
+pub use frames::FrameStats;
+pub use frozen::FrozenGrid;
+pub use fuzz::Op;
+pub use grid64::{Entity64, Grid64, Query64};
+pub use guard::EntityGuard;
+pub use handoff::RegionSnapshot;
+pub use idset::IdSet;
+pub use layers::{LayerMask, MAX_LAYERS};
+pub use overlap::mtv;
+pub use pairs::{PairCache, PairEvents};
+pub use profile::{ProfileReport, QueryProfile};
+pub use publish::{GridReader, PublishedGrid};
+pub use quadtree::Quadtree;
+pub use raw::CellIds;
+#[cfg(feature = "record")]
+pub use record::ReplayError;
+pub use results::QueryResults;
+pub use robin_hood::RobinHoodTable;
+pub use select::Select;
+pub use shapes::{PolygonTest, Shape};
+pub use size_class::SizeClassGrid;
+pub use static_grid::StaticGrid;
+#[cfg(feature = "async")]
+pub use stream::QueryStream;
+pub use sweep::{Axis, SweepPrune};
+pub use tags::{Tag, MAX_TAGS};
+pub use templates::TemplateId;
+pub use transition::CellTransition;
+pub use tuner::ShiftStats;
+
+/// Default number of ids a single cell can hold.
 pub const FIXED_SIZE: usize = 32;
 
+/// Check whether an entity stored in the buckets cannot cover this many cells. There is no limit with `spill-cells`,
+/// whose cell lists move to the heap.
+#[inline(always)]
+fn too_many_cells(cells: u64) -> bool {
+    cfg!(not(feature = "spill-cells")) && cells > FIXED_SIZE as u64
+}
+
+#[cfg(not(feature = "spill-cells"))]
+type CellList = ArrayVec<(u32, u32), FIXED_SIZE>;
+#[cfg(feature = "spill-cells")]
+use cell_list::CellList;
+
+/// Result size at which queries stop deduplicating as they go and sort the results once instead.
+const SORT_DEDUP_THRESHOLD: usize = 256;
+
 /// A rectangular entity. **Identifier must be unique.**
 #[derive(Debug, Clone)]
 #[repr(C)]
@@ -58,6 +195,66 @@ pub struct Query {
     pub height: u32,
 }
 
+impl Entity {
+    /// Create an entity from its center and size. For odd sizes the extra unit goes to the right or bottom,
+    /// and edges that would fall below zero are cut off.
+    pub fn from_center(id: u32, cx: u32, cy: u32, width: u32, height: u32) -> Self {
+        let x = cx.saturating_sub(width / 2);
+        let y = cy.saturating_sub(height / 2);
+        Self {
+            id,
+            x,
+            y,
+            width: width - (width / 2 - (cx - x)),
+            height: height - (height / 2 - (cy - y)),
+        }
+    }
+
+    /// Create an entity covering every point within `radius` of a point on both axes, cut off at the edges of the `u32` range.
+    pub fn around_point(id: u32, x: u32, y: u32, radius: u32) -> Self {
+        Self::from_corners(
+            id,
+            x.saturating_sub(radius),
+            y.saturating_sub(radius),
+            x.saturating_add(radius),
+            y.saturating_add(radius),
+        )
+    }
+
+    /// Create an entity spanning two opposite corners, given in any order. Both corners are inside it.
+    pub fn from_corners(id: u32, x0: u32, y0: u32, x1: u32, y1: u32) -> Self {
+        Self {
+            id,
+            x: x0.min(x1),
+            y: y0.min(y1),
+            width: x0.abs_diff(x1),
+            height: y0.abs_diff(y1),
+        }
+    }
+
+    /// Get the center of the entity, rounded down.
+    pub fn center(&self) -> (u32, u32) {
+        (self.x + self.width / 2, self.y + self.height / 2)
+    }
+}
+
+impl Query {
+    /// Create a query region from its center and size, like `Entity::from_center`.
+    pub fn from_center(cx: u32, cy: u32, width: u32, height: u32) -> Self {
+        Entity::from_center(0, cx, cy, width, height).into()
+    }
+
+    /// Create a query region covering every point within `radius` of a point on both axes, like `Entity::around_point`.
+    pub fn around_point(x: u32, y: u32, radius: u32) -> Self {
+        Entity::around_point(0, x, y, radius).into()
+    }
+
+    /// Create a query region spanning two opposite corners, like `Entity::from_corners`.
+    pub fn from_corners(x0: u32, y0: u32, x1: u32, y1: u32) -> Self {
+        Entity::from_corners(0, x0, y0, x1, y1).into()
+    }
+}
+
 impl From<Entity> for Query {
     fn from(value: Entity) -> Self {
         Self {
@@ -69,13 +266,141 @@ impl From<Entity> for Query {
     }
 }
 
-#[derive(Debug, Clone, Default)]
-struct Entry(ArrayVec<u32, FIXED_SIZE>);
+impl From<&Entity> for Query {
+    fn from(value: &Entity) -> Self {
+        Self {
+            x: value.x,
+            y: value.y,
+            width: value.width,
+            height: value.height,
+        }
+    }
+}
+
+/// Errors returned by grid operations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// A cell was full, so an entity could not be stored in it. `written` of the entity's cells were stored before the full one,
+    /// and `rect` is the entity's `(x, y, width, height)`.
+    CellFull {
+        cell: (u32, u32),
+        id: u32,
+        written: usize,
+        rect: (u32, u32, u32, u32),
+    },
+    /// The entity would cover more than `FIXED_SIZE` cells. Never returned with the `spill-cells` feature.
+    TooManyCells(u32),
+    /// The id uses the high bit, which is reserved by the grid.
+    InvalidId(u32),
+    /// The entity is not registered in the grid.
+    NotFound(u32),
+    /// An entity with this id is already registered in the grid.
+    DuplicateId(u32),
+    /// `x + width` or `y + height` does not fit in a `u32`.
+    CoordinateOverflow,
+    /// The rectangle reaches outside the grid's world bounds.
+    OutOfBounds,
+    /// The id shares its entry in the entity table with another registered id, so it cannot be stored.
+    IdCollision(u32),
+    /// The entity is wider or taller than the grid's maximum entity size.
+    EntityTooLarge(u32),
+    /// The shape has a coordinate or size that is not finite, a negative radius or extent, or reaches outside the `u32` range.
+    InvalidShape(u32),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::CellFull {
+                cell: (x, y),
+                id,
+                written,
+                rect: (rx, ry, width, height),
+            } => write!(
+                f,
+                "cell ({}, {}) is full, cannot insert entity {} at ({}, {}) sized {}x{} after writing {} cells",
+                x, y, id, rx, ry, width, height, written
+            ),
+            Error::TooManyCells(id) => {
+                write!(f, "entity {} covers more than {} cells", id, FIXED_SIZE)
+            }
+            Error::InvalidId(id) => write!(f, "entity id {} uses the reserved high bit", id),
+            Error::NotFound(id) => write!(f, "entity {} is not in the grid", id),
+            Error::DuplicateId(id) => write!(f, "entity {} is already in the grid", id),
+            Error::CoordinateOverflow => write!(f, "rectangle extends past u32::MAX"),
+            Error::OutOfBounds => write!(f, "rectangle reaches outside the world bounds"),
+            Error::IdCollision(id) => write!(
+                f,
+                "entity {} shares its table entry with another registered entity",
+                id
+            ),
+            Error::EntityTooLarge(id) => write!(
+                f,
+                "entity {} exceeds the maximum entity size, use a large threshold or a bigger shift",
+                id
+            ),
+            Error::InvalidShape(id) => write!(f, "entity {} has an invalid shape", id),
+        }
+    }
+}
 
+impl std::error::Error for Error {}
+
+impl Error {
+    /// Check whether the operation was rejected before the grid was touched, so there is nothing to clean up.
+    pub(crate) fn is_rejection(&self) -> bool {
+        matches!(
+            self,
+            Error::TooManyCells(_)
+                | Error::InvalidId(_)
+                | Error::DuplicateId(_)
+                | Error::CoordinateOverflow
+                | Error::OutOfBounds
+                | Error::EntityTooLarge(_)
+                | Error::IdCollision(_)
+                | Error::InvalidShape(_)
+        )
+    }
+}
+
+/// The cells an entity was registered in, which layer they belong to, and the entity's last inserted rectangle.
 #[derive(Debug, Clone, Default)]
-struct Map(ArrayVec<(u32, u32), FIXED_SIZE>);
+struct Map {
+    cells: CellList,
+    is_static: bool,
+    id: u32,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    meta: Meta,
+    /// Whether the entity is in the large entity list rather than the cells, in which case `cells` is empty.
+    is_large: bool,
+}
+
+impl Map {
+    /// Check whether the entry holds an entity, in cells or in the large entity list.
+    #[inline(always)]
+    fn is_registered(&self) -> bool {
+        !self.cells.is_empty() || self.is_large
+    }
+}
+
+/// Per-entity data that `update`, rehashing and rollbacks keep and `delete` resets.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Meta {
+    priority: u8,
+    /// The user-facing layer, as opposed to the dynamic or static layer the cells belong to.
+    layer: u8,
+    group: Option<u32>,
+    tags: u64,
+    /// A numeric payload such as mass, folded by `query_aggregate`.
+    value: f32,
+}
 
 /// An extremely optimized fixed-size hash table implementation.
+/// Keys are reduced modulo the table size, so every key maps to a valid entry; the unchecked accessors never index
+/// out of bounds. Entries do not remember their key, though, so distinct keys can share an entry.
 #[derive(Debug, Clone)]
 pub struct Table<T: Default + Clone> {
     entries: Vec<T>,
@@ -84,8 +409,7 @@ pub struct Table<T: Default + Clone> {
 impl<T: Default + Clone> Table<T> {
     /// Create a new table with `size` entries.
     pub fn new(size: usize) -> Self {
-        let cached_entries_capacity = (size * 1000).next_power_of_two() + 1;
-        let entries = vec![T::default(); cached_entries_capacity];
+        let entries = vec![T::default(); table_size(size)];
         Self { entries }
     }
 
@@ -96,35 +420,57 @@ impl<T: Default + Clone> Table<T> {
 
     #[inline(always)]
     fn index(&self, idx: u64) -> usize {
-        (hash_u64(idx) % self.entries.len() as u64) as usize
+        slot_index(idx, self.entries.len())
     }
 
     /// Get a mutable reference to an entry from a 2D key.
     #[inline(always)]
     pub fn get_vector_mut(&mut self, x: u32, y: u32) -> &mut T {
         let idx = self.index(vector_hash(x, y));
-        unsafe { self.entries.get_unchecked_mut(idx) }
+        at_mut(&mut self.entries, idx)
     }
 
     /// Get a reference to an entry from a 2D key.
     #[inline(always)]
     pub fn get_vector(&self, x: u32, y: u32) -> &T {
         let idx = self.index(vector_hash(x, y));
-        unsafe { self.entries.get_unchecked(idx) }
+        at(&self.entries, idx)
     }
 
     /// Get a reference to an entry from a scalar key.
     #[inline(always)]
     pub fn get_scalar(&self, s: u32) -> &T {
         let idx = self.index(hash_u64(s as u64));
-        unsafe { self.entries.get_unchecked(idx) }
+        at(&self.entries, idx)
     }
 
     /// Get a mutable reference to an entry from a scalar key.
     #[inline(always)]
     pub fn get_scalar_mut(&mut self, s: u32) -> &mut T {
         let idx = self.index(hash_u64(s as u64));
-        unsafe { self.entries.get_unchecked_mut(idx) }
+        at_mut(&mut self.entries, idx)
+    }
+
+    /// Get a reference to an entry from a 2D key, with bounds-checked indexing even without the `safe` feature.
+    pub fn try_get_vector(&self, x: u32, y: u32) -> Option<&T> {
+        self.entries.get(self.index(vector_hash(x, y)))
+    }
+
+    /// Get a mutable reference to an entry from a 2D key, with bounds-checked indexing.
+    pub fn try_get_vector_mut(&mut self, x: u32, y: u32) -> Option<&mut T> {
+        let idx = self.index(vector_hash(x, y));
+        self.entries.get_mut(idx)
+    }
+
+    /// Get a reference to an entry from a scalar key, with bounds-checked indexing.
+    pub fn try_get_scalar(&self, s: u32) -> Option<&T> {
+        self.entries.get(self.index(hash_u64(s as u64)))
+    }
+
+    /// Get a mutable reference to an entry from a scalar key, with bounds-checked indexing.
+    pub fn try_get_scalar_mut(&mut self, s: u32) -> Option<&mut T> {
+        let idx = self.index(hash_u64(s as u64));
+        self.entries.get_mut(idx)
     }
 
     /// Clear the table.
@@ -135,21 +481,465 @@ impl<T: Default + Clone> Table<T> {
     }
 }
 
-/// Spatial hash grid implementation.
+/// What to do when an entity is inserted into a cell that is already full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    /// Fail the insert with `Error::CellFull`.
+    #[default]
+    Error,
+    /// Remove the oldest id from the full cell to make room. The evicted entity stays in any other cells it covers.
+    EvictOldest,
+    /// Store the extra ids in a heap-allocated overflow list for that cell.
+    SpillToHeap,
+    /// Skip the full cell. The incoming entity is not registered in it.
+    DropIncoming,
+}
+
+/// Which table an entity lives in, and whether it has a `Map`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Layer {
+    Dynamic,
+    Static,
+    Pinned,
+}
+
+/// How cell coordinates are mapped to slots in the grid's tables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CellLayout {
+    /// Pack the cell coordinates into a key, taken modulo the number of slots. Which cells share a slot depends on the
+    /// table size, and nearby cells can: on the 1025 slots of `Grid::new(1, shift)`, `(x, y)` shares one with `(x + 1, y + 4)`.
+    /// Ids of cells sharing a slot are returned by queries of either cell. Use `Sparse` to rule sharing out.
+    #[default]
+    Hashed,
+    /// Scramble the packed key, so regular patterns of cells spanning huge worlds don't pile into the same slots.
+    /// This gives up the locality of `Hashed`, which makes inserts and queries slower on smaller worlds.
+    Mixed,
+    /// Like `Mixed`, but with a secret seed folded into the key first. Without the seed, clients influencing entity
+    /// positions cannot aim them at colliding slots to overflow buckets. The mixing is fast, not cryptographic.
+    Salted(u64),
+    /// Interleave the bits of the cell coordinates (Z-order), so cells that are close together
+    /// are usually close together in memory. This can speed up queries covering many cells.
+    Morton,
+    /// Give every occupied cell its own slot through a `HashMap`, allocated on first insert and recycled once empty.
+    /// Cells never share a slot and the world size is unbounded, at the cost of a map lookup per cell visited.
+    Sparse,
+}
+
+impl CellLayout {
+    /// A `Salted` layout with a seed drawn from the standard library's per-process random keys.
+    pub fn salted() -> Self {
+        let seed = std::collections::hash_map::RandomState::new()
+            .build_hasher()
+            .finish();
+        CellLayout::Salted(seed)
+    }
+}
+
+/// Cell buckets stored in one contiguous slab. Every slot owns `capacity` consecutive ids,
+/// and the number of occupied ids per slot is kept in a separate, densely packed array so
+/// empty cells can be skipped without touching the id slab. A bitmap with one bit per slot marks the
+/// slots holding ids, so emptiness checks and full scans read 512 slots per cache line.
+/// Ids past capacity can be spilled to the heap; a slot only spills once its inline bucket is full.
 #[derive(Debug, Clone)]
+struct Buckets {
+    lens: Vec<u16>,
+    ids: Vec<u32>,
+    /// Bit `slot % 64` of word `slot / 64` is set while the slot holds ids.
+    occupied: Vec<u64>,
+    spill: HashMap<usize, Vec<u32>>,
+    capacity: usize,
+    layout: CellLayout,
+    /// Number of slots allocated for the fixed layouts.
+    slots: usize,
+    /// Slot of every occupied cell in the sparse layout. Slot 0 is never assigned and stays empty.
+    sparse: HashMap<(u32, u32), usize>,
+    /// Cell owning each sparse slot, used to release the slot once it empties.
+    cells: Vec<(u32, u32)>,
+    free: Vec<usize>,
+    /// The cell holding the grid's origin, subtracted from cell coordinates before they are mapped to slots.
+    origin: (u32, u32),
+}
+
+impl Buckets {
+    fn new(size: usize, capacity: usize, layout: CellLayout) -> Self {
+        let mut buckets = Self::deferred(size, capacity, layout);
+        buckets.allocate();
+        buckets
+    }
+
+    /// Create buckets without a slab: a single empty slot that every cell maps to until `allocate` is called.
+    fn deferred(size: usize, capacity: usize, layout: CellLayout) -> Self {
+        assert!(
+            capacity <= u16::MAX as usize,
+            "bucket capacity must not exceed {}",
+            u16::MAX
+        );
+        Self {
+            lens: vec![0],
+            ids: vec![0; capacity],
+            occupied: vec![0],
+            spill: HashMap::new(),
+            capacity,
+            layout,
+            slots: table_size(size),
+            sparse: HashMap::new(),
+            cells: Vec::new(),
+            free: Vec::new(),
+            origin: (0, 0),
+        }
+    }
+
+    /// Allocate the slab of deferred buckets. The sparse layout allocates slots as cells fill instead.
+    fn allocate(&mut self) {
+        if self.layout != CellLayout::Sparse && self.lens.len() != self.slots {
+            self.lens = vec![0; self.slots];
+            self.ids = vec![0; self.slots * self.capacity];
+            self.occupied = vec![0; self.slots.div_ceil(64)];
+        }
+    }
+
+    /// Give back the slab and every id, leaving the buckets as `deferred` made them.
+    fn defer(&mut self) {
+        self.lens = vec![0];
+        self.ids = vec![0; self.capacity];
+        self.occupied = vec![0];
+        self.spill = HashMap::new();
+        self.sparse = HashMap::new();
+        self.cells = Vec::new();
+        self.free = Vec::new();
+    }
+
+    /// Switch layouts, dropping every id. Switching to or from the sparse layout reallocates the slab.
+    fn set_layout(&mut self, layout: CellLayout) {
+        let slots = if layout == CellLayout::Sparse {
+            1
+        } else {
+            self.slots
+        };
+        self.layout = layout;
+        if self.lens.len() != slots {
+            self.lens = vec![0; slots];
+            self.ids = vec![0; slots * self.capacity];
+            self.occupied = vec![0; slots.div_ceil(64)];
+        }
+        self.clear();
+    }
+
+    fn count(&self) -> usize {
+        self.lens.len()
+    }
+
+    #[inline(always)]
+    fn slot(&self, x: u32, y: u32) -> usize {
+        // fixed layouts place cells relative to the origin cell, wrapping around below it.
+        let (rx, ry) = (x.wrapping_sub(self.origin.0), y.wrapping_sub(self.origin.1));
+        match self.layout {
+            CellLayout::Hashed => slot_index(vector_hash(rx, ry), self.lens.len()),
+            CellLayout::Mixed => slot_index(mix(vector_hash(rx, ry)), self.lens.len()),
+            CellLayout::Salted(seed) => {
+                slot_index(mix(vector_hash(rx, ry) ^ seed), self.lens.len())
+            }
+            // not hashed, since hashing would scatter neighboring codes.
+            CellLayout::Morton => (morton(rx, ry) % self.lens.len() as u64) as usize,
+            CellLayout::Sparse => self.sparse.get(&(x, y)).copied().unwrap_or(0),
+        }
+    }
+
+    /// Get the slot of a cell about to receive an id, allocating one in the sparse layout.
+    #[inline(always)]
+    fn slot_or_insert(&mut self, x: u32, y: u32) -> usize {
+        if self.layout != CellLayout::Sparse {
+            return self.slot(x, y);
+        }
+        if let Some(&slot) = self.sparse.get(&(x, y)) {
+            return slot;
+        }
+        let slot = match self.free.pop() {
+            Some(slot) => {
+                self.cells[slot] = (x, y);
+                slot
+            }
+            None => {
+                if self.cells.is_empty() {
+                    // reserve slot 0 for cells that were never allocated.
+                    self.cells.push((u32::MAX, u32::MAX));
+                }
+                self.cells.push((x, y));
+                self.lens.push(0);
+                self.ids.resize(self.ids.len() + self.capacity, 0);
+                self.occupied.resize(self.lens.len().div_ceil(64), 0);
+                self.lens.len() - 1
+            }
+        };
+        self.sparse.insert((x, y), slot);
+        slot
+    }
+
+    /// Give an emptied sparse slot back for reuse.
+    #[inline(always)]
+    fn release(&mut self, slot: usize) {
+        if self.layout == CellLayout::Sparse && slot != 0 && self.is_empty(slot) {
+            self.sparse.remove(&self.cells[slot]);
+            self.free.push(slot);
+        }
+    }
+
+    /// Check whether a slot holds no ids.
+    #[inline(always)]
+    fn is_empty(&self, slot: usize) -> bool {
+        *at(&self.occupied, slot / 64) & (1 << (slot % 64)) == 0
+    }
+
+    /// Iterate over the slots holding ids in ascending order, skipping 64 empty slots at a time.
+    fn occupied_slots(&self) -> impl Iterator<Item = usize> + '_ {
+        self.occupied
+            .iter()
+            .enumerate()
+            .filter(|(_, &word)| word != 0)
+            .flat_map(|(i, &word)| {
+                let mut bits = word;
+                std::iter::from_fn(move || {
+                    if bits == 0 {
+                        return None;
+                    }
+                    let slot = i * 64 + bits.trailing_zeros() as usize;
+                    bits &= bits - 1;
+                    Some(slot)
+                })
+            })
+    }
+
+    /// Get the ids stored in a slot.
+    #[inline(always)]
+    fn get(&self, slot: usize) -> &[u32] {
+        let start = slot * self.capacity;
+        let len = *at(&self.lens, slot) as usize;
+        at(&self.ids, start..start + len)
+    }
+
+    /// Get the inline and spilled ids of a slot for reordering them in place.
+    #[inline(always)]
+    fn get_mut(&mut self, slot: usize) -> (&mut [u32], &mut [u32]) {
+        let start = slot * self.capacity;
+        let len = *at(&self.lens, slot) as usize;
+        let inline = at_mut(&mut self.ids, start..start + len);
+        let spilled = match self.spill.get_mut(&slot) {
+            Some(ids) => ids.as_mut_slice(),
+            None => &mut [],
+        };
+        (inline, spilled)
+    }
+
+    /// Reserve room for `cells` more sparse slots. Dense layouts are allocated up front, so this only affects `Sparse`.
+    fn reserve(&mut self, cells: usize) {
+        if self.layout == CellLayout::Sparse {
+            self.sparse.reserve(cells);
+            self.cells.reserve(cells);
+            self.lens.reserve(cells);
+            self.ids.reserve(cells * self.capacity);
+            self.occupied.reserve(cells / 64 + 1);
+        }
+    }
+
+    /// Get the number of ids in a slot, spilled ones included.
+    #[inline(always)]
+    fn len(&self, slot: usize) -> usize {
+        *at(&self.lens, slot) as usize + self.spilled(slot).len()
+    }
+
+    /// Get the ids spilled to the heap from a slot.
+    #[inline(always)]
+    fn spilled(&self, slot: usize) -> &[u32] {
+        if self.spill.is_empty() {
+            return &[];
+        }
+        self.spill.get(&slot).map_or(&[], |ids| ids.as_slice())
+    }
+
+    /// Find the index of an id in a slot, ignoring the reserved high bit.
+    #[inline(always)]
+    fn position(&self, slot: usize, id: u32) -> Option<usize> {
+        let inline = self.get(slot);
+        scan::position(inline, id)
+            .or_else(|| scan::position(self.spilled(slot), id).map(|i| i + inline.len()))
+    }
+
+    /// Iterate over every id in a slot, inline ids first.
+    #[inline(always)]
+    fn iter(&self, slot: usize) -> impl Iterator<Item = &u32> {
+        self.get(slot).iter().chain(self.spilled(slot))
+    }
+
+    /// Append an id to a slot, failing if the slot is full.
+    #[inline(always)]
+    fn try_push(&mut self, slot: usize, id: u32) -> Result<(), CapacityError<u32>> {
+        let len = at_mut(&mut self.lens, slot);
+        if *len as usize == self.capacity {
+            return Err(CapacityError::new(id));
+        }
+        let idx = slot * self.capacity + *len as usize;
+        *len += 1;
+        *at_mut(&mut self.ids, idx) = id;
+        *at_mut(&mut self.occupied, slot / 64) |= 1 << (slot % 64);
+        Ok(())
+    }
+
+    /// Append an id to the heap overflow list of a full slot.
+    fn push_spilled(&mut self, slot: usize, id: u32) {
+        self.spill.entry(slot).or_default().push(id);
+    }
+
+    /// Remove the id at `index` in a slot, preserving the order of the remaining ids.
+    /// Indices past the inline length address the spilled ids.
+    #[inline(always)]
+    fn remove(&mut self, slot: usize, index: usize) -> u32 {
+        let start = slot * self.capacity;
+        let len = at_mut(&mut self.lens, slot);
+        if index >= *len as usize {
+            let spilled = self.spill.get_mut(&slot).unwrap();
+            let id = spilled.remove(index - *len as usize);
+            if spilled.is_empty() {
+                self.spill.remove(&slot);
+            }
+            return id;
+        }
+
+        let id = self.ids[start + index];
+        self.ids
+            .copy_within(start + index + 1..start + *len as usize, start + index);
+        *len -= 1;
+
+        // keep the inline bucket full while the slot still has spilled ids.
+        if !self.spill.is_empty() {
+            if let Some(spilled) = self.spill.get_mut(&slot) {
+                let refill = spilled.remove(0);
+                if spilled.is_empty() {
+                    self.spill.remove(&slot);
+                }
+                self.ids[start + *len as usize] = refill;
+                *len += 1;
+            }
+        }
+        if *len == 0 {
+            *at_mut(&mut self.occupied, slot / 64) &= !(1 << (slot % 64));
+        }
+        id
+    }
+
+    fn clear(&mut self) {
+        if self.layout == CellLayout::Sparse {
+            self.lens.truncate(1);
+            self.ids.truncate(self.capacity);
+            self.occupied.truncate(1);
+            self.sparse.clear();
+            self.cells.clear();
+            self.free.clear();
+        }
+        self.lens.fill(0);
+        self.occupied.fill(0);
+        self.spill.clear();
+    }
+}
+
+/// Spatial hash grid implementation.
+#[derive(Clone)]
 pub struct Grid {
-    grid: Table<Entry>,
+    grid: Buckets,
+    statics: Buckets,
+    has_statics: bool,
     maps: Table<Map>,
     shift: u32,
+    overflow: OverflowPolicy,
+    changes: Option<Vec<u32>>,
+    scratch: Vec<u32>,
+    /// Cells entered and left by the last `update_with_transition`.
+    entered: Vec<(u32, u32)>,
+    left: Vec<(u32, u32)>,
+    journal: Option<Vec<journal::JournalEntry>>,
+    tick: u64,
+    seen: dedup::SeenTable,
+    /// Buffers of recycled query results.
+    results: results::ResultPool,
+    profile: profile::Profile,
+    frames: frames::Frames,
+    /// Rectangles of pinned entities, which have no `Map`.
+    pinned: HashMap<u32, Entity>,
+    /// Exact shapes of entities inserted with `insert_shape`.
+    shapes: HashMap<u32, Shape>,
+    polygon_test: Option<PolygonTest>,
+    /// Ids that joined each group. Entries for entities that left are only dropped lazily.
+    groups: HashMap<u32, Vec<u32>>,
+    /// Sizes and tags registered with `register_template`, indexed by `TemplateId`.
+    templates: Vec<templates::Template>,
+    expiries: expiry::Expiries,
+    history: history::History,
+    delta: Option<delta::DeltaLog>,
+    watermark: Option<usize>,
+    /// Cells that reached the watermark since the last `take_hot_cells`.
+    hot: Vec<(u32, u32)>,
+    /// Id counts per block of cells, if `set_coarse_level` enabled them.
+    coarse: Option<coarse::Coarse>,
+    /// Ids of the entities stored outside the cells, see `set_large_threshold`.
+    large: Vec<u32>,
+    large_threshold: Option<usize>,
+    bounds: Option<Query>,
+    bounds_policy: BoundsPolicy,
+    max_size: Option<(u32, u32)>,
+    origin: (u32, u32),
+    recorder: record::Recorder,
 }
 
 impl Grid {
     /// Create a new grid with a fixed bucket size and cell size.
     pub fn new(size: usize, shift: u32) -> Self {
+        Self::with_bucket_capacity(size, shift, FIXED_SIZE)
+    }
+
+    /// Create a new grid whose cells each hold up to `capacity` ids, chosen at runtime.
+    /// `capacity` must not exceed `u16::MAX`.
+    pub fn with_bucket_capacity(size: usize, shift: u32, capacity: usize) -> Self {
+        Self::with_cell_layout(size, shift, capacity, CellLayout::default())
+    }
+
+    /// Create a new grid with a bucket capacity and cell layout chosen at runtime.
+    /// With `CellLayout::Sparse`, slots are only allocated for occupied cells, so `size` only matters if the layout is switched later.
+    pub fn with_cell_layout(size: usize, shift: u32, capacity: usize, layout: CellLayout) -> Self {
         Self {
-            grid: Table::new(size),
+            grid: Buckets::new(size, capacity, layout),
+            // most grids never insert a static entity, so the static slab is allocated by the first one.
+            statics: Buckets::deferred(size, capacity, layout),
+            has_statics: false,
             maps: Table::new(size),
             shift,
+            overflow: OverflowPolicy::default(),
+            changes: None,
+            scratch: Vec::new(),
+            entered: Vec::new(),
+            left: Vec::new(),
+            journal: None,
+            tick: 0,
+            seen: dedup::SeenTable::enabled(),
+            results: results::ResultPool::default(),
+            profile: profile::Profile::default(),
+            frames: frames::Frames::default(),
+            pinned: HashMap::new(),
+            shapes: HashMap::new(),
+            polygon_test: None,
+            groups: HashMap::new(),
+            templates: Vec::new(),
+            expiries: expiry::Expiries::default(),
+            history: history::History::default(),
+            delta: None,
+            watermark: None,
+            hot: Vec::new(),
+            coarse: None,
+            large: Vec::new(),
+            large_threshold: None,
+            recorder: record::Recorder::default(),
+            bounds: None,
+            bounds_policy: BoundsPolicy::default(),
+            max_size: None,
+            origin: (0, 0),
         }
     }
 
@@ -158,85 +948,1348 @@ impl Grid {
         self.grid.count()
     }
 
-    /// Insert an entity.
-    pub fn insert(&mut self, entity: &Entity) -> Result<(), CapacityError<u32>> {
+    /// Get the number of ids a single cell can hold.
+    pub fn bucket_capacity(&self) -> usize {
+        self.grid.capacity
+    }
+
+    /// Get the policy applied when an insert hits a full cell.
+    pub fn overflow_policy(&self) -> OverflowPolicy {
+        self.overflow
+    }
+
+    /// Set the policy applied when an insert hits a full cell.
+    pub fn set_overflow_policy(&mut self, policy: OverflowPolicy) {
+        self.recorder.record(record::Op::Overflow(policy));
+        self.overflow = policy;
+    }
+
+    /// Get how cells are mapped to slots.
+    pub fn cell_layout(&self) -> CellLayout {
+        self.grid.layout
+    }
+
+    /// Switch how cells are mapped to slots, rebuilding the grid like `rehash_with_shift` if the layout changes.
+    pub fn set_cell_layout(&mut self, layout: CellLayout) -> Result<(), Error> {
+        if layout == self.grid.layout {
+            return Ok(());
+        }
+        self.grid.set_layout(layout);
+        if self.has_statics {
+            self.statics.set_layout(layout);
+        } else {
+            self.statics.layout = layout;
+        }
+        self.rehash_with_shift(self.shift)
+    }
+
+    /// Enable or disable change tracking. While enabled, the grid records the ids of entities whose cells changed.
+    pub fn set_change_tracking(&mut self, enabled: bool) {
+        self.changes = enabled.then(Vec::new);
+    }
+
+    /// Take the ids of entities whose cells changed since the last call, sorted and deduplicated.
+    /// Always empty unless change tracking is enabled. `clear` does not record changes.
+    pub fn take_changes(&mut self) -> Vec<u32> {
+        let mut changes = match &mut self.changes {
+            Some(changes) => std::mem::take(changes),
+            None => return Vec::new(),
+        };
+        changes.sort_unstable();
+        changes.dedup();
+        changes
+    }
+
+    /// Insert an entity. Full cells are handled according to the grid's `OverflowPolicy`.
+    /// Rectangles extending past `u32::MAX` are cut off there; use `try_insert` to reject them instead.
+    /// Rectangles outside the world bounds are rejected or clamped according to the bounds policy.
+    /// Ids that are already registered in any layer are rejected with `Error::DuplicateId`; use `update` or `insert_replace` to move them.
+    /// Ids sharing their entity table entry with another registered id are rejected with `Error::IdCollision`.
+    /// Entities covering more than `FIXED_SIZE` cells are rejected with `Error::TooManyCells` unless they go to the large list.
+    pub fn insert(&mut self, entity: &Entity) -> Result<(), Error> {
         let sx = entity.x >> self.shift;
         let sy = entity.y >> self.shift;
 
-        let ex = (entity.x + entity.width) >> self.shift;
-        let ey = (entity.y + entity.height) >> self.shift;
+        let ex = entity.x.saturating_add(entity.width) >> self.shift;
+        let ey = entity.y.saturating_add(entity.height) >> self.shift;
 
+        self.insert_cells(entity, sx, sy, ex, ey, Layer::Dynamic)
+    }
+
+    /// Insert an entity into the dynamic layer, first removing its old placement if the id is already registered.
+    /// The old placement is only removed once the new one passed validation. Pinned ids are rejected with `Error::DuplicateId`.
+    pub fn insert_replace(&mut self, entity: &Entity) -> Result<(), Error> {
+        let clamped = self.bound_entity(entity)?;
+        self.check_size(clamped.as_ref().unwrap_or(entity))?;
+        if self.pinned.contains_key(&entity.id) {
+            return Err(Error::DuplicateId(entity.id));
+        }
+        let map = self.maps.get_scalar(entity.id);
+        if map.id == entity.id && map.is_registered() {
+            self.delete(entity.id);
+        }
+        self.insert(entity)
+    }
+
+    /// Insert an entity that never moves into the static layer. Static entities live in their own table,
+    /// so per-frame deletes, updates and `clear_dynamic` never touch them, while queries still return them.
+    pub fn insert_static(&mut self, entity: &Entity) -> Result<(), Error> {
+        let sx = entity.x >> self.shift;
+        let sy = entity.y >> self.shift;
+
+        let ex = entity.x.saturating_add(entity.width) >> self.shift;
+        let ey = entity.y.saturating_add(entity.height) >> self.shift;
+
+        self.statics.allocate();
+        self.has_statics = true;
+        self.insert_cells(entity, sx, sy, ex, ey, Layer::Static)
+    }
+
+    /// Insert an entity that will never move or be deleted individually, such as decorative props. Pinned entities
+    /// live in the static layer but get no per-entity map: only their rectangle is kept, in a map keyed by id, so they
+    /// never take or collide over an entry of the entity table, and are skipped by the undo journal and change tracking.
+    /// The entity table is allocated up front either way, so pinning saves no memory over `insert_static`.
+    /// `delete` and `update` ignore them; only `clear` removes them.
+    pub fn insert_pinned(&mut self, entity: &Entity) -> Result<(), Error> {
+        let sx = entity.x >> self.shift;
+        let sy = entity.y >> self.shift;
+
+        let ex = entity.x.saturating_add(entity.width) >> self.shift;
+        let ey = entity.y.saturating_add(entity.height) >> self.shift;
+
+        self.statics.allocate();
+        self.has_statics = true;
+        self.insert_cells(entity, sx, sy, ex, ey, Layer::Pinned)
+    }
+
+    /// Insert an entity without panicking. The id, coordinates, world bounds, size and cell count are validated before the grid is touched,
+    /// and ids that are already registered are rejected with `Error::DuplicateId`, or `Error::IdCollision` if another id holds their table entry.
+    pub fn try_insert(&mut self, entity: &Entity) -> Result<(), Error> {
+        if entity.id & (1 << 31) != 0 {
+            return Err(Error::InvalidId(entity.id));
+        }
+        let (x, y, width, height) =
+            self.bounded(entity.x, entity.y, entity.width, entity.height)?;
+        let entity = &Entity {
+            id: entity.id,
+            x,
+            y,
+            width,
+            height,
+        };
+        self.check_size(entity)?;
+
+        let (sx, sy, ex, ey) =
+            self.checked_cells(entity.x, entity.y, entity.width, entity.height)?;
+
+        let map = self.maps.get_scalar(entity.id);
+        if (map.id == entity.id && map.is_registered()) || self.pinned.contains_key(&entity.id) {
+            return Err(Error::DuplicateId(entity.id));
+        }
+        if map.is_registered() {
+            return Err(Error::IdCollision(entity.id));
+        }
+        let cells = (ex - sx + 1) as u64 * (ey - sy + 1) as u64;
+        let mapped = map.cells.len() as u64;
+        if too_many_cells(mapped + cells) && !self.goes_large(cells, Layer::Dynamic) {
+            return Err(Error::TooManyCells(entity.id));
+        }
+
+        self.insert_cells(entity, sx, sy, ex, ey, Layer::Dynamic)
+    }
+
+    fn insert_cells(
+        &mut self,
+        entity: &Entity,
+        sx: u32,
+        sy: u32,
+        ex: u32,
+        ey: u32,
+        layer: Layer,
+    ) -> Result<(), Error> {
+        self.insert_cells_where(entity, sx, sy, ex, ey, layer, |_, _| true)
+    }
+
+    /// Like `insert_cells`, but skipping the cells in the range that `covers` rejects.
+    #[allow(clippy::too_many_arguments)]
+    fn insert_cells_where(
+        &mut self,
+        entity: &Entity,
+        sx: u32,
+        sy: u32,
+        ex: u32,
+        ey: u32,
+        layer: Layer,
+        covers: impl Fn(u32, u32) -> bool,
+    ) -> Result<(), Error> {
+        let clamped = self.bound_entity(entity)?;
+        let (entity, sx, sy, ex, ey) = match &clamped {
+            Some(clamped) => (
+                clamped,
+                clamped.x >> self.shift,
+                clamped.y >> self.shift,
+                clamped.x.saturating_add(clamped.width) >> self.shift,
+                clamped.y.saturating_add(clamped.height) >> self.shift,
+            ),
+            None => (entity, sx, sy, ex, ey),
+        };
+        self.check_size(entity)?;
+        let map = self.maps.get_scalar(entity.id);
+        if (map.id == entity.id && map.is_registered()) || self.pinned.contains_key(&entity.id) {
+            return Err(Error::DuplicateId(entity.id));
+        }
+        if map.is_registered() && layer != Layer::Pinned {
+            return Err(Error::IdCollision(entity.id));
+        }
+        // pinned entities keep no cell list, and large ones keep none in the buckets.
+        let range = (ex - sx + 1) as u64 * (ey - sy + 1) as u64;
+        if layer != Layer::Pinned && too_many_cells(range) && !self.goes_large(range, layer) {
+            let covered = (sy..=ey)
+                .flat_map(|y| (sx..=ex).map(move |x| (x, y)))
+                .filter(|&(x, y)| covers(x, y))
+                .take(FIXED_SIZE + 1)
+                .count();
+            if too_many_cells(covered as u64) {
+                return Err(Error::TooManyCells(entity.id));
+            }
+        }
+        self.recorder.record(match layer {
+            Layer::Dynamic => record::Op::Insert(entity),
+            Layer::Static => record::Op::InsertStatic(entity),
+            Layer::Pinned => record::Op::InsertPinned(entity),
+        });
+        self.frames.insert();
+        let id = entity.id;
+        // `insert_shape` stores the shape again once the cells are written.
+        if !self.shapes.is_empty() {
+            self.shapes.remove(&id);
+        }
         let is_ideal = sx == ex && sy == ey;
+        let tagged = id | ((is_ideal as u32) << 31);
+        let is_pinned = layer == Layer::Pinned;
+        let is_large = self.goes_large((ex - sx + 1) as u64 * (ey - sy + 1) as u64, layer);
+        if is_pinned {
+            self.pinned.insert(id, entity.clone());
+        } else {
+            self.record(id);
+            let map = self.maps.get_scalar_mut(id);
+            map.is_static = layer == Layer::Static;
+            map.id = id;
+            map.x = entity.x;
+            map.y = entity.y;
+            map.width = entity.width;
+            map.height = entity.height;
+            if let Some(changes) = &mut self.changes {
+                changes.push(id);
+            }
+            if is_large {
+                if !map.is_large {
+                    map.is_large = true;
+                    self.large.push(id);
+                }
+                return Ok(());
+            }
+        }
+        self.push_cells(entity, tagged, layer, sx, sy, ex, ey, covers)
+    }
+
+    /// Store an entity's id, tagged if it is ideal, in the cells of a range that `covers` accepts, and add them to its map.
+    /// Full cells are handled according to the grid's `OverflowPolicy`.
+    #[allow(clippy::too_many_arguments)]
+    fn push_cells(
+        &mut self,
+        entity: &Entity,
+        tagged: u32,
+        layer: Layer,
+        sx: u32,
+        sy: u32,
+        ex: u32,
+        ey: u32,
+        covers: impl Fn(u32, u32) -> bool,
+    ) -> Result<(), Error> {
+        let id = entity.id;
+        let is_pinned = layer == Layer::Pinned;
+        let mut written = 0;
+        self.profile.insert();
+        let buckets = if layer == Layer::Dynamic {
+            &mut self.grid
+        } else {
+            &mut self.statics
+        };
 
-        let map = self.maps.get_scalar_mut(entity.id);
         for y in sy..=ey {
             for x in sx..=ex {
-                let cell = self.grid.get_vector_mut(x, y);
-                map.0.push((x, y));
-                cell.0.try_push(entity.id | ((is_ideal as u32) << 31))?;
+                if !covers(x, y) {
+                    continue;
+                }
+                let slot = buckets.slot_or_insert(x, y);
+                if buckets.try_push(slot, tagged).is_err() {
+                    self.profile.overflow();
+                    let full = Error::CellFull {
+                        cell: (x, y),
+                        id,
+                        written,
+                        rect: (entity.x, entity.y, entity.width, entity.height),
+                    };
+                    match self.overflow {
+                        OverflowPolicy::Error => return Err(full),
+                        OverflowPolicy::EvictOldest => {
+                            // the evicted entity no longer occupies this cell, so drop it from its map.
+                            let evicted = buckets.remove(slot, 0) & !(1 << 31);
+                            if let Some(changes) = &mut self.changes {
+                                changes.push(evicted);
+                            }
+                            let map = self.maps.get_scalar_mut(evicted);
+                            if let Some(index) = map
+                                .cells
+                                .iter()
+                                .position(|&(x, y)| buckets.slot(x, y) == slot)
+                                .filter(|_| map.id == evicted)
+                            {
+                                let cell = map.cells.remove(index);
+                                if let Some(coarse) = &mut self.coarse {
+                                    coarse.remove(cell, map.is_static);
+                                }
+                            }
+                            buckets.try_push(slot, tagged).map_err(|_| full)?;
+                        }
+                        OverflowPolicy::SpillToHeap => buckets.push_spilled(slot, tagged),
+                        OverflowPolicy::DropIncoming => continue,
+                    }
+                }
+                if self
+                    .watermark
+                    .is_some_and(|watermark| buckets.len(slot) == watermark)
+                {
+                    self.hot.push((x, y));
+                }
+                self.profile.write(buckets.len(slot), buckets.capacity);
+                if let Some(coarse) = &mut self.coarse {
+                    coarse.add((x, y), layer != Layer::Dynamic);
+                }
+                if !is_pinned {
+                    self.maps.get_scalar_mut(id).cells.push((x, y));
+                }
+                written += 1;
             }
         }
 
         Ok(())
     }
 
-    /// Delete an entity by ID.
+    /// Compute the cell range covered by a rectangle, failing if its far edge overflows.
+    fn checked_cells(
+        &self,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<(u32, u32, u32, u32), Error> {
+        let right = x.checked_add(width).ok_or(Error::CoordinateOverflow)?;
+        let bottom = y.checked_add(height).ok_or(Error::CoordinateOverflow)?;
+        Ok((
+            x >> self.shift,
+            y >> self.shift,
+            right >> self.shift,
+            bottom >> self.shift,
+        ))
+    }
+
+    /// Delete an entity by ID. Ids that are not registered are ignored.
     pub fn delete(&mut self, id: u32) {
+        if !self.shapes.is_empty() {
+            self.shapes.remove(&id);
+        }
+        // the table entry may hold another id sharing it, which must be left alone.
+        let map = self.maps.get_scalar(id);
+        if map.id != id && map.is_registered() {
+            return;
+        }
+        self.recorder.record(record::Op::Delete(id));
+        self.frames.delete();
+        self.record(id);
+        let map = self.maps.get_scalar(id);
+        let buckets = if map.is_static {
+            &mut self.statics
+        } else {
+            &mut self.grid
+        };
+        for &(x, y) in map.cells.iter() {
+            let slot = buckets.slot(x, y);
+            // a cell the buckets no longer hold the id in has nothing left to remove.
+            if let Some(index) = buckets.position(slot, id) {
+                buckets.remove(slot, index);
+                buckets.release(slot);
+                if let Some(coarse) = &mut self.coarse {
+                    coarse.remove((x, y), map.is_static);
+                }
+            }
+        }
+
+        let map = self.maps.get_scalar_mut(id);
+        map.cells.clear();
+        map.meta = Meta::default();
+        // a later `update` of the id inserts it anew, into the dynamic layer.
+        map.is_static = false;
+        if map.is_large {
+            map.is_large = false;
+            self.large.retain(|&large| large != id);
+        }
+        self.expiries.remove(id);
+        if let Some(changes) = &mut self.changes {
+            changes.push(id);
+        }
+    }
+
+    /// Delete an entity by ID without panicking. Cells that do contain the entity are still cleared if another one does not.
+    pub fn try_delete(&mut self, id: u32) -> Result<(), Error> {
+        let map = self.maps.get_scalar(id);
+        if !map.is_registered() || map.id != id {
+            return Err(Error::NotFound(id));
+        }
+        self.recorder.record(record::Op::Delete(id));
+        self.frames.delete();
+        self.record(id);
         let map = self.maps.get_scalar(id);
-        for &(x, y) in map.0.iter() {
-            let cell = self.grid.get_vector_mut(x, y);
-            let index = cell.0.iter().position(|x| (*x & !(1 << 31)) == id).unwrap();
-            cell.0.remove(index);
+        let buckets = if map.is_static {
+            &mut self.statics
+        } else {
+            &mut self.grid
+        };
+
+        let mut missing = false;
+        for &(x, y) in map.cells.iter() {
+            let slot = buckets.slot(x, y);
+            match buckets.position(slot, id) {
+                Some(index) => {
+                    buckets.remove(slot, index);
+                    buckets.release(slot);
+                    if let Some(coarse) = &mut self.coarse {
+                        coarse.remove((x, y), map.is_static);
+                    }
+                }
+                None => missing = true,
+            }
         }
 
-        self.maps.get_scalar_mut(id).0.clear();
+        let map = self.maps.get_scalar_mut(id);
+        map.cells.clear();
+        map.meta = Meta::default();
+        // a later `update` of the id inserts it anew, into the dynamic layer.
+        map.is_static = false;
+        if map.is_large {
+            map.is_large = false;
+            self.large.retain(|&large| large != id);
+        }
+        if !self.shapes.is_empty() {
+            self.shapes.remove(&id);
+        }
+        self.expiries.remove(id);
+        if let Some(changes) = &mut self.changes {
+            changes.push(id);
+        }
+        if missing {
+            return Err(Error::NotFound(id));
+        }
+        Ok(())
+    }
+
+    /// Move or resize an entity, keeping it in the same layer. Its cells are only rewritten if the range of cells it covers changed.
+    /// Rectangles outside the world bounds are rejected or clamped according to the bounds policy before anything changes.
+    /// If the new cells do not fit, such as on a full cell, the entity is put back where it was and the error is returned.
+    pub fn update(&mut self, entity: &Entity) -> Result<(), Error> {
+        let clamped = self.bound_entity(entity)?;
+        let entity = clamped.as_ref().unwrap_or(entity);
+        let sx = entity.x >> self.shift;
+        let sy = entity.y >> self.shift;
+
+        let ex = entity.x.saturating_add(entity.width) >> self.shift;
+        let ey = entity.y.saturating_add(entity.height) >> self.shift;
+
+        self.check_size(entity)?;
+        let map = self.maps.get_scalar(entity.id);
+        if map.cells.is_empty() && self.pinned.contains_key(&entity.id) {
+            return Ok(());
+        }
+        if map.is_registered() && map.id != entity.id {
+            return Err(Error::IdCollision(entity.id));
+        }
+        let cells = (ex - sx + 1) as u64 * (ey - sy + 1) as u64;
+        // the old cells are deleted before the new ones are written, so check they fit first.
+        if too_many_cells(cells) && !self.goes_large(cells, Layer::Dynamic) {
+            return Err(Error::TooManyCells(entity.id));
+        }
+        let cells = cells as usize;
+        if map.cells.len() == cells
+            && map.cells.first() == Some(&(sx, sy))
+            && map.cells.last() == Some(&(ex, ey))
+        {
+            self.recorder.record(record::Op::Update(entity));
+            self.frames.update();
+            self.record(entity.id);
+            if !self.shapes.is_empty() {
+                self.shapes.remove(&entity.id);
+            }
+            let map = self.maps.get_scalar_mut(entity.id);
+            map.x = entity.x;
+            map.y = entity.y;
+            map.width = entity.width;
+            map.height = entity.height;
+            return Ok(());
+        }
+
+        let is_static = map.is_static;
+        let meta = map.meta;
+        let old = map.is_registered().then_some(Entity {
+            id: entity.id,
+            x: map.x,
+            y: map.y,
+            width: map.width,
+            height: map.height,
+        });
+        let shape = self.shapes.get(&entity.id).cloned();
+        let expiry = self.expiry(entity.id);
+        self.delete(entity.id);
+        let layer = if is_static {
+            Layer::Static
+        } else {
+            Layer::Dynamic
+        };
+        let result = self.insert_cells(entity, sx, sy, ex, ey, layer);
+        if let Some(old) = old.filter(|_| result.is_err()) {
+            // the new cells did not fit, so clear the ones written and put the entity back where it was.
+            self.delete(entity.id);
+            match shape {
+                Some(shape) => self.insert_shape(entity.id, shape).ok(),
+                None => {
+                    let sx = old.x >> self.shift;
+                    let sy = old.y >> self.shift;
+                    let ex = old.x.saturating_add(old.width) >> self.shift;
+                    let ey = old.y.saturating_add(old.height) >> self.shift;
+                    self.insert_cells(&old, sx, sy, ex, ey, layer).ok()
+                }
+            };
+        }
+        self.frames.reinsert();
+        let map = self.maps.get_scalar_mut(entity.id);
+        map.meta = meta;
+        if let Some(tick) = expiry.filter(|_| map.is_registered()) {
+            self.expiries.restore(entity.id, tick);
+        }
+        result
     }
 
-    /// Retrieve entities in a region.
-    pub fn query(&self, query: &Query) -> Vec<u32> {
-        let mut result = Vec::new();
+    /// Move or resize an entity without panicking. The id, coordinates, world bounds, size and cell count are validated before the grid is touched.
+    pub fn try_update(&mut self, entity: &Entity) -> Result<(), Error> {
+        if entity.id & (1 << 31) != 0 {
+            return Err(Error::InvalidId(entity.id));
+        }
+        let (x, y, width, height) =
+            self.bounded(entity.x, entity.y, entity.width, entity.height)?;
+        let entity = &Entity {
+            id: entity.id,
+            x,
+            y,
+            width,
+            height,
+        };
+        self.check_size(entity)?;
+
+        let (sx, sy, ex, ey) =
+            self.checked_cells(entity.x, entity.y, entity.width, entity.height)?;
+        let cells = (ex - sx + 1) as u64 * (ey - sy + 1) as u64;
+        if too_many_cells(cells) && !self.goes_large(cells, Layer::Dynamic) {
+            return Err(Error::TooManyCells(entity.id));
+        }
 
+        self.update(entity)
+    }
+
+    /// Retrieve entities in a region, in no particular order.
+    pub fn query(&self, query: &Query) -> QueryResults {
         let sx = query.x >> self.shift;
         let sy = query.y >> self.shift;
 
-        let ex = (query.x + query.width) >> self.shift;
-        let ey = (query.y + query.height) >> self.shift;
+        let ex = query.x.saturating_add(query.width) >> self.shift;
+        let ey = query.y.saturating_add(query.height) >> self.shift;
+
+        self.query_cells(sx, sy, ex, ey, usize::MAX)
+    }
+
+    /// Retrieve up to `max` entities in a region, stopping the traversal as soon as that many unique ids were found.
+    pub fn query_n(&self, query: &Query, max: usize) -> QueryResults {
+        let sx = query.x >> self.shift;
+        let sy = query.y >> self.shift;
+
+        let ex = query.x.saturating_add(query.width) >> self.shift;
+        let ey = query.y.saturating_add(query.height) >> self.shift;
+
+        self.query_cells(sx, sy, ex, ey, max)
+    }
+
+    /// Retrieve entities in a region without panicking on coordinate overflow, applying the world bounds.
+    pub fn try_query(&self, query: &Query) -> Result<QueryResults, Error> {
+        let (x, y, width, height) = self.bounded(query.x, query.y, query.width, query.height)?;
+        let (sx, sy, ex, ey) = self.checked_cells(x, y, width, height)?;
+        Ok(self.query_cells(sx, sy, ex, ey, usize::MAX))
+    }
+
+    /// Retrieve entities in the cell containing a point.
+    pub fn query_point(&self, x: u32, y: u32) -> QueryResults {
+        let cx = x >> self.shift;
+        let cy = y >> self.shift;
+        self.query_cells(cx, cy, cx, cy, usize::MAX)
+    }
+
+    /// Find every pair of entities sharing a cell. Each pair is reported once, smallest id first.
+    /// Pairs of two static entities are not reported. This iterates over every cell.
+    pub fn find_pairs(&self) -> Vec<(u32, u32)> {
+        let mut pairs = Vec::new();
+        let mut cell = Vec::new();
+        for slot in self.grid.occupied_slots() {
+            cell.clear();
+            cell.extend(self.grid.iter(slot).map(|id| id & !(1 << 31)));
+            let dynamics = cell.len();
+            if self.has_statics {
+                // sparse layers allocate slots independently, so look the cell up again.
+                let slot = match self.grid.layout {
+                    CellLayout::Sparse => {
+                        let (x, y) = self.grid.cells[slot];
+                        self.statics.slot(x, y)
+                    }
+                    _ => slot,
+                };
+                cell.extend(self.statics.iter(slot).map(|id| id & !(1 << 31)));
+            }
+            for (i, &a) in cell[..dynamics].iter().enumerate() {
+                for &b in &cell[i + 1..] {
+                    if a != b {
+                        pairs.push((a.min(b), a.max(b)));
+                    }
+                }
+            }
+        }
+        for &a in &self.large {
+            for b in self.large_pairs_of(a) {
+                pairs.push((a.min(b), a.max(b)));
+            }
+        }
+        pairs.sort_unstable();
+        pairs.dedup();
+        pairs
+    }
+
+    fn query_cells(&self, sx: u32, sy: u32, ex: u32, ey: u32, limit: usize) -> QueryResults {
+        let mut result = self.results.take();
+        self.collect_cells(sx, sy, ex, ey, limit, |_| true, &mut result);
+        self.frames.query(result.len());
+        QueryResults::new(result)
+    }
+
+    /// Collect the unique ids in a range of cells that `accept` passes into `result`, which must be empty.
+    #[allow(clippy::too_many_arguments)]
+    fn collect_cells(
+        &self,
+        sx: u32,
+        sy: u32,
+        ex: u32,
+        ey: u32,
+        limit: usize,
+        accept: impl Fn(u32) -> bool,
+        result: &mut Vec<u32>,
+    ) {
+        if limit == 0 {
+            return;
+        }
 
         let is_ideal = sx == ex && sy == ey;
+        // past this many results, deduplicating by linear scans costs more than one sort at the end.
+        // limited queries always deduplicate as they go so the limit counts unique ids.
+        let mut deferred = false;
+        let threshold = if limit == usize::MAX {
+            SORT_DEDUP_THRESHOLD
+        } else {
+            usize::MAX
+        };
 
-        for y in sy..=ey {
-            for x in sx..=ex {
-                let region = self.grid.get_vector(x, y);
-                for id in region.0.iter() {
-                    // there CANNOT be duplicates if we are only checking a single cell.
-                    // we do not have to deduplicate an ID if it is known to only occupy a single cell.
-                    if id & (1 << 31) != 0 || is_ideal {
-                        result.push(*id & !(1 << 31));
-                    } else {
-                        if !result.contains(id) {
+        let layers = if self.has_statics {
+            &[&self.grid, &self.statics][..]
+        } else {
+            &[&self.grid][..]
+        };
+
+        let mut seen = if is_ideal { None } else { self.seen.lock() };
+        if let Some(seen) = &mut seen {
+            seen.begin();
+        }
+
+        let (mut cells, mut candidates) = (0, 0);
+        'cells: for buckets in layers {
+            for y in sy..=ey {
+                for x in sx..=ex {
+                    cells += 1;
+                    let slot = buckets.slot(x, y);
+                    if buckets.is_empty(slot) {
+                        continue;
+                    }
+                    for id in buckets.iter(slot) {
+                        candidates += 1;
+                        // rejecting ids first keeps them out of the dedup work.
+                        if !accept(id & !(1 << 31)) {
+                            continue;
+                        }
+                        // there CANNOT be duplicates if we are only checking a single cell.
+                        // we do not have to deduplicate an ID if it is known to only occupy a single cell.
+                        if id & (1 << 31) != 0 || is_ideal {
+                            result.push(*id & !(1 << 31));
+                        } else if let Some(seen) = &mut seen {
+                            if seen.insert(*id) {
+                                result.push(*id);
+                            }
+                        } else if deferred {
                             result.push(*id);
+                        } else if scan::position(result, *id).is_none() {
+                            result.push(*id);
+                            deferred = result.len() >= threshold;
+                        }
+                        if result.len() == limit {
+                            break 'cells;
                         }
                     }
                 }
             }
         }
+
+        // large entities are in no bucket, so they cannot duplicate the ids found so far.
+        if !self.large.is_empty() && result.len() < limit {
+            for id in self.large_in(sx, sy, ex, ey).filter(|&id| accept(id)) {
+                result.push(id);
+                if result.len() == limit {
+                    break;
+                }
+            }
+        }
+
+        if deferred {
+            result.sort_unstable();
+            result.dedup();
+        }
+        self.profile.query(
+            ((ex - sx).saturating_add(1), (ey - sy).saturating_add(1)),
+            cells,
+            candidates,
+            result.len(),
+        );
+    }
+
+    /// Find the entities whose centers lie within `radius` of an entity's center, excluding the entity itself.
+    /// At most `max_count` ids are returned. The result lives in a scratch buffer reused across calls, so this does not allocate once warmed up.
+    pub fn neighbors(&mut self, id: u32, radius: u32, max_count: usize) -> &[u32] {
+        let mut scratch = std::mem::take(&mut self.scratch);
+        self.neighbors_into(id, radius, max_count, &mut scratch);
+        self.scratch = scratch;
+        &self.scratch
+    }
+
+    /// Like `neighbors`, but writes the ids into a caller-provided buffer, so the grid can stay shared across threads.
+    /// `out` is cleared first.
+    pub fn neighbors_into(&self, id: u32, radius: u32, max_count: usize, out: &mut Vec<u32>) {
+        out.clear();
+
+        let map = match self.rect(id) {
+            Some(map) => map,
+            None => return,
+        };
+        let cx = map.x as u64 + map.width as u64 / 2;
+        let cy = map.y as u64 + map.height as u64 / 2;
+        // one extra unit covers the half unit lost when halving odd sizes.
+        let reach = radius as u64 + 1;
+        let x = cx.saturating_sub(reach) as u32;
+        let y = cy.saturating_sub(reach) as u32;
+        let ex = (cx + reach).min(u32::MAX as u64) as u32;
+        let ey = (cy + reach).min(u32::MAX as u64) as u32;
+        self.collect_cells(
+            x >> self.shift,
+            y >> self.shift,
+            ex >> self.shift,
+            ey >> self.shift,
+            usize::MAX,
+            |_| true,
+            out,
+        );
+
+        // compare doubled centers so odd sizes don't lose half a unit.
+        let (cx2, cy2) = (
+            2 * map.x as i128 + map.width as i128,
+            2 * map.y as i128 + map.height as i128,
+        );
+        let limit = 4 * radius as i128 * radius as i128;
+        let mut kept = 0;
+        for i in 0..out.len() {
+            let other = out[i];
+            if other == id {
+                continue;
+            }
+            let o = match self.rect(other) {
+                Some(o) => o,
+                None => continue,
+            };
+            let dx = 2 * o.x as i128 + o.width as i128 - cx2;
+            let dy = 2 * o.y as i128 + o.height as i128 - cy2;
+            if dx * dx + dy * dy <= limit {
+                out[kept] = other;
+                kept += 1;
+                if kept == max_count {
+                    break;
+                }
+            }
+        }
+        out.truncate(kept);
+    }
+
+    /// Rebuild the grid with a different cell size, re-inserting every entity with its last inserted rectangle.
+    /// This iterates over every entry of the internal tables. If a cell overflows, the entities after it are left out.
+    /// The undo journal is kept, since every entity keeps its rectangle.
+    pub fn rehash_with_shift(&mut self, new_shift: u32) -> Result<(), Error> {
+        let entities: Vec<(Entity, Layer)> = self.entities().collect();
+        let metas: Vec<(u32, Meta)> = entities
+            .iter()
+            .filter(|(_, layer)| *layer != Layer::Pinned)
+            .map(|(entity, _)| (entity.id, self.maps.get_scalar(entity.id).meta))
+            .filter(|(_, meta)| *meta != Meta::default())
+            .collect();
+        let journal = self.journal.take();
+        let delta = self.delta.take();
+        let mut shapes = std::mem::take(&mut self.shapes);
+        let groups = std::mem::take(&mut self.groups);
+        let expiries = std::mem::take(&mut self.expiries);
+        // re-inserting every entity is not the caller's work for the frame.
+        let frames = std::mem::take(&mut self.frames);
+        self.clear();
+        self.groups = groups;
+        self.shift = new_shift;
+        self.place_origin();
+        self.recorder.record(record::Op::Shift(new_shift));
+        let result = entities.iter().try_for_each(|(entity, layer)| match layer {
+            Layer::Dynamic => match shapes.remove(&entity.id) {
+                Some(shape) => self.insert_shape(entity.id, shape),
+                None => self.insert(entity),
+            },
+            Layer::Static => self.insert_static(entity),
+            Layer::Pinned => self.insert_pinned(entity),
+        });
+        for &(id, meta) in metas.iter() {
+            self.maps.get_scalar_mut(id).meta = meta;
+        }
+        self.expiries = expiries;
+        self.delta = delta;
+        self.journal = journal;
+        self.frames = frames;
         result
     }
 
+    /// Pre-size the grid for `expected_entities` entities with ids below that count, so spawning them does not reallocate mid-frame.
+    /// If the entity table is smaller than `Grid::new` would make it for that many entities, it is rebuilt larger, which
+    /// iterates over every entry; the old table is kept if two registered ids would share an entry of the larger one.
+    /// The table never shrinks. The dedup table, change list and undo journal reserve room, and sparse layouts reserve
+    /// one cell per entity. Spilled ids are not reserved.
+    pub fn reserve(&mut self, expected_entities: usize) {
+        let entries = table_size(expected_entities.div_ceil(1000));
+        if self.maps.count() < entries {
+            let mut maps = Table {
+                entries: vec![Map::default(); entries],
+            };
+            let mut taken = HashSet::new();
+            let collides = self
+                .maps
+                .entries
+                .iter()
+                .filter(|map| map.is_registered())
+                .any(|map| !taken.insert(maps.index(hash_u64(map.id as u64))));
+            if !collides {
+                for map in self.maps.entries.drain(..) {
+                    if map.is_registered() {
+                        let id = map.id;
+                        *maps.get_scalar_mut(id) = map;
+                    }
+                }
+                self.maps = maps;
+            }
+        }
+        self.seen.reserve(expected_entities);
+        if let Some(changes) = &mut self.changes {
+            changes.reserve(expected_entities);
+        }
+        if let Some(journal) = &mut self.journal {
+            journal.reserve(expected_entities);
+        }
+        self.grid.reserve(expected_entities);
+    }
+
+    /// Hash every entity's id, layer and occupied cells into one value, independent of insertion order and cell layout.
+    /// Grids that went through the same operations have equal checksums, so lockstep peers can compare them to detect desyncs.
+    /// This visits every entry of the entity table.
+    pub fn checksum(&self) -> u64 {
+        let mapped = self
+            .maps
+            .entries
+            .iter()
+            .filter(|map| !map.cells.is_empty())
+            .flat_map(|map| {
+                let key = mix(map.id as u64 | (map.is_static as u64) << 32);
+                map.cells
+                    .iter()
+                    .map(move |&(x, y)| mix(key ^ vector_hash(x, y)))
+            });
+        let pinned = self.pinned.keys().map(|&id| mix(id as u64 | 2 << 32));
+        let large = self.large.iter().map(|&id| {
+            let key = mix(id as u64 | (self.maps.get_scalar(id).is_static as u64) << 32 | 4 << 32);
+            let (sx, sy, ex, ey) = self.large_cells(id);
+            mix(key ^ vector_hash(sx, sy)).wrapping_add(mix(!key ^ vector_hash(ex, ey)))
+        });
+        mapped.chain(pinned).chain(large).fold(0, u64::wrapping_add)
+    }
+
+    /// Get the bitshift cell size.
+    pub fn shift(&self) -> u32 {
+        self.shift
+    }
+
+    /// Iterate over every registered entity and the layer it lives in.
+    fn entities(&self) -> impl Iterator<Item = (Entity, Layer)> + '_ {
+        let mapped = self
+            .maps
+            .entries
+            .iter()
+            .filter(|map| map.is_registered())
+            .map(|map| {
+                let entity = Entity {
+                    id: map.id,
+                    x: map.x,
+                    y: map.y,
+                    width: map.width,
+                    height: map.height,
+                };
+                let layer = if map.is_static {
+                    Layer::Static
+                } else {
+                    Layer::Dynamic
+                };
+                (entity, layer)
+            });
+        let pinned = self
+            .pinned
+            .values()
+            .map(|entity| (entity.clone(), Layer::Pinned));
+        mapped.chain(pinned)
+    }
+
+    /// Get the last inserted rectangle of an entity, whether mapped or pinned.
+    fn rect(&self, id: u32) -> Option<Entity> {
+        let map = self.maps.get_scalar(id);
+        if map.is_registered() && map.id == id {
+            return Some(Entity {
+                id,
+                x: map.x,
+                y: map.y,
+                width: map.width,
+                height: map.height,
+            });
+        }
+        self.pinned.get(&id).cloned()
+    }
+
     /// Clear the grid. This method is extremely slow since it iterates over every cell. As a faster alternative, just call the `delete(id)` method for each entity in the grid.
+    /// The undo journal is emptied, so the grid cannot be rolled back past a clear.
     pub fn clear(&mut self) {
+        self.recorder.record(record::Op::Clear);
+        if let Some(delta) = &mut self.delta {
+            delta.clear(self.tick);
+        }
+        if let Some(journal) = &mut self.journal {
+            journal.clear();
+        }
         self.grid.clear();
+        self.statics.defer();
+        self.has_statics = false;
         self.maps.clear();
+        self.pinned.clear();
+        self.shapes.clear();
+        self.groups.clear();
+        self.expiries.clear();
+        self.large.clear();
+        if let Some(coarse) = &mut self.coarse {
+            coarse.clear();
+        }
+    }
+
+    /// Clear every dynamic entity, keeping the static layer. Like `clear`, this iterates over every cell and empties the undo journal.
+    pub fn clear_dynamic(&mut self) {
+        self.recorder.record(record::Op::ClearDynamic);
+        if let Some(delta) = &mut self.delta {
+            delta.clear(self.tick);
+        }
+        if let Some(journal) = &mut self.journal {
+            journal.clear();
+        }
+        self.grid.clear();
+        self.shapes.clear();
+        if let Some(coarse) = &mut self.coarse {
+            coarse.clear_dynamic();
+        }
+        let maps = &self.maps;
+        self.large.retain(|&id| maps.get_scalar(id).is_static);
+        self.expiries.retain(|id| maps.get_scalar(id).is_static);
+        for map in self.maps.entries.iter_mut() {
+            if !map.is_static {
+                map.cells.clear();
+                map.is_large = false;
+            }
+        }
+    }
+}
+
+// queries only need `&self`, so a grid can be shared across threads once built.
+const _: fn() = || {
+    fn assert_shared<T: Send + Sync>() {}
+    assert_shared::<Grid>();
+};
+
+/// Inclusive rectangle overlap test, treating `x + width` as covered like the grid does.
+#[inline]
+fn overlaps(entity: &Entity, query: &Query) -> bool {
+    entity.x as u64 <= query.x as u64 + query.width as u64
+        && query.x as u64 <= entity.x as u64 + entity.width as u64
+        && entity.y as u64 <= query.y as u64 + query.height as u64
+        && query.y as u64 <= entity.y as u64 + entity.height as u64
+}
+
+/// Index into a slice. Unchecked unless the `safe` feature is enabled; debug builds still assert the bounds.
+#[inline(always)]
+fn at<T, I: SliceIndex<[T]> + Clone>(slice: &[T], idx: I) -> &I::Output {
+    #[cfg(feature = "safe")]
+    {
+        &slice[idx]
+    }
+    #[cfg(not(feature = "safe"))]
+    {
+        debug_assert!(slice.get(idx.clone()).is_some(), "index out of bounds");
+        unsafe { slice.get_unchecked(idx) }
     }
 }
 
+/// Mutably index into a slice. Unchecked unless the `safe` feature is enabled; debug builds still assert the bounds.
+#[inline(always)]
+fn at_mut<T, I: SliceIndex<[T]> + Clone>(slice: &mut [T], idx: I) -> &mut I::Output {
+    #[cfg(feature = "safe")]
+    {
+        &mut slice[idx]
+    }
+    #[cfg(not(feature = "safe"))]
+    {
+        debug_assert!(slice.get(idx.clone()).is_some(), "index out of bounds");
+        unsafe { slice.get_unchecked_mut(idx) }
+    }
+}
+
+/// Number of slots allocated for a table of `size` entries.
+#[inline]
+fn table_size(size: usize) -> usize {
+    (size * 1000).next_power_of_two() + 1
+}
+
+#[inline(always)]
+fn slot_index(key: u64, len: usize) -> usize {
+    (hash_u64(key) % len as u64) as usize
+}
+
+/// Operations shared by spatial index backends, so callers can swap the backend or wrap it
+/// without changing call sites. Every backend follows the same contract:
+///
+/// - `insert` fails with `Error::DuplicateId` for an id that is already present, `Error::InvalidId` for ids using the
+///   high bit and `Error::CoordinateOverflow` for rectangles extending past `u32::MAX`, leaving the index untouched.
+/// - `update` validates like `insert` and inserts ids that are not present yet.
+/// - `query` and `query_point` return each entity whose rectangle overlaps the region or contains the point exactly
+///   once, in no particular order. Edges touch inclusively, like `Grid::query_exact`.
+/// - `find_pairs` returns every pair of entities whose rectangles overlap, sorted, smallest id first.
+///
+/// `Grid` and `NaiveGrid` visit cells, so their implementations filter the cell candidates by rectangle. Their own
+/// static layers are outside the contract: they never report pairs of two static entities.
+pub trait SpatialIndex {
+    /// Insert an entity.
+    fn insert(&mut self, entity: &Entity) -> Result<(), Error>;
+
+    /// Delete an entity by ID.
+    fn delete(&mut self, id: u32);
+
+    /// Move or resize an entity.
+    fn update(&mut self, entity: &Entity) -> Result<(), Error>;
+
+    /// Retrieve entities overlapping a region.
+    fn query(&self, query: &Query) -> Vec<u32>;
+
+    /// Retrieve entities containing a point.
+    fn query_point(&self, x: u32, y: u32) -> Vec<u32>;
+
+    /// Find every pair of overlapping entities, smallest id first.
+    fn find_pairs(&self) -> Vec<(u32, u32)>;
+}
+
+impl SpatialIndex for Grid {
+    fn insert(&mut self, entity: &Entity) -> Result<(), Error> {
+        self.try_insert(entity)
+    }
+
+    fn delete(&mut self, id: u32) {
+        Grid::delete(self, id)
+    }
+
+    fn update(&mut self, entity: &Entity) -> Result<(), Error> {
+        self.try_update(entity)
+    }
+
+    fn query(&self, query: &Query) -> Vec<u32> {
+        let mut result = Grid::query(self, query).into_vec();
+        // fixed layouts can repeat ids, so sort to deduplicate.
+        result.sort_unstable();
+        result.dedup();
+        result.retain(|&id| self.rect(id).is_some_and(|entity| overlaps(&entity, query)));
+        result
+    }
+
+    fn query_point(&self, x: u32, y: u32) -> Vec<u32> {
+        SpatialIndex::query(
+            self,
+            &Query {
+                x,
+                y,
+                width: 0,
+                height: 0,
+            },
+        )
+    }
+
+    fn find_pairs(&self) -> Vec<(u32, u32)> {
+        let mut pairs = Grid::find_pairs(self);
+        pairs.retain(|&(a, b)| match (self.rect(a), self.rect(b)) {
+            (Some(a), Some(b)) => overlaps(&a, &(&b).into()),
+            _ => false,
+        });
+        pairs
+    }
+}
+
+/// Validate an entity for `SpatialIndex::insert` and `SpatialIndex::update`, like the grid's `try_*` methods without
+/// world bounds or a maximum entity size.
+fn check_entity(entity: &Entity) -> Result<(), Error> {
+    if entity.id & (1 << 31) != 0 {
+        return Err(Error::InvalidId(entity.id));
+    }
+    if entity.x.checked_add(entity.width).is_none() || entity.y.checked_add(entity.height).is_none()
+    {
+        return Err(Error::CoordinateOverflow);
+    }
+    Ok(())
+}
+
+/// Interleave the bits of two coordinates, `x` in the even bits.
+#[inline]
+fn morton(x: u32, y: u32) -> u64 {
+    fn spread(v: u32) -> u64 {
+        let mut v = v as u64;
+        v = (v | (v << 16)) & 0x0000_FFFF_0000_FFFF;
+        v = (v | (v << 8)) & 0x00FF_00FF_00FF_00FF;
+        v = (v | (v << 4)) & 0x0F0F_0F0F_0F0F_0F0F;
+        v = (v | (v << 2)) & 0x3333_3333_3333_3333;
+        (v | (v << 1)) & 0x5555_5555_5555_5555
+    }
+    spread(x) | (spread(y) << 1)
+}
+
 #[inline]
 fn vector_hash(x: u32, y: u32) -> u64 {
     ((x as u64) << 32) | y as u64
 }
 
-/// Identity hash for now
+/// Scramble a key with the MurmurHash3 finalizer.
+#[inline]
+fn mix(mut key: u64) -> u64 {
+    key ^= key >> 33;
+    key = key.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    key ^= key >> 33;
+    key = key.wrapping_mul(0xc4ce_b3fe_1a85_ec53);
+    key ^ (key >> 33)
+}
+
+/// Identity hash. Ids are usually dense, so this keeps them from colliding in tables indexed by id.
 #[inline]
 fn hash_u64(seed: u64) -> u64 {
     seed
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::naive::NaiveGrid;
+    use crate::{
+        Axis, Entity, Error, Grid, OverflowPolicy, Quadtree, Query, SpatialIndex, SweepPrune, Tag,
+    };
+
+    fn point(id: u32, x: u32, y: u32) -> Entity {
+        Entity {
+            id,
+            x,
+            y,
+            width: 1,
+            height: 1,
+        }
+    }
+
+    #[test]
+    fn update_into_full_cell_keeps_old_placement() {
+        let mut grid = Grid::with_bucket_capacity(64, 4, 1);
+        grid.insert(&point(2, 100, 100)).unwrap();
+        grid.insert(&point(1, 10, 10)).unwrap();
+        grid.tag(1, Tag::new(3)).unwrap();
+        assert!(matches!(
+            grid.update(&point(1, 100, 100)),
+            Err(Error::CellFull { id: 1, .. })
+        ));
+        assert_eq!(grid.query_point(10, 10).into_vec(), [1]);
+        assert_eq!(grid.query_point(100, 100).into_vec(), [2]);
+        assert_eq!(grid.rect(1).map(|entity| entity.x), Some(10));
+        assert!(grid.has_tag(1, Tag::new(3)));
+    }
+
+    #[test]
+    fn delete_tolerates_cells_missing_from_the_buckets() {
+        let mut grid = Grid::with_bucket_capacity(64, 4, 1);
+        grid.set_overflow_policy(OverflowPolicy::EvictOldest);
+        grid.insert(&Entity {
+            id: 1,
+            x: 0,
+            y: 0,
+            width: 20,
+            height: 1,
+        })
+        .unwrap();
+        grid.insert(&point(2, 16, 0)).unwrap();
+        grid.delete(1);
+        assert!(grid.query_point(0, 0).is_empty());
+        assert_eq!(grid.query_point(16, 0).into_vec(), [2]);
+    }
+
+    fn square(id: u32, x: u32, y: u32) -> Entity {
+        Entity {
+            id,
+            x,
+            y,
+            width: 4,
+            height: 4,
+        }
+    }
+
+    fn sorted(mut ids: Vec<u32>) -> Vec<u32> {
+        ids.sort_unstable();
+        ids
+    }
+
+    fn check_contract(mut index: impl SpatialIndex) {
+        // 1 and 2 share a 16 unit cell without overlapping, 3 overlaps 2.
+        index.insert(&square(1, 0, 0)).unwrap();
+        index.insert(&square(2, 8, 8)).unwrap();
+        index.insert(&square(3, 10, 10)).unwrap();
+        assert_eq!(index.insert(&square(1, 50, 50)), Err(Error::DuplicateId(1)));
+        assert_eq!(
+            index.insert(&square(1 << 31, 0, 0)),
+            Err(Error::InvalidId(1 << 31))
+        );
+        assert_eq!(
+            index.insert(&square(4, u32::MAX - 1, 0)),
+            Err(Error::CoordinateOverflow)
+        );
+        assert_eq!(index.query_point(2, 2), [1]);
+
+        let region = Query {
+            x: 9,
+            y: 9,
+            width: 0,
+            height: 0,
+        };
+        assert_eq!(sorted(index.query(&region)), [2]);
+        assert_eq!(index.find_pairs(), [(2, 3)]);
+
+        index.update(&square(5, 100, 100)).unwrap();
+        index.update(&square(3, 200, 200)).unwrap();
+        assert!(index.find_pairs().is_empty());
+        assert_eq!(index.query_point(101, 101), [5]);
+        index.delete(5);
+        assert!(index.query_point(101, 101).is_empty());
+    }
+
+    #[test]
+    fn spatial_index_backends_share_the_contract() {
+        check_contract(Grid::new(64, 4));
+        check_contract(NaiveGrid::new(64, 4));
+        check_contract(Quadtree::new(1024, 1024));
+        check_contract(SweepPrune::new(Axis::X));
+    }
+
+    fn full_grid(policy: OverflowPolicy) -> Grid {
+        let mut grid = Grid::with_bucket_capacity(64, 4, 1);
+        grid.set_overflow_policy(policy);
+        grid.insert(&point(1, 20, 0)).unwrap();
+        grid
+    }
+
+    fn wide(id: u32) -> Entity {
+        // covers the cells at x 0 and 16, the second of which is taken.
+        Entity {
+            id,
+            x: 0,
+            y: 0,
+            width: 20,
+            height: 1,
+        }
+    }
+
+    #[test]
+    fn overflow_error_reports_the_full_cell() {
+        let mut grid = full_grid(OverflowPolicy::Error);
+        assert_eq!(
+            grid.insert(&wide(2)),
+            Err(Error::CellFull {
+                cell: (1, 0),
+                id: 2,
+                written: 1,
+                rect: (0, 0, 20, 1),
+            })
+        );
+        assert_eq!(grid.query_point(20, 0).into_vec(), [1]);
+    }
+
+    #[test]
+    fn overflow_evict_oldest_replaces_the_stored_id() {
+        let mut grid = full_grid(OverflowPolicy::EvictOldest);
+        grid.insert(&wide(2)).unwrap();
+        assert_eq!(grid.query_point(20, 0).into_vec(), [2]);
+        grid.delete(1);
+        assert_eq!(grid.query_point(20, 0).into_vec(), [2]);
+    }
+
+    #[test]
+    fn overflow_spill_to_heap_keeps_both_ids() {
+        let mut grid = full_grid(OverflowPolicy::SpillToHeap);
+        grid.insert(&wide(2)).unwrap();
+        assert_eq!(sorted(grid.query_point(20, 0).into_vec()), [1, 2]);
+        grid.delete(2);
+        assert_eq!(grid.query_point(20, 0).into_vec(), [1]);
+    }
+
+    #[test]
+    fn overflow_drop_incoming_skips_the_full_cell() {
+        let mut grid = full_grid(OverflowPolicy::DropIncoming);
+        grid.insert(&wide(2)).unwrap();
+        assert_eq!(grid.query_point(20, 0).into_vec(), [1]);
+        assert_eq!(grid.query_point(0, 0).into_vec(), [2]);
+    }
+}
@@ -0,0 +1,160 @@
+//! Heap memory accounting for capacity planning, and compaction to give memory back after churn.
+
+use std::collections::HashMap;
+use std::mem::size_of;
+
+use crate::{Buckets, CellLayout, Grid, Shape};
+
+/// Get the heap memory allocated by a vector.
+pub(crate) fn vec_bytes<T>(vec: &Vec<T>) -> usize {
+    vec.capacity() * size_of::<T>()
+}
+
+/// Estimate the heap memory allocated by a hash map: one entry and one control byte per bucket.
+/// The map's own allocations are counted, not those owned by its keys or values.
+pub(crate) fn map_bytes<K, V>(map: &HashMap<K, V>) -> usize {
+    map.capacity() * (size_of::<(K, V)>() + 1)
+}
+
+impl Buckets {
+    fn bytes_used(&self) -> usize {
+        vec_bytes(&self.lens)
+            + vec_bytes(&self.ids)
+            + vec_bytes(&self.occupied)
+            + map_bytes(&self.spill)
+            + self.spill.values().map(vec_bytes).sum::<usize>()
+            + map_bytes(&self.sparse)
+            + vec_bytes(&self.cells)
+            + vec_bytes(&self.free)
+    }
+
+    /// Release the capacity left over from churn. Sparse slots are re-packed: occupied slots move down into the
+    /// empty ones so the slab can be truncated, which is safe because maps store cells rather than slots.
+    fn compact(&mut self) {
+        if self.layout == CellLayout::Sparse {
+            // slots whose insert was dropped are empty without being on the free list, so release every empty slot.
+            for slot in 1..self.lens.len() {
+                if self.is_empty(slot) && self.sparse.get(&self.cells[slot]) == Some(&slot) {
+                    self.sparse.remove(&self.cells[slot]);
+                }
+            }
+            let mut packed = 1;
+            for slot in 1..self.lens.len() {
+                if self.is_empty(slot) {
+                    continue;
+                }
+                if slot != packed {
+                    let (from, to) = (slot * self.capacity, packed * self.capacity);
+                    self.ids
+                        .copy_within(from..from + self.lens[slot] as usize, to);
+                    self.lens[packed] = self.lens[slot];
+                    self.lens[slot] = 0;
+                    self.occupied[packed / 64] |= 1 << (packed % 64);
+                    self.occupied[slot / 64] &= !(1 << (slot % 64));
+                    self.cells[packed] = self.cells[slot];
+                    self.sparse.insert(self.cells[slot], packed);
+                    if let Some(spilled) = self.spill.remove(&slot) {
+                        self.spill.insert(packed, spilled);
+                    }
+                }
+                packed += 1;
+            }
+            self.lens.truncate(packed);
+            self.ids.truncate(packed * self.capacity);
+            self.occupied.truncate(packed.div_ceil(64));
+            self.cells.truncate(packed);
+            self.free.clear();
+        }
+        for spilled in self.spill.values_mut() {
+            spilled.shrink_to_fit();
+        }
+        self.spill.shrink_to_fit();
+        self.sparse.shrink_to_fit();
+        self.lens.shrink_to_fit();
+        self.ids.shrink_to_fit();
+        self.occupied.shrink_to_fit();
+        self.cells.shrink_to_fit();
+        self.free.shrink_to_fit();
+    }
+}
+
+impl Grid {
+    /// Get the heap memory held by the grid in bytes, counting allocated capacity rather than what is in use.
+    /// Walks the cell buckets, spilled ids, entity maps, pinned entities, shapes, groups, expiries, history, delta ticks and every journal,
+    /// log and scratch buffer. Hash maps are estimated from their capacity, so the total is approximate.
+    pub fn bytes_used(&self) -> usize {
+        let shapes = self
+            .shapes
+            .values()
+            .map(|shape| match shape {
+                Shape::Polygon(points) => vec_bytes(points),
+                _ => 0,
+            })
+            .sum::<usize>();
+        let groups = self.groups.values().map(vec_bytes).sum::<usize>();
+        self.grid.bytes_used()
+            + self.statics.bytes_used()
+            + vec_bytes(&self.maps.entries)
+            + self.changes.as_ref().map_or(0, vec_bytes)
+            + vec_bytes(&self.scratch)
+            + vec_bytes(&self.entered)
+            + vec_bytes(&self.left)
+            + self.journal.as_ref().map_or(0, vec_bytes)
+            + self.seen.bytes_used()
+            + self.results.bytes_used()
+            + self.frames.bytes_used()
+            + map_bytes(&self.pinned)
+            + map_bytes(&self.shapes)
+            + shapes
+            + map_bytes(&self.groups)
+            + groups
+            + vec_bytes(&self.templates)
+            + self.expiries.bytes_used()
+            + self.history.bytes_used()
+            + self.delta.as_ref().map_or(0, |delta| delta.bytes_used())
+            + vec_bytes(&self.hot)
+            + vec_bytes(&self.large)
+            + self.coarse.as_ref().map_or(0, |coarse| coarse.bytes_used())
+            + self.recorder.bytes_used()
+    }
+
+    /// Give back memory left over from churn, such as during a loading screen or a quiet tick. Spilled ids and sparse cells
+    /// are re-packed, group indexes drop the ids that left, and scratch buffers are released. Returns the bytes freed as
+    /// counted by `bytes_used`. This touches every slot, so avoid calling it every frame.
+    pub fn compact(&mut self) -> usize {
+        let before = self.bytes_used();
+        self.grid.compact();
+        self.statics.compact();
+
+        let mut groups = std::mem::take(&mut self.groups);
+        groups.retain(|&group, members| {
+            members.sort_unstable();
+            members.dedup();
+            members.retain(|&member| self.group(member) == Some(group));
+            members.shrink_to_fit();
+            !members.is_empty()
+        });
+        groups.shrink_to_fit();
+        self.groups = groups;
+        self.expiries.compact();
+
+        self.pinned.shrink_to_fit();
+        self.shapes.shrink_to_fit();
+        self.results.clear();
+        self.scratch = Vec::new();
+        self.entered = Vec::new();
+        self.left = Vec::new();
+        self.hot.shrink_to_fit();
+        self.large.shrink_to_fit();
+        if let Some(changes) = &mut self.changes {
+            changes.shrink_to_fit();
+        }
+        if let Some(journal) = &mut self.journal {
+            journal.shrink_to_fit();
+        }
+        if let Some(coarse) = &mut self.coarse {
+            coarse.shrink_to_fit();
+        }
+        before.saturating_sub(self.bytes_used())
+    }
+}
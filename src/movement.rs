@@ -0,0 +1,158 @@
+//! Moving and resizing entities relative to the rectangle stored in their map.
+
+use crate::{hash_u64, record, too_many_cells, Entity, Error, Grid, Layer};
+
+impl Grid {
+    /// Move an entity by an offset from its stored rectangle, keeping its size and layer. Only the cells it leaves and enters
+    /// are rewritten. Pinned entities are ignored like with `update`, and an exact shape is dropped like with `update`.
+    /// Fails with `Error::NotFound` if the entity is not registered, `Error::CoordinateOverflow` if it would leave the `u32` range
+    /// and `Error::TooManyCells` if it would cover more cells than it can hold, leaving it where it was.
+    /// Like `update`, the new rectangle is rejected or clamped at the world bounds according to the bounds policy.
+    pub fn move_by(&mut self, id: u32, dx: i32, dy: i32) -> Result<(), Error> {
+        if self.pinned.contains_key(&id) {
+            return Ok(());
+        }
+        let map = self.maps.get_scalar(id);
+        if map.id != id || !map.is_registered() {
+            return Err(Error::NotFound(id));
+        }
+        let (x, y) = match (map.x.checked_add_signed(dx), map.y.checked_add_signed(dy)) {
+            (Some(x), Some(y)) => (x, y),
+            _ => return Err(Error::CoordinateOverflow),
+        };
+        let entity = Entity {
+            id,
+            x,
+            y,
+            width: map.width,
+            height: map.height,
+        };
+        self.relocate(&entity)
+    }
+
+    /// Move many entities by offsets like `move_by`, such as the output of a movement system each tick. The moves are
+    /// applied in the order of the internal entity table so lookups walk it front to back; moves of the same entity keep
+    /// their order. Every move is attempted, and the error of the earliest failed move in the slice is returned.
+    pub fn apply_moves(&mut self, moves: &[(u32, i32, i32)]) -> Result<(), Error> {
+        let mut order: Vec<(usize, usize)> = moves
+            .iter()
+            .enumerate()
+            .map(|(i, &(id, _, _))| (self.maps.index(hash_u64(id as u64)), i))
+            .collect();
+        order.sort_unstable();
+
+        let mut first: Option<(usize, Error)> = None;
+        for (_, i) in order {
+            let (id, dx, dy) = moves[i];
+            if let Err(err) = self.move_by(id, dx, dy) {
+                if first.is_none_or(|(earlier, _)| i < earlier) {
+                    first = Some((i, err));
+                }
+            }
+        }
+        first.map_or(Ok(()), |(_, err)| Err(err))
+    }
+
+    /// Change an entity's size, keeping its position and layer. Its cell coverage is recomputed from the stored position
+    /// and only the cells it leaves and enters are rewritten. Pinned entities are ignored and exact shapes dropped like with `update`.
+    /// Fails with `Error::NotFound` if the entity is not registered, and validates the size like `try_update`.
+    pub fn resize_entity(&mut self, id: u32, width: u32, height: u32) -> Result<(), Error> {
+        if self.pinned.contains_key(&id) {
+            return Ok(());
+        }
+        let map = self.maps.get_scalar(id);
+        if map.id != id || !map.is_registered() {
+            return Err(Error::NotFound(id));
+        }
+        let entity = Entity {
+            id,
+            x: map.x,
+            y: map.y,
+            width,
+            height,
+        };
+        self.check_size(&entity)?;
+        self.relocate(&entity)
+    }
+
+    /// Give a registered entity a new rectangle, rewriting only the cells that differ when its old cells are all stored.
+    /// The new rectangle's cell count is checked before any cell is rewritten.
+    fn relocate(&mut self, entity: &Entity) -> Result<(), Error> {
+        let clamped = self.bound_entity(entity)?;
+        let entity = clamped.as_ref().unwrap_or(entity);
+        let id = entity.id;
+        let map = self.maps.get_scalar(id);
+        let (sx, sy, ex, ey) =
+            self.checked_cells(entity.x, entity.y, entity.width, entity.height)?;
+        let (osx, osy, oex, oey) = (
+            map.x >> self.shift,
+            map.y >> self.shift,
+            map.x.saturating_add(map.width) >> self.shift,
+            map.y.saturating_add(map.height) >> self.shift,
+        );
+
+        let cells = (ex - sx + 1) as u64 * (ey - sy + 1) as u64;
+        if too_many_cells(cells) && !self.goes_large(cells, Layer::Dynamic) {
+            return Err(Error::TooManyCells(id));
+        }
+
+        // rewriting only the difference needs every old cell in the map and no change of the ideal tag.
+        let complete = map.cells.len() as u64 == (oex - osx + 1) as u64 * (oey - osy + 1) as u64;
+        let overlaps = osx <= ex && sx <= oex && osy <= ey && sy <= oey;
+        let is_ideal = (sx == ex && sy == ey) || (osx == oex && osy == oey);
+        let unchanged = (sx, sy, ex, ey) == (osx, osy, oex, oey);
+        if unchanged
+            || !complete
+            || !overlaps
+            || is_ideal
+            || self.goes_large(cells, Layer::Dynamic)
+            || self.shapes.contains_key(&id)
+        {
+            return self.update(entity);
+        }
+
+        self.recorder.record(record::Op::Update(entity));
+        self.frames.update();
+        self.record(id);
+        let left = |x: u32, y: u32| x < sx || x > ex || y < sy || y > ey;
+        let map = self.maps.get_scalar(id);
+        let is_static = map.is_static;
+        let buckets = if is_static {
+            &mut self.statics
+        } else {
+            &mut self.grid
+        };
+        for &(x, y) in map.cells.iter().filter(|&&(x, y)| left(x, y)) {
+            let slot = buckets.slot(x, y);
+            if let Some(index) = buckets.position(slot, id) {
+                buckets.remove(slot, index);
+                buckets.release(slot);
+                if let Some(coarse) = &mut self.coarse {
+                    coarse.remove((x, y), is_static);
+                }
+            }
+        }
+
+        let map = self.maps.get_scalar_mut(id);
+        map.cells.retain(|&mut (x, y)| !left(x, y));
+        map.x = entity.x;
+        map.y = entity.y;
+        map.width = entity.width;
+        map.height = entity.height;
+        if let Some(changes) = &mut self.changes {
+            changes.push(id);
+        }
+        let layer = if is_static {
+            Layer::Static
+        } else {
+            Layer::Dynamic
+        };
+        let result = self.push_cells(entity, id, layer, sx, sy, ex, ey, |x, y| {
+            x < osx || x > oex || y < osy || y > oey
+        });
+        // `update` recognizes unchanged ranges by the first and last cell, so keep the cells in row order.
+        let map = self.maps.get_scalar_mut(id);
+        map.cells.sort_unstable_by_key(|&(x, y)| (y, x));
+        result
+    }
+}
@@ -0,0 +1,283 @@
+//! A reference implementation of the grid's core operations as a scan over a `Vec`, for differential testing.
+//! Only inserting, deleting, updating, `query`, `query_point`, `find_pairs` and `clear` are mirrored, with their `try_*`
+//! forms; layers, tags, shapes, journals and the other extensions of `Grid` have no counterpart here.
+
+use crate::{check_entity, overlaps, Entity, Error, Query, SpatialIndex};
+
+/// A slow stand-in for `Grid` that stores entities in a `Vec` and answers every query by scanning all of them.
+/// It has the same cell semantics as `Grid`: a query returns every entity sharing a cell with the region, so a
+/// workload run against both must produce the same result sets. It stores nothing per cell, so it never fails
+/// with `Error::CellFull` or `Error::TooManyCells`; workloads compared against it should stay within the grid's limits.
+#[derive(Debug, Clone, Default)]
+pub struct NaiveGrid {
+    shift: u32,
+    entities: Vec<(Entity, bool)>,
+}
+
+impl NaiveGrid {
+    /// Create an empty grid with the cell size of `Grid::new(size, shift)`. `size` is only taken to match it.
+    pub fn new(_size: usize, shift: u32) -> Self {
+        Self {
+            shift,
+            entities: Vec::new(),
+        }
+    }
+
+    /// Get the number of entities in the grid.
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    /// Check whether the grid holds no entities.
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    /// Insert an entity like `Grid::insert`, cutting off rectangles extending past `u32::MAX`.
+    /// Ids that are already registered are rejected with `Error::DuplicateId`.
+    pub fn insert(&mut self, entity: &Entity) -> Result<(), Error> {
+        self.push(entity, false)
+    }
+
+    /// Insert an entity into the static layer like `Grid::insert_static`.
+    pub fn insert_static(&mut self, entity: &Entity) -> Result<(), Error> {
+        self.push(entity, true)
+    }
+
+    /// Insert an entity like `Grid::try_insert`, rejecting ids with the high bit set and rectangles extending past `u32::MAX`.
+    pub fn try_insert(&mut self, entity: &Entity) -> Result<(), Error> {
+        check_entity(entity)?;
+        self.insert(entity)
+    }
+
+    /// Delete an entity by ID. Unknown ids are ignored.
+    pub fn delete(&mut self, id: u32) {
+        if let Some(index) = self.position(id) {
+            self.entities.remove(index);
+        }
+    }
+
+    /// Delete an entity by ID, failing with `Error::NotFound` if it is not registered.
+    pub fn try_delete(&mut self, id: u32) -> Result<(), Error> {
+        let index = self.position(id).ok_or(Error::NotFound(id))?;
+        self.entities.remove(index);
+        Ok(())
+    }
+
+    /// Move or resize an entity, keeping it in the same layer. Like `Grid::update`, an unknown id is inserted.
+    pub fn update(&mut self, entity: &Entity) -> Result<(), Error> {
+        match self.position(entity.id) {
+            Some(index) => {
+                self.entities[index].0 = entity.clone();
+                Ok(())
+            }
+            None => self.insert(entity),
+        }
+    }
+
+    /// Move or resize an entity like `Grid::try_update`, validating it like `try_insert`.
+    pub fn try_update(&mut self, entity: &Entity) -> Result<(), Error> {
+        check_entity(entity)?;
+        self.update(entity)
+    }
+
+    /// Retrieve every entity sharing a cell with a region, in insertion order.
+    pub fn query(&self, query: &Query) -> Vec<u32> {
+        let cells = self.cells(query.x, query.y, query.width, query.height);
+        self.entities
+            .iter()
+            .filter(|(entity, _)| {
+                shares_cell(
+                    self.cells(entity.x, entity.y, entity.width, entity.height),
+                    cells,
+                )
+            })
+            .map(|(entity, _)| entity.id)
+            .collect()
+    }
+
+    /// Retrieve every entity in the cell containing a point.
+    pub fn query_point(&self, x: u32, y: u32) -> Vec<u32> {
+        self.query(&Query {
+            x,
+            y,
+            width: 0,
+            height: 0,
+        })
+    }
+
+    /// Find every pair of entities sharing a cell, smallest id first, sorted. Like `Grid::find_pairs`,
+    /// pairs of two static entities are not reported.
+    pub fn find_pairs(&self) -> Vec<(u32, u32)> {
+        let mut pairs = Vec::new();
+        for (i, (a, a_static)) in self.entities.iter().enumerate() {
+            let cells = self.cells(a.x, a.y, a.width, a.height);
+            for (b, b_static) in &self.entities[i + 1..] {
+                if (*a_static && *b_static)
+                    || !shares_cell(self.cells(b.x, b.y, b.width, b.height), cells)
+                {
+                    continue;
+                }
+                pairs.push((a.id.min(b.id), a.id.max(b.id)));
+            }
+        }
+        pairs.sort_unstable();
+        pairs
+    }
+
+    /// Remove every entity.
+    pub fn clear(&mut self) {
+        self.entities.clear();
+    }
+
+    /// Get the cell size as a power of two.
+    pub fn shift(&self) -> u32 {
+        self.shift
+    }
+
+    fn push(&mut self, entity: &Entity, is_static: bool) -> Result<(), Error> {
+        if self.position(entity.id).is_some() {
+            return Err(Error::DuplicateId(entity.id));
+        }
+        self.entities.push((entity.clone(), is_static));
+        Ok(())
+    }
+
+    fn position(&self, id: u32) -> Option<usize> {
+        self.entities.iter().position(|(entity, _)| entity.id == id)
+    }
+
+    /// Get the first and last cell a rectangle covers, cut off at `u32::MAX` like the grid.
+    fn cells(&self, x: u32, y: u32, width: u32, height: u32) -> (u32, u32, u32, u32) {
+        (
+            x >> self.shift,
+            y >> self.shift,
+            x.saturating_add(width) >> self.shift,
+            y.saturating_add(height) >> self.shift,
+        )
+    }
+}
+
+impl SpatialIndex for NaiveGrid {
+    fn insert(&mut self, entity: &Entity) -> Result<(), Error> {
+        self.try_insert(entity)
+    }
+
+    fn delete(&mut self, id: u32) {
+        NaiveGrid::delete(self, id)
+    }
+
+    fn update(&mut self, entity: &Entity) -> Result<(), Error> {
+        self.try_update(entity)
+    }
+
+    fn query(&self, query: &Query) -> Vec<u32> {
+        let mut result = NaiveGrid::query(self, query);
+        result.retain(|&id| {
+            self.position(id)
+                .is_some_and(|index| overlaps(&self.entities[index].0, query))
+        });
+        result
+    }
+
+    fn query_point(&self, x: u32, y: u32) -> Vec<u32> {
+        SpatialIndex::query(
+            self,
+            &Query {
+                x,
+                y,
+                width: 0,
+                height: 0,
+            },
+        )
+    }
+
+    fn find_pairs(&self) -> Vec<(u32, u32)> {
+        let rect = |id| {
+            let index = self.position(id).unwrap();
+            &self.entities[index].0
+        };
+        let mut pairs = NaiveGrid::find_pairs(self);
+        pairs.retain(|&(a, b)| overlaps(rect(a), &rect(b).into()));
+        pairs
+    }
+}
+
+/// Check whether two ranges of cells, as `(sx, sy, ex, ey)`, have a cell in common.
+fn shares_cell(a: (u32, u32, u32, u32), b: (u32, u32, u32, u32)) -> bool {
+    a.0 <= b.2 && b.0 <= a.2 && a.1 <= b.3 && b.1 <= a.3
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::NaiveGrid;
+    use crate::{CellLayout, Entity, Grid, Query};
+
+    // repeated ids are kept, so a query returning an entity twice fails the comparison.
+    fn sorted(mut ids: Vec<u32>) -> Vec<u32> {
+        ids.sort_unstable();
+        ids
+    }
+
+    #[test]
+    fn random_workload_matches_grid() {
+        let mut rng = StdRng::seed_from_u64(0x5eed);
+        for _ in 0..10 {
+            // a sparse grid never shares slots between cells, and ids below 400 never share an entity table entry.
+            let mut grid = Grid::with_cell_layout(64, 4, 64, CellLayout::Sparse);
+            let mut naive = NaiveGrid::new(64, 4);
+            for _ in 0..3000 {
+                let entity = Entity {
+                    id: rng.gen_range(0..400),
+                    x: rng.gen_range(0..600),
+                    y: rng.gen_range(0..600),
+                    width: rng.gen_range(0..30),
+                    height: rng.gen_range(0..30),
+                };
+                match rng.gen_range(0..10) {
+                    0..=2 => assert_eq!(
+                        grid.try_insert(&entity).is_ok(),
+                        naive.try_insert(&entity).is_ok()
+                    ),
+                    3 => assert_eq!(
+                        grid.insert_static(&entity).is_ok(),
+                        naive.insert_static(&entity).is_ok()
+                    ),
+                    4 => assert_eq!(
+                        grid.try_delete(entity.id).is_ok(),
+                        naive.try_delete(entity.id).is_ok()
+                    ),
+                    5 | 6 => assert_eq!(
+                        grid.try_update(&entity).is_ok(),
+                        naive.try_update(&entity).is_ok()
+                    ),
+                    7 => {
+                        let (x, y) = (rng.gen_range(0..700), rng.gen_range(0..700));
+                        assert_eq!(
+                            sorted(grid.query_point(x, y).into_vec()),
+                            sorted(naive.query_point(x, y))
+                        );
+                    }
+                    _ => {
+                        let query = Query {
+                            x: rng.gen_range(0..600),
+                            y: rng.gen_range(0..600),
+                            width: rng.gen_range(0..100),
+                            height: rng.gen_range(0..100),
+                        };
+                        assert_eq!(
+                            sorted(grid.query(&query).into_vec()),
+                            sorted(naive.query(&query))
+                        );
+                    }
+                }
+            }
+            assert_eq!(grid.find_pairs(), naive.find_pairs());
+            grid.clear();
+            naive.clear();
+        }
+    }
+}
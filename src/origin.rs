@@ -0,0 +1,34 @@
+//! A world origin the cell tables are laid out around, for worlds far from coordinate zero.
+
+use crate::{record, CellLayout, Error, Grid};
+
+impl Grid {
+    /// Get the world origin. Defaults to `(0, 0)`.
+    pub fn origin(&self) -> (u32, u32) {
+        self.origin
+    }
+
+    /// Move the world origin, such as when a floating-origin camera rebases the world. Cells keep their positions,
+    /// but the fixed cell layouts map them to slots relative to the cell holding the origin, so a world whose coordinates
+    /// start far from zero is laid out like one starting at zero and the origin lands in the first slot.
+    /// The origin is rounded down to its cell. If that cell changes, every entity is re-inserted like with `rehash_with_shift`;
+    /// sparse layouts don't depend on the origin and keep their cells.
+    pub fn recenter(&mut self, origin: (u32, u32)) -> Result<(), Error> {
+        self.recorder.record(record::Op::Origin(origin));
+        let cell = (origin.0 >> self.shift, origin.1 >> self.shift);
+        let moved = cell != self.grid.origin;
+        self.origin = origin;
+        if !moved || self.grid.layout == CellLayout::Sparse {
+            self.place_origin();
+            return Ok(());
+        }
+        self.rehash_with_shift(self.shift)
+    }
+
+    /// Point the cell tables at the cell holding the origin with the current shift.
+    pub(crate) fn place_origin(&mut self) {
+        let cell = (self.origin.0 >> self.shift, self.origin.1 >> self.shift);
+        self.grid.origin = cell;
+        self.statics.origin = cell;
+    }
+}
@@ -0,0 +1,52 @@
+//! Separating overlapping rectangles, for games that use the grid as their whole physics.
+
+use crate::{Entity, Grid, Query};
+
+/// Find the shortest move of `a` along one axis that separates it from `b`, or `None` if they don't overlap.
+/// Rectangles include their far edge, so touching ones overlap and are pushed one unit apart.
+/// The move is along the axis of least penetration, the x axis and the positive direction on ties, and saturates at the `i32` range.
+pub fn mtv(a: &Entity, b: &Entity) -> Option<(i32, i32)> {
+    // the shorter of the moves to either side along one axis, negative towards the start.
+    let axis = |a_min: u32, a_size: u32, b_min: u32, b_size: u32| {
+        let (a_min, b_min) = (a_min as i64, b_min as i64);
+        let (a_max, b_max) = (a_min + a_size as i64, b_min + b_size as i64);
+        if a_max < b_min || b_max < a_min {
+            return None;
+        }
+        let (back, forward) = (a_max - b_min + 1, b_max - a_min + 1);
+        Some(if back < forward { -back } else { forward })
+    };
+    let dx = axis(a.x, a.width, b.x, b.width)?;
+    let dy = axis(a.y, a.height, b.y, b.height)?;
+    let clamp = |v: i64| v.clamp(i32::MIN as i64, i32::MAX as i64) as i32;
+    Some(if dx.abs() <= dy.abs() {
+        (clamp(dx), 0)
+    } else {
+        (0, clamp(dy))
+    })
+}
+
+impl Grid {
+    /// Find every overlapping pair of entities in a region with the `mtv` moving the first out of the second.
+    /// Each pair is reported once, smallest id first. Entities outside the region are ignored, even if they overlap one inside it.
+    pub fn resolve_overlaps(&self, query: &Query) -> Vec<(u32, u32, (i32, i32))> {
+        let mut ids = self.query(query).into_vec();
+        ids.sort_unstable();
+        ids.dedup();
+        let mut entities: Vec<Entity> = ids.into_iter().filter_map(|id| self.rect(id)).collect();
+        entities.sort_unstable_by_key(|entity| entity.x);
+
+        // sweep along x: only entities starting before the current one ends can overlap it.
+        let mut pairs = Vec::new();
+        for (i, a) in entities.iter().enumerate() {
+            let end = a.x as u64 + a.width as u64;
+            for b in entities[i + 1..].iter().take_while(|b| b.x as u64 <= end) {
+                let (first, second) = if a.id < b.id { (a, b) } else { (b, a) };
+                if let Some(vector) = mtv(first, second) {
+                    pairs.push((first.id, second.id, vector));
+                }
+            }
+        }
+        pairs
+    }
+}
@@ -0,0 +1,131 @@
+//! Frame-coherent pair caching.
+
+use crate::{scan, Grid};
+
+/// How the set of pairs changed since the previous frame.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PairEvents {
+    /// Pairs that share a cell this frame but did not last frame.
+    pub new: Vec<(u32, u32)>,
+    /// Pairs that shared a cell last frame and still do.
+    pub persisting: Vec<(u32, u32)>,
+    /// Pairs that shared a cell last frame but no longer do.
+    pub vanished: Vec<(u32, u32)>,
+}
+
+/// Last frame's pair set, used by `Grid::find_pairs_cached` to only recompute pairs involving entities that changed.
+/// Reset it with `clear` after clearing the grid.
+#[derive(Debug, Clone, Default)]
+pub struct PairCache {
+    pairs: Vec<(u32, u32)>,
+    primed: bool,
+}
+
+impl PairCache {
+    /// Create an empty cache. The first frame computes every pair.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the cached pairs, sorted with the smallest id first.
+    pub fn pairs(&self) -> &[(u32, u32)] {
+        &self.pairs
+    }
+
+    /// Forget the cached pairs so the next frame recomputes all of them.
+    pub fn clear(&mut self) {
+        self.pairs.clear();
+        self.primed = false;
+    }
+}
+
+impl Grid {
+    /// Find every pair of entities sharing a cell, reusing last frame's pairs from `cache`.
+    /// Only pairs involving entities reported by change tracking are recomputed, so change tracking must be enabled;
+    /// without it, or on the first frame, every pair is recomputed.
+    pub fn find_pairs_cached(&mut self, cache: &mut PairCache) -> PairEvents {
+        let previous = std::mem::take(&mut cache.pairs);
+        let changed = self.take_changes();
+
+        let current = if !cache.primed || self.changes.is_none() {
+            self.find_pairs()
+        } else {
+            let mut current: Vec<(u32, u32)> = previous
+                .iter()
+                .filter(|(a, b)| {
+                    changed.binary_search(a).is_err() && changed.binary_search(b).is_err()
+                })
+                .copied()
+                .collect();
+            for &id in changed.iter() {
+                for other in self.pairs_of(id) {
+                    current.push((id.min(other), id.max(other)));
+                }
+            }
+            current.sort_unstable();
+            current.dedup();
+            current
+        };
+
+        let mut events = PairEvents::default();
+        let (mut i, mut j) = (0, 0);
+        while i < previous.len() || j < current.len() {
+            match (previous.get(i), current.get(j)) {
+                (Some(old), Some(new)) if old == new => {
+                    events.persisting.push(*new);
+                    i += 1;
+                    j += 1;
+                }
+                (Some(old), Some(new)) if old < new => {
+                    events.vanished.push(*old);
+                    i += 1;
+                }
+                (Some(old), None) => {
+                    events.vanished.push(*old);
+                    i += 1;
+                }
+                (_, Some(new)) => {
+                    events.new.push(*new);
+                    j += 1;
+                }
+                (None, None) => unreachable!(),
+            }
+        }
+
+        cache.pairs = current;
+        cache.primed = true;
+        events
+    }
+
+    /// Get the ids sharing a cell with an entity, excluding itself. Static entities are not paired with each other.
+    fn pairs_of(&self, id: u32) -> Vec<u32> {
+        let map = self.maps.get_scalar(id);
+        if map.is_large && map.id == id {
+            return self.large_pairs_of(id);
+        }
+        let mut others = Vec::new();
+        for &(x, y) in map.cells.iter() {
+            let slot = self.grid.slot(x, y);
+            let dynamics = self.grid.iter(slot);
+            let statics = self
+                .has_statics
+                .then(|| self.statics.iter(self.statics.slot(x, y)))
+                .into_iter()
+                .flatten()
+                .filter(|_| !map.is_static);
+            for other in dynamics.chain(statics) {
+                let other = other & !(1 << 31);
+                if other != id && scan::position(&others, other).is_none() {
+                    others.push(other);
+                }
+            }
+            for other in self.large_in(x, y, x, y) {
+                let both_static = map.is_static && self.maps.get_scalar(other).is_static;
+                if !both_static && scan::position(&others, other).is_none() {
+                    others.push(other);
+                }
+            }
+        }
+        others
+    }
+}
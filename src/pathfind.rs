@@ -0,0 +1,164 @@
+//! A* pathfinding and free cell searches over cell coordinates, treating cells occupied by blocking layers as walls.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+use crate::{Grid, LayerMask};
+
+/// Cost of a straight step; diagonal steps cost 14, approximating the square root of two.
+const STRAIGHT: u64 = 10;
+const DIAGONAL: u64 = 14;
+
+impl Grid {
+    /// Find a path of cells from `start` to `goal`, moving in eight directions without cutting corners of blocked cells.
+    /// A cell is blocked when an entity in one of the layers in `mask` occupies it; the start cell is never blocked, so the
+    /// moving entity may stand in it. The returned path includes both ends. Returns `None` if the goal is blocked or cannot
+    /// be reached after expanding `max_cells` cells, which bounds the search on an unbounded grid.
+    pub fn find_path(
+        &self,
+        start: (u32, u32),
+        goal: (u32, u32),
+        mask: LayerMask,
+        max_cells: usize,
+    ) -> Option<Vec<(u32, u32)>> {
+        let mut blocked: HashMap<(u32, u32), bool> = HashMap::new();
+        let mut is_blocked = |cell: (u32, u32)| {
+            cell != start
+                && *blocked
+                    .entry(cell)
+                    .or_insert_with(|| self.is_cell_blocked(cell, mask))
+        };
+        if is_blocked(goal) {
+            return None;
+        }
+
+        let heuristic = |(x, y): (u32, u32)| {
+            let (dx, dy) = (x.abs_diff(goal.0) as u64, y.abs_diff(goal.1) as u64);
+            STRAIGHT * dx.max(dy) + (DIAGONAL - STRAIGHT) * dx.min(dy)
+        };
+        // best known cost to reach each cell, and the cell it was reached from.
+        let mut costs: HashMap<(u32, u32), (u64, (u32, u32))> = HashMap::new();
+        let mut open = BinaryHeap::new();
+        costs.insert(start, (0, start));
+        open.push(Reverse((heuristic(start), 0, start)));
+
+        let mut expanded = 0;
+        while let Some(Reverse((_, cost, cell))) = open.pop() {
+            if cell == goal {
+                let mut path = vec![goal];
+                let mut cell = goal;
+                while cell != start {
+                    cell = costs[&cell].1;
+                    path.push(cell);
+                }
+                path.reverse();
+                return Some(path);
+            }
+            if costs[&cell].0 < cost {
+                continue;
+            }
+            expanded += 1;
+            if expanded > max_cells {
+                return None;
+            }
+
+            for (dx, dy) in [
+                (-1, 0),
+                (1, 0),
+                (0, -1),
+                (0, 1),
+                (-1, -1),
+                (1, -1),
+                (-1, 1),
+                (1, 1),
+            ] {
+                let (Some(x), Some(y)) =
+                    (cell.0.checked_add_signed(dx), cell.1.checked_add_signed(dy))
+                else {
+                    continue;
+                };
+                if is_blocked((x, y)) {
+                    continue;
+                }
+                let diagonal = dx != 0 && dy != 0;
+                if diagonal && (is_blocked((x, cell.1)) || is_blocked((cell.0, y))) {
+                    continue;
+                }
+                let next = cost + if diagonal { DIAGONAL } else { STRAIGHT };
+                if costs.get(&(x, y)).is_none_or(|&(known, _)| next < known) {
+                    costs.insert((x, y), (next, cell));
+                    open.push(Reverse((next + heuristic((x, y)), next, (x, y))));
+                }
+            }
+        }
+        None
+    }
+
+    /// Find the cell nearest to the one containing the point `(x, y)` that no entity in the layers of `mask` occupies,
+    /// such as a respawn or teleport destination. Cells are searched in rings around the point's cell, up to `max_radius`
+    /// cells away, and the nearest by straight-line distance between cells wins, ties going to the topmost, then leftmost.
+    /// Returns the cell's coordinates, or `None` if every cell in reach is occupied.
+    pub fn nearest_empty_cell(
+        &self,
+        x: u32,
+        y: u32,
+        mask: LayerMask,
+        max_radius: u32,
+    ) -> Option<(u32, u32)> {
+        let (cx, cy) = (x >> self.shift, y >> self.shift);
+        let mut best: Option<(u64, (u32, u32))> = None;
+        for radius in 0..=max_radius {
+            // cells of later rings are at least `radius` away, so stop once that is farther than the best found.
+            if best.is_some_and(|(distance, _)| distance < radius as u64 * radius as u64) {
+                break;
+            }
+            let (sx, ex) = (cx.saturating_sub(radius), cx.saturating_add(radius));
+            let (sy, ey) = (cy.saturating_sub(radius), cy.saturating_add(radius));
+            for y in sy..=ey {
+                // the ring's top and bottom rows are whole; other rows only have its left and right cells.
+                let row = y.abs_diff(cy) == radius;
+                let left = cx.checked_sub(radius);
+                let right = cx.checked_add(radius).filter(|_| radius > 0);
+                let cells: &mut dyn Iterator<Item = u32> = if row {
+                    &mut (sx..=ex)
+                } else {
+                    &mut left.into_iter().chain(right)
+                };
+                for x in cells {
+                    let (dx, dy) = (x.abs_diff(cx) as u64, y.abs_diff(cy) as u64);
+                    let distance = dx * dx + dy * dy;
+                    if best.is_some_and(|(known, cell)| {
+                        (known, (cell.1, cell.0)) <= (distance, (y, x))
+                    }) {
+                        continue;
+                    }
+                    if !self.is_cell_blocked((x, y), mask) {
+                        best = Some((distance, (x, y)));
+                    }
+                }
+            }
+            // past the edges of the `u32` range in every direction, no ring holds new cells.
+            if sx == 0 && sy == 0 && ex == u32::MAX && ey == u32::MAX {
+                break;
+            }
+        }
+        best.map(|(_, cell)| cell)
+    }
+
+    /// Check whether an entity in one of the layers in `mask` occupies a cell.
+    pub(crate) fn is_cell_blocked(&self, (x, y): (u32, u32), mask: LayerMask) -> bool {
+        let mut found = Vec::new();
+        // buckets can be shared with other cells, so check that the blocker's rectangle covers this one.
+        let accept = |id| {
+            mask.contains(self.layer(id))
+                && self.rect(id).is_some_and(|entity| {
+                    entity.x >> self.shift <= x
+                        && x <= entity.x.saturating_add(entity.width) >> self.shift
+                        && entity.y >> self.shift <= y
+                        && y <= entity.y.saturating_add(entity.height) >> self.shift
+                })
+        };
+        self.collect_cells(x, y, x, y, 1, accept, &mut found);
+        !found.is_empty()
+    }
+}
@@ -0,0 +1,71 @@
+//! Finding room for a new rectangle among the entities of a region, such as to spawn buildings or loot.
+
+use crate::{overlaps, Grid, LayerMask, Query};
+
+impl Grid {
+    /// Find a position in a region where a `width` by `height` rectangle overlaps no entity in the layers of `mask`,
+    /// testing the entities' exact rectangles with edges inclusive like queries. The rectangle must fit inside the region.
+    /// Positions are tried top to bottom, then left to right, so the topmost and then leftmost free position is returned.
+    /// Returns `None` if the region has no room.
+    pub fn find_free_rect(
+        &self,
+        region: &Query,
+        width: u32,
+        height: u32,
+        mask: LayerMask,
+    ) -> Option<(u32, u32)> {
+        let (right, bottom) = (
+            region.x as u64 + region.width as u64,
+            region.y as u64 + region.height as u64,
+        );
+        if region.width < width || region.height < height {
+            return None;
+        }
+        let mut ids = self.query_layers(region, mask).into_vec();
+        ids.sort_unstable();
+        ids.dedup();
+        let obstacles: Vec<(u64, u64, u64, u64)> = ids
+            .into_iter()
+            .filter_map(|id| self.rect(id))
+            .filter(|entity| overlaps(entity, region))
+            .map(|entity| {
+                let (x, y) = (entity.x as u64, entity.y as u64);
+                (x, y, x + entity.width as u64, y + entity.height as u64)
+            })
+            .collect();
+
+        // a free rectangle can slide up until it touches the region or an entity, so the rows worth trying are the
+        // region's top and the rows just below each entity; likewise for columns within a row.
+        let last_y = bottom - height as u64;
+        let mut rows: Vec<u64> = std::iter::once(region.y as u64)
+            .chain(obstacles.iter().map(|&(_, _, _, ey)| ey + 1))
+            .filter(|&y| region.y as u64 <= y && y <= last_y)
+            .collect();
+        rows.sort_unstable();
+        rows.dedup();
+
+        let mut band = Vec::new();
+        for y in rows {
+            let end = y + height as u64;
+            band.clear();
+            band.extend(
+                obstacles
+                    .iter()
+                    .filter(|&&(_, sy, _, ey)| sy <= end && y <= ey)
+                    .map(|&(sx, _, ex, _)| (sx, ex)),
+            );
+            band.sort_unstable();
+            let mut x = region.x as u64;
+            for &(sx, ex) in band.iter() {
+                if x + (width as u64) < sx {
+                    break;
+                }
+                x = x.max(ex + 1);
+            }
+            if x + width as u64 <= right {
+                return Some((x as u32, y as u32));
+            }
+        }
+        None
+    }
+}
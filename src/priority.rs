@@ -0,0 +1,34 @@
+//! Per-entity priorities for ordering query results.
+
+use std::cmp::Reverse;
+
+use crate::{Error, Grid, Query, QueryResults};
+
+impl Grid {
+    /// Set an entity's priority. Entities start at priority 0; `update` keeps the priority and `delete` resets it.
+    /// Pinned entities always have priority 0.
+    pub fn set_priority(&mut self, id: u32, priority: u8) -> Result<(), Error> {
+        let map = self.maps.get_scalar_mut(id);
+        if !map.is_registered() || map.id != id {
+            return Err(Error::NotFound(id));
+        }
+        map.meta.priority = priority;
+        Ok(())
+    }
+
+    /// Get an entity's priority.
+    pub fn priority(&self, id: u32) -> u8 {
+        let map = self.maps.get_scalar(id);
+        if !map.is_registered() || map.id != id {
+            return 0;
+        }
+        map.meta.priority
+    }
+
+    /// Retrieve entities in a region, highest priority first. Entities with the same priority are ordered by id.
+    pub fn query_by_priority(&self, query: &Query) -> QueryResults {
+        let mut result = self.query(query);
+        result.sort_unstable_by_key(|&id| (Reverse(self.priority(id)), id));
+        result
+    }
+}
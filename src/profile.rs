@@ -0,0 +1,306 @@
+//! Usage counters collected while profiling, and a report recommending a shift and bucket capacity from them.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{Grid, Query};
+
+/// Counters of the work done by inserts and queries. Queries only take `&self`, so they count with relaxed atomics.
+#[derive(Debug, Default)]
+pub(crate) struct Counters {
+    inserts: AtomicU64,
+    cells_written: AtomicU64,
+    near_misses: AtomicU64,
+    overflows: AtomicU64,
+    peak_fill: AtomicU64,
+    queries: AtomicU64,
+    cells_visited: AtomicU64,
+    candidates: AtomicU64,
+    results: AtomicU64,
+    /// Sums of the width and height of the queried cell ranges.
+    query_columns: AtomicU64,
+    query_rows: AtomicU64,
+}
+
+/// The grid's counters, present while profiling is enabled.
+#[derive(Debug, Default)]
+pub(crate) struct Profile(Option<Box<Counters>>);
+
+impl Profile {
+    /// Count an insert or move about to write cells.
+    #[inline]
+    pub(crate) fn insert(&mut self) {
+        if let Some(counters) = &mut self.0 {
+            *counters.inserts.get_mut() += 1;
+        }
+    }
+
+    /// Count an id written to a cell, which now holds `fill` ids out of `capacity`.
+    #[inline]
+    pub(crate) fn write(&mut self, fill: usize, capacity: usize) {
+        if let Some(counters) = &mut self.0 {
+            *counters.cells_written.get_mut() += 1;
+            // a near miss leaves a cell at least three quarters full.
+            if fill * 4 >= capacity * 3 {
+                *counters.near_misses.get_mut() += 1;
+            }
+            let peak = counters.peak_fill.get_mut();
+            *peak = (*peak).max(fill as u64);
+        }
+    }
+
+    /// Count a write that found its cell full.
+    #[inline]
+    pub(crate) fn overflow(&mut self) {
+        if let Some(counters) = &mut self.0 {
+            *counters.overflows.get_mut() += 1;
+        }
+    }
+
+    /// Count a query over a `columns` by `rows` range of cells.
+    #[inline]
+    pub(crate) fn query(
+        &self,
+        (columns, rows): (u32, u32),
+        cells: usize,
+        candidates: usize,
+        results: usize,
+    ) {
+        if let Some(counters) = &self.0 {
+            counters.queries.fetch_add(1, Ordering::Relaxed);
+            let sums = [
+                (&counters.cells_visited, cells as u64),
+                (&counters.candidates, candidates as u64),
+                (&counters.results, results as u64),
+                (&counters.query_columns, columns as u64),
+                (&counters.query_rows, rows as u64),
+            ];
+            for (counter, value) in sums {
+                counter.fetch_add(value, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+impl Clone for Profile {
+    fn clone(&self) -> Self {
+        Self(self.0.as_ref().map(|counters| {
+            let copy = |counter: &AtomicU64| AtomicU64::new(counter.load(Ordering::Relaxed));
+            Box::new(Counters {
+                inserts: copy(&counters.inserts),
+                cells_written: copy(&counters.cells_written),
+                near_misses: copy(&counters.near_misses),
+                overflows: copy(&counters.overflows),
+                peak_fill: copy(&counters.peak_fill),
+                queries: copy(&counters.queries),
+                cells_visited: copy(&counters.cells_visited),
+                candidates: copy(&counters.candidates),
+                results: copy(&counters.results),
+                query_columns: copy(&counters.query_columns),
+                query_rows: copy(&counters.query_rows),
+            })
+        }))
+    }
+}
+
+/// A summary of the grid's usage since profiling was enabled, with recommended configuration changes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProfileReport {
+    /// Number of inserts, updates and moves that wrote cells.
+    pub inserts: u64,
+    /// Average number of cells written per insert.
+    pub cells_per_insert: f64,
+    /// Number of queries collecting unique ids.
+    pub queries: u64,
+    /// Average number of cells visited per query.
+    pub cells_per_query: f64,
+    /// Average number of ids read from cells per query.
+    pub candidates_per_query: f64,
+    /// Share of the ids read from cells that were not returned, being duplicates or filtered out.
+    pub dedup_waste: f64,
+    /// Number of writes leaving a cell at least three quarters full.
+    pub near_misses: u64,
+    /// Number of writes that found their cell full.
+    pub overflows: u64,
+    /// Most ids seen in one cell after a write.
+    pub peak_fill: usize,
+    /// A shift expected to make the recorded inserts and queries cheaper, if it differs from the current one.
+    pub suggested_shift: Option<u32>,
+    /// A bucket capacity fitting the most crowded cells with room to spare, if it differs from the current one.
+    pub suggested_capacity: Option<usize>,
+}
+
+impl fmt::Display for ProfileReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} inserts writing {:.2} cells each",
+            self.inserts, self.cells_per_insert
+        )?;
+        writeln!(
+            f,
+            "{} queries visiting {:.2} cells and {:.2} ids each, {:.1}% of the ids wasted",
+            self.queries,
+            self.cells_per_query,
+            self.candidates_per_query,
+            self.dedup_waste * 100.0
+        )?;
+        writeln!(
+            f,
+            "{} near misses and {} overflows, at most {} ids in a cell",
+            self.near_misses, self.overflows, self.peak_fill
+        )?;
+        match self.suggested_shift {
+            Some(shift) => writeln!(f, "consider a shift of {}", shift)?,
+            None => writeln!(f, "the shift fits the workload")?,
+        }
+        match self.suggested_capacity {
+            Some(capacity) => write!(f, "consider a bucket capacity of {}", capacity),
+            None => write!(f, "the bucket capacity fits the workload"),
+        }
+    }
+}
+
+/// The work done by one query of `Grid::query_profiled`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QueryProfile {
+    /// Number of cells visited, once per cell table.
+    pub cells_visited: usize,
+    /// Number of ids read from cells and the large entity list.
+    pub candidates: usize,
+    /// Number of ids skipped for having been read before.
+    pub duplicates: usize,
+    /// Number of unique ids whose exact shape or rectangle missed the region.
+    pub rejected: usize,
+}
+
+impl Grid {
+    /// Retrieve entities like `query_exact` and report how much work it took, such as to tune the cell size for a
+    /// query pattern. This walks the cells with a hash set instead of the tuned paths of `query`, so it is slower;
+    /// the counts are those of the cells and buckets, which `query` reads too.
+    pub fn query_profiled(&self, query: &Query) -> (Vec<u32>, QueryProfile) {
+        let sx = query.x >> self.shift;
+        let sy = query.y >> self.shift;
+
+        let ex = query.x.saturating_add(query.width) >> self.shift;
+        let ey = query.y.saturating_add(query.height) >> self.shift;
+
+        let layers = if self.has_statics {
+            &[&self.grid, &self.statics][..]
+        } else {
+            &[&self.grid][..]
+        };
+        let mut profile = QueryProfile::default();
+        let mut seen = HashSet::new();
+        let mut result = Vec::new();
+        let mut visit = |id: u32, profile: &mut QueryProfile| {
+            profile.candidates += 1;
+            if !seen.insert(id) {
+                profile.duplicates += 1;
+            } else if self.overlaps_exact(id, query) {
+                result.push(id);
+            } else {
+                profile.rejected += 1;
+            }
+        };
+        for buckets in layers {
+            for y in sy..=ey {
+                for x in sx..=ex {
+                    profile.cells_visited += 1;
+                    let slot = buckets.slot(x, y);
+                    if buckets.is_empty(slot) {
+                        continue;
+                    }
+                    for id in buckets.iter(slot) {
+                        visit(id & !(1 << 31), &mut profile);
+                    }
+                }
+            }
+        }
+        if !self.large.is_empty() {
+            for id in self.large_in(sx, sy, ex, ey) {
+                visit(id, &mut profile);
+            }
+        }
+        (result, profile)
+    }
+
+    /// Enable or disable profiling. While enabled, inserts and queries count the cells and ids they touch
+    /// for `profile_report`; enabling it again starts over.
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.profile = Profile(enabled.then(Box::default));
+    }
+
+    /// Check whether profiling is enabled.
+    pub fn profiling(&self) -> bool {
+        self.profile.0.is_some()
+    }
+
+    /// Summarize the usage counted since profiling was enabled and recommend a shift and bucket capacity for it.
+    /// The shift is chosen like `suggest_shift`, but weighs the cost of the current population's cells by the recorded mix
+    /// of inserts and query sizes. Returns `None` if profiling is disabled. This walks the whole population once per candidate shift.
+    pub fn profile_report(&self) -> Option<ProfileReport> {
+        let counters = self.profile.0.as_ref()?;
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        let per = |total: u64, count: u64| {
+            if count == 0 {
+                0.0
+            } else {
+                total as f64 / count as f64
+            }
+        };
+        let (inserts, queries) = (load(&counters.inserts), load(&counters.queries));
+        let candidates = load(&counters.candidates);
+        let results = load(&counters.results);
+        let peak_fill = load(&counters.peak_fill) as usize;
+
+        // the mean query size in world units, to price queries at other shifts.
+        let size = (1u64 << self.shift) as f64;
+        let width = per(load(&counters.query_columns), queries) * size;
+        let height = per(load(&counters.query_rows), queries) * size;
+        let best = (0..32)
+            .filter_map(|shift| self.shift_stats(shift))
+            .min_by(|a, b| {
+                let cost = |stats: &crate::ShiftStats| {
+                    let size = (1u64 << stats.shift) as f64;
+                    let cells = (width / size + 1.0) * (height / size + 1.0);
+                    queries as f64 * cells * (1.0 + stats.mean_occupancy)
+                        + inserts as f64 * stats.cells_per_entity
+                };
+                a.overflowing_cells
+                    .cmp(&b.overflowing_cells)
+                    .then(cost(a).total_cmp(&cost(b)))
+            });
+        let suggested_shift = best
+            .as_ref()
+            .map(|stats| stats.shift)
+            .filter(|&shift| shift != self.shift && inserts + queries > 0);
+
+        // size buckets for the most crowded cell at the suggested shift, or the fullest cell seen at this one.
+        let needed = match (&best, suggested_shift) {
+            (Some(stats), Some(_)) => stats.max_occupancy,
+            _ => peak_fill,
+        };
+        let capacity = self.grid.capacity;
+        let target = (needed * 2).next_power_of_two().min(u16::MAX as usize);
+        let crowded = needed * 4 >= capacity * 3;
+        let sparse = needed * 4 <= capacity && target < capacity;
+        let suggested_capacity =
+            Some(target).filter(|&target| needed > 0 && target != capacity && (crowded || sparse));
+
+        Some(ProfileReport {
+            inserts,
+            cells_per_insert: per(load(&counters.cells_written), inserts),
+            queries,
+            cells_per_query: per(load(&counters.cells_visited), queries),
+            candidates_per_query: per(candidates, queries),
+            dedup_waste: per(candidates.saturating_sub(results), candidates),
+            near_misses: load(&counters.near_misses),
+            overflows: load(&counters.overflows),
+            peak_fill,
+            suggested_shift,
+            suggested_capacity,
+        })
+    }
+}
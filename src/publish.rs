@@ -0,0 +1,86 @@
+//! Publishing immutable versions of a grid to concurrent readers while one writer keeps changing it.
+
+use std::sync::{Arc, RwLock};
+
+use crate::Grid;
+
+/// The version readers currently see, with its number.
+type Current = RwLock<(u64, Arc<Grid>)>;
+
+/// A grid with one writer and any number of readers. The writer changes its own copy freely, and `publish`
+/// swaps a copy of it in for readers, who keep querying the version they loaded until they load again.
+/// Readers only take a lock for as long as it takes to clone an `Arc`, so they never wait on the writer's changes.
+#[derive(Debug)]
+pub struct PublishedGrid {
+    writer: Grid,
+    current: Arc<Current>,
+}
+
+/// A handle for reading the latest published version of a `PublishedGrid` from any thread.
+#[derive(Debug, Clone)]
+pub struct GridReader {
+    current: Arc<Current>,
+}
+
+impl PublishedGrid {
+    /// Wrap a grid, publishing its current contents as version 0.
+    pub fn new(grid: Grid) -> Self {
+        let current = Arc::new(RwLock::new((0, Arc::new(grid.clone()))));
+        Self {
+            writer: grid,
+            current,
+        }
+    }
+
+    /// Get the writer's copy, including changes that were not published yet.
+    pub fn grid(&self) -> &Grid {
+        &self.writer
+    }
+
+    /// Get the writer's copy to change it. Readers see nothing until the next `publish`.
+    pub fn grid_mut(&mut self) -> &mut Grid {
+        &mut self.writer
+    }
+
+    /// Make the writer's copy visible to readers as a new version, returning its number.
+    /// This clones the whole grid, so batch changes and publish once per frame or tick.
+    pub fn publish(&mut self) -> u64 {
+        let snapshot = Arc::new(self.writer.clone());
+        let (version, old) = {
+            let mut current = self
+                .current
+                .write()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            current.0 += 1;
+            (current.0, std::mem::replace(&mut current.1, snapshot))
+        };
+        // freeing the old version can take a while, so it happens after the lock is released.
+        drop(old);
+        version
+    }
+
+    /// Get a handle for readers.
+    pub fn reader(&self) -> GridReader {
+        GridReader {
+            current: Arc::clone(&self.current),
+        }
+    }
+}
+
+impl GridReader {
+    /// Get the latest published version. It stays valid and unchanged for as long as it is held.
+    pub fn load(&self) -> Arc<Grid> {
+        Arc::clone(&self.read().1)
+    }
+
+    /// Get the number of the latest published version, to check for a new one without loading it.
+    pub fn version(&self) -> u64 {
+        self.read().0
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, (u64, Arc<Grid>)> {
+        self.current
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
@@ -0,0 +1,257 @@
+//! Region quadtree for worlds with very uneven entity density.
+
+use std::collections::HashMap;
+
+use crate::{check_entity, overlaps, Entity, Error, Query, SpatialIndex};
+
+const ROOT: usize = 0;
+
+#[derive(Debug, Clone)]
+struct Node {
+    x: u64,
+    y: u64,
+    width: u64,
+    height: u64,
+    /// Index of the first of four consecutive children, if split.
+    children: Option<usize>,
+    items: Vec<u32>,
+}
+
+impl Node {
+    fn new(x: u64, y: u64, width: u64, height: u64) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+            children: None,
+            items: Vec::new(),
+        }
+    }
+
+    fn contains(&self, entity: &Entity) -> bool {
+        entity.x as u64 >= self.x
+            && entity.y as u64 >= self.y
+            && entity.x as u64 + entity.width as u64 <= self.x + self.width
+            && entity.y as u64 + entity.height as u64 <= self.y + self.height
+    }
+
+    fn overlaps(&self, query: &Query) -> bool {
+        query.x as u64 <= self.x + self.width
+            && self.x <= query.x as u64 + query.width as u64
+            && query.y as u64 <= self.y + self.height
+            && self.y <= query.y as u64 + query.height as u64
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Placement {
+    entity: Entity,
+    node: usize,
+}
+
+/// Quadtree spatial index. Entities are stored in the smallest node that fully contains them,
+/// and queries return only entities whose rectangles actually overlap the query.
+#[derive(Debug, Clone)]
+pub struct Quadtree {
+    nodes: Vec<Node>,
+    entities: HashMap<u32, Placement>,
+    node_capacity: usize,
+    max_depth: u32,
+}
+
+impl Quadtree {
+    /// Create a quadtree covering a `width` by `height` world.
+    pub fn new(width: u32, height: u32) -> Self {
+        Self::with_limits(width, height, 8, 16)
+    }
+
+    /// Create a quadtree whose leaves split after holding `node_capacity` entities, at most `max_depth` levels deep.
+    pub fn with_limits(width: u32, height: u32, node_capacity: usize, max_depth: u32) -> Self {
+        Self {
+            nodes: vec![Node::new(0, 0, width as u64, height as u64)],
+            entities: HashMap::new(),
+            node_capacity,
+            max_depth,
+        }
+    }
+
+    /// Get the number of entities in the tree.
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    /// Check whether the tree holds no entities.
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    /// Insert an entity. Inserting an id that is already present moves it; `SpatialIndex::insert` rejects it instead.
+    pub fn insert(&mut self, entity: &Entity) {
+        if self.entities.contains_key(&entity.id) {
+            self.delete(entity.id);
+        }
+
+        let mut node = ROOT;
+        let mut depth = 0;
+        while let Some(first) = self.nodes[node].children {
+            match (first..first + 4).find(|&c| self.nodes[c].contains(entity)) {
+                Some(child) => {
+                    node = child;
+                    depth += 1;
+                }
+                None => break,
+            }
+        }
+
+        self.nodes[node].items.push(entity.id);
+        self.entities.insert(
+            entity.id,
+            Placement {
+                entity: entity.clone(),
+                node,
+            },
+        );
+
+        if self.nodes[node].children.is_none()
+            && self.nodes[node].items.len() > self.node_capacity
+            && depth < self.max_depth
+        {
+            self.split(node);
+        }
+    }
+
+    fn split(&mut self, node: usize) {
+        let Node {
+            x,
+            y,
+            width,
+            height,
+            ..
+        } = self.nodes[node];
+        if width < 2 || height < 2 {
+            return;
+        }
+
+        let (hw, hh) = (width / 2, height / 2);
+        let first = self.nodes.len();
+        self.nodes.push(Node::new(x, y, hw, hh));
+        self.nodes.push(Node::new(x + hw, y, width - hw, hh));
+        self.nodes.push(Node::new(x, y + hh, hw, height - hh));
+        self.nodes
+            .push(Node::new(x + hw, y + hh, width - hw, height - hh));
+        self.nodes[node].children = Some(first);
+
+        let items = std::mem::take(&mut self.nodes[node].items);
+        for id in items {
+            let placement = self.entities.get_mut(&id).unwrap();
+            let target = (first..first + 4)
+                .find(|&c| self.nodes[c].contains(&placement.entity))
+                .unwrap_or(node);
+            placement.node = target;
+            self.nodes[target].items.push(id);
+        }
+    }
+
+    /// Delete an entity by ID.
+    pub fn delete(&mut self, id: u32) {
+        if let Some(placement) = self.entities.remove(&id) {
+            let items = &mut self.nodes[placement.node].items;
+            if let Some(index) = items.iter().position(|&x| x == id) {
+                items.swap_remove(index);
+            }
+        }
+    }
+
+    /// Move or resize an entity.
+    pub fn update(&mut self, entity: &Entity) {
+        self.insert(entity);
+    }
+
+    /// Retrieve entities whose rectangles overlap a region.
+    pub fn query(&self, query: &Query) -> Vec<u32> {
+        let mut result = Vec::new();
+        let mut stack = vec![ROOT];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            // the root also holds entities outside the world bounds, so it is always visited.
+            if index != ROOT && !node.overlaps(query) {
+                continue;
+            }
+            for id in node.items.iter() {
+                if overlaps(&self.entities[id].entity, query) {
+                    result.push(*id);
+                }
+            }
+            if let Some(first) = node.children {
+                stack.extend(first..first + 4);
+            }
+        }
+        result
+    }
+
+    /// Retrieve entities whose rectangles contain a point.
+    pub fn query_point(&self, x: u32, y: u32) -> Vec<u32> {
+        self.query(&Query {
+            x,
+            y,
+            width: 0,
+            height: 0,
+        })
+    }
+
+    /// Find every pair of overlapping entities, smallest id first.
+    pub fn find_pairs(&self) -> Vec<(u32, u32)> {
+        let mut pairs = Vec::new();
+        for (&id, placement) in self.entities.iter() {
+            for other in self.query(&(&placement.entity).into()) {
+                if id < other {
+                    pairs.push((id, other));
+                }
+            }
+        }
+        pairs.sort_unstable();
+        pairs
+    }
+
+    /// Remove every entity and collapse the tree.
+    pub fn clear(&mut self) {
+        self.nodes.truncate(1);
+        self.nodes[ROOT].children = None;
+        self.nodes[ROOT].items.clear();
+        self.entities.clear();
+    }
+}
+
+impl SpatialIndex for Quadtree {
+    fn insert(&mut self, entity: &Entity) -> Result<(), Error> {
+        check_entity(entity)?;
+        if self.entities.contains_key(&entity.id) {
+            return Err(Error::DuplicateId(entity.id));
+        }
+        Quadtree::insert(self, entity);
+        Ok(())
+    }
+
+    fn delete(&mut self, id: u32) {
+        Quadtree::delete(self, id)
+    }
+
+    fn update(&mut self, entity: &Entity) -> Result<(), Error> {
+        check_entity(entity)?;
+        Quadtree::update(self, entity);
+        Ok(())
+    }
+
+    fn query(&self, query: &Query) -> Vec<u32> {
+        Quadtree::query(self, query)
+    }
+
+    fn query_point(&self, x: u32, y: u32) -> Vec<u32> {
+        Quadtree::query_point(self, x, y)
+    }
+
+    fn find_pairs(&self) -> Vec<(u32, u32)> {
+        Quadtree::find_pairs(self)
+    }
+}
@@ -0,0 +1,152 @@
+//! Raw access to the cell buckets, for callers doing their own filtering and deduplication.
+
+use crate::{Grid, Query, QueryResults};
+
+/// The ids stored in a cell, which `Grid::visit_cells_mut` lets callers reorder but not change.
+/// Spilled ids follow the inline ones, and ids keep the high bit marking entities that occupy only one cell.
+#[derive(Debug)]
+pub struct CellIds<'a> {
+    inline: &'a mut [u32],
+    spilled: &'a mut [u32],
+}
+
+impl CellIds<'_> {
+    /// Get the number of ids.
+    pub fn len(&self) -> usize {
+        self.inline.len() + self.spilled.len()
+    }
+
+    /// Check whether the cell holds no ids.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the id at an index. Panics if the index is out of range.
+    pub fn get(&self, index: usize) -> u32 {
+        *self.raw(index) & !(1 << 31)
+    }
+
+    /// Iterate over the ids in their stored order.
+    pub fn iter(&self) -> impl Iterator<Item = u32> + '_ {
+        self.inline
+            .iter()
+            .chain(self.spilled.iter())
+            .map(|id| id & !(1 << 31))
+    }
+
+    /// Swap the ids at two indices. Panics if either is out of range.
+    pub fn swap(&mut self, a: usize, b: usize) {
+        let (a, b) = (a.min(b), a.max(b));
+        let inline = self.inline.len();
+        if b < inline {
+            self.inline.swap(a, b);
+        } else if a >= inline {
+            self.spilled.swap(a - inline, b - inline);
+        } else {
+            std::mem::swap(&mut self.inline[a], &mut self.spilled[b - inline]);
+        }
+    }
+
+    /// Move the id at an index to the front, shifting the ids before it back by one. Panics if the index is out of range.
+    pub fn move_to_front(&mut self, index: usize) {
+        assert!(index < self.len(), "index out of range");
+        for i in (1..=index).rev() {
+            self.swap(i - 1, i);
+        }
+    }
+
+    fn raw(&self, index: usize) -> &u32 {
+        match self.inline.get(index) {
+            Some(id) => id,
+            None => &self.spilled[index - self.inline.len()],
+        }
+    }
+}
+
+impl Grid {
+    /// Call `visit` with the ids stored in each cell of a region, without copying or deduplicating them.
+    /// Ids are raw: the high bit is set on ids of entities occupying only that one cell. Dynamic cells are visited
+    /// before static ones, so a cell can be visited once per layer, and once more for ids spilled to the heap.
+    /// Under the hashed layouts a slice also holds the ids of cells sharing its bucket. Large entities are not visited;
+    /// `query_raw` includes them.
+    pub fn visit_cells(&self, query: &Query, mut visit: impl FnMut((u32, u32), &[u32])) {
+        let sx = query.x >> self.shift;
+        let sy = query.y >> self.shift;
+
+        let ex = query.x.saturating_add(query.width) >> self.shift;
+        let ey = query.y.saturating_add(query.height) >> self.shift;
+
+        let layers = if self.has_statics {
+            &[&self.grid, &self.statics][..]
+        } else {
+            &[&self.grid][..]
+        };
+        for buckets in layers {
+            for y in sy..=ey {
+                for x in sx..=ex {
+                    let slot = buckets.slot(x, y);
+                    if buckets.is_empty(slot) {
+                        continue;
+                    }
+                    visit((x, y), buckets.get(slot));
+                    let spilled = buckets.spilled(slot);
+                    if !spilled.is_empty() {
+                        visit((x, y), spilled);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Call `visit` with the ids stored in each cell of a region so it can reorder them, such as to move the ids queries
+    /// hit most to the front of their buckets. Cells are visited like with `visit_cells`, but inline and spilled ids
+    /// are one list. Reordering only changes the order later queries and `visit_cells` find ids in.
+    pub fn visit_cells_mut(
+        &mut self,
+        query: &Query,
+        mut visit: impl FnMut((u32, u32), CellIds<'_>),
+    ) {
+        let sx = query.x >> self.shift;
+        let sy = query.y >> self.shift;
+
+        let ex = query.x.saturating_add(query.width) >> self.shift;
+        let ey = query.y.saturating_add(query.height) >> self.shift;
+
+        let has_statics = self.has_statics;
+        for buckets in [&mut self.grid, &mut self.statics] {
+            for y in sy..=ey {
+                for x in sx..=ex {
+                    let slot = buckets.slot(x, y);
+                    if buckets.is_empty(slot) {
+                        continue;
+                    }
+                    let (inline, spilled) = buckets.get_mut(slot);
+                    visit((x, y), CellIds { inline, spilled });
+                }
+            }
+            if !has_statics {
+                break;
+            }
+        }
+    }
+
+    /// Retrieve entities in a region without deduplicating them: an entity is returned once for every covered cell
+    /// it occupies, and under the hashed layouts entities of cells sharing a bucket may be returned too.
+    /// Cheaper than `query` in dense scenes for callers that deduplicate or tolerate repeats anyway.
+    pub fn query_raw(&self, query: &Query) -> QueryResults {
+        let mut result = self.results.take();
+        self.visit_cells(query, |_, ids| {
+            result.extend(ids.iter().map(|id| id & !(1 << 31)));
+        });
+        if !self.large.is_empty() {
+            let sx = query.x >> self.shift;
+            let sy = query.y >> self.shift;
+
+            let ex = query.x.saturating_add(query.width) >> self.shift;
+            let ey = query.y.saturating_add(query.height) >> self.shift;
+
+            result.extend(self.large_in(sx, sy, ex, ey));
+        }
+        QueryResults::new(result)
+    }
+}
@@ -0,0 +1,393 @@
+//! Compact binary log of grid mutations, for sending a reproduction of a bug instead of minimizing it by hand.
+
+#[cfg(feature = "record")]
+use std::fmt;
+#[cfg(feature = "record")]
+use std::io::{self, Read};
+
+use crate::{Entity, OverflowPolicy};
+#[cfg(feature = "record")]
+use crate::{Grid, Layer};
+
+/// A mutation as it is applied to the grid's tables. High-level operations are logged as the mutations they
+/// perform, so rehashing, rollbacks and updates that move entities to other cells replay exactly.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(not(feature = "record"), allow(dead_code))]
+pub(crate) enum Op<'a> {
+    Insert(&'a Entity),
+    InsertStatic(&'a Entity),
+    InsertPinned(&'a Entity),
+    Delete(u32),
+    /// An update that kept the entity's cells, or a `move_by` that only rewrote the cells it left and entered.
+    Update(&'a Entity),
+    Clear,
+    ClearDynamic,
+    /// The grid was rehashed to a new shift after being cleared.
+    Shift(u32),
+    Overflow(OverflowPolicy),
+    /// The world origin moved, re-inserting the entities if its cell changed.
+    Origin((u32, u32)),
+}
+
+/// Opcodes, each followed by its fields as little-endian `u32`s, except the policy which is one byte.
+#[cfg(feature = "record")]
+pub(crate) mod code {
+    pub(crate) const INSERT: u8 = 1;
+    pub(crate) const INSERT_STATIC: u8 = 2;
+    pub(crate) const INSERT_PINNED: u8 = 3;
+    pub(crate) const DELETE: u8 = 4;
+    pub(crate) const UPDATE: u8 = 5;
+    pub(crate) const CLEAR: u8 = 6;
+    pub(crate) const CLEAR_DYNAMIC: u8 = 7;
+    pub(crate) const SHIFT: u8 = 8;
+    pub(crate) const OVERFLOW: u8 = 9;
+    pub(crate) const ORIGIN: u8 = 10;
+}
+
+impl Op<'_> {
+    #[cfg(feature = "record")]
+    fn encode(self, log: &mut Vec<u8>) {
+        let entity = |log: &mut Vec<u8>, code: u8, entity: &Entity| {
+            log.push(code);
+            for field in [entity.id, entity.x, entity.y, entity.width, entity.height] {
+                log.extend_from_slice(&field.to_le_bytes());
+            }
+        };
+        match self {
+            Op::Insert(e) => entity(log, code::INSERT, e),
+            Op::InsertStatic(e) => entity(log, code::INSERT_STATIC, e),
+            Op::InsertPinned(e) => entity(log, code::INSERT_PINNED, e),
+            Op::Update(e) => entity(log, code::UPDATE, e),
+            Op::Delete(id) => {
+                log.push(code::DELETE);
+                log.extend_from_slice(&id.to_le_bytes());
+            }
+            Op::Clear => log.push(code::CLEAR),
+            Op::ClearDynamic => log.push(code::CLEAR_DYNAMIC),
+            Op::Shift(shift) => {
+                log.push(code::SHIFT);
+                log.extend_from_slice(&shift.to_le_bytes());
+            }
+            Op::Overflow(policy) => log.extend_from_slice(&[code::OVERFLOW, policy as u8]),
+            Op::Origin((x, y)) => {
+                log.push(code::ORIGIN);
+                log.extend_from_slice(&x.to_le_bytes());
+                log.extend_from_slice(&y.to_le_bytes());
+            }
+        }
+    }
+}
+
+/// The grid's mutation log. Without the `record` feature it holds nothing and recording compiles away.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Recorder {
+    #[cfg(feature = "record")]
+    log: Option<Vec<u8>>,
+}
+
+impl Recorder {
+    #[inline(always)]
+    pub(crate) fn record(&mut self, op: Op) {
+        #[cfg(feature = "record")]
+        if let Some(log) = &mut self.log {
+            op.encode(log);
+        }
+        #[cfg(not(feature = "record"))]
+        let _ = op;
+    }
+
+    pub(crate) fn bytes_used(&self) -> usize {
+        #[cfg(feature = "record")]
+        return self.log.as_ref().map_or(0, crate::memory::vec_bytes);
+        #[cfg(not(feature = "record"))]
+        0
+    }
+}
+
+#[cfg(feature = "record")]
+impl Grid {
+    /// Start logging every mutation. The log opens with the overflow policy, shift, origin and current contents,
+    /// so it can be replayed onto any empty grid with the same size, bucket capacity and cell layout.
+    /// Rectangles are logged, not priorities, layers, groups, tags or exact shapes, which are replayed as their bounding boxes.
+    pub fn start_recording(&mut self) {
+        let mut log = Vec::new();
+        Op::Overflow(self.overflow).encode(&mut log);
+        Op::Clear.encode(&mut log);
+        Op::Shift(self.shift).encode(&mut log);
+        Op::Origin(self.origin).encode(&mut log);
+        for (entity, layer) in self.entities() {
+            match layer {
+                Layer::Dynamic => Op::Insert(&entity),
+                Layer::Static => Op::InsertStatic(&entity),
+                Layer::Pinned => Op::InsertPinned(&entity),
+            }
+            .encode(&mut log);
+        }
+        self.recorder.log = Some(log);
+    }
+
+    /// Stop logging, returning the part of the log that was not taken yet.
+    pub fn stop_recording(&mut self) -> Vec<u8> {
+        self.recorder.log.take().unwrap_or_default()
+    }
+
+    /// Take the log recorded so far, for example to append it to a file, and keep recording.
+    pub fn take_recording(&mut self) -> Vec<u8> {
+        self.recorder
+            .log
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// Check whether mutations are being logged.
+    pub fn is_recording(&self) -> bool {
+        self.recorder.log.is_some()
+    }
+}
+
+/// Why a replay stopped.
+#[cfg(feature = "record")]
+#[derive(Debug)]
+pub enum ReplayError {
+    /// Reading the log failed, or it ended in the middle of an operation.
+    Io(io::Error),
+    /// The log contains an unknown opcode at this operation index.
+    InvalidOp { step: usize, code: u8 },
+    /// After this operation, the cells of this entity no longer match its map.
+    Invariant { step: usize, id: u32 },
+}
+
+#[cfg(feature = "record")]
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Io(err) => write!(f, "cannot read replay log: {}", err),
+            ReplayError::InvalidOp { step, code } => {
+                write!(f, "unknown opcode {} at operation {}", code, step)
+            }
+            ReplayError::Invariant { step, id } => {
+                write!(f, "entity {} is corrupted after operation {}", id, step)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "record")]
+impl std::error::Error for ReplayError {}
+
+#[cfg(feature = "record")]
+impl From<io::Error> for ReplayError {
+    fn from(value: io::Error) -> Self {
+        ReplayError::Io(value)
+    }
+}
+
+#[cfg(feature = "record")]
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+#[cfg(feature = "record")]
+fn read_entity(reader: &mut impl Read) -> io::Result<Entity> {
+    Ok(Entity {
+        id: read_u32(reader)?,
+        x: read_u32(reader)?,
+        y: read_u32(reader)?,
+        width: read_u32(reader)?,
+        height: read_u32(reader)?,
+    })
+}
+
+#[cfg(feature = "record")]
+impl Grid {
+    /// Re-execute a log written by the recorder, checking after each operation that the entity it touched
+    /// is stored in exactly the cells its map lists and has left the cells it moved out of.
+    /// Failed inserts are replayed as they happened rather than reported. Returns the number of operations replayed.
+    pub fn replay(&mut self, mut reader: impl Read) -> Result<usize, ReplayError> {
+        let mut step = 0;
+        loop {
+            let mut code = [0];
+            if reader.read(&mut code)? == 0 {
+                return Ok(step);
+            }
+            let id = match code[0] {
+                code::INSERT | code::INSERT_STATIC | code::INSERT_PINNED | code::UPDATE => {
+                    let entity = read_entity(&mut reader)?;
+                    let before = self.cells_of(entity.id);
+                    let _ = match code[0] {
+                        code::INSERT => self.insert(&entity),
+                        code::INSERT_STATIC => self.insert_static(&entity),
+                        code::INSERT_PINNED => self.insert_pinned(&entity),
+                        _ => self.update(&entity),
+                    };
+                    Some((entity.id, before))
+                }
+                code::DELETE => {
+                    let id = read_u32(&mut reader)?;
+                    let before = self.cells_of(id);
+                    self.delete(id);
+                    Some((id, before))
+                }
+                code::CLEAR => {
+                    self.clear();
+                    None
+                }
+                code::CLEAR_DYNAMIC => {
+                    self.clear_dynamic();
+                    None
+                }
+                code::SHIFT => {
+                    let shift = read_u32(&mut reader)?;
+                    let _ = self.rehash_with_shift(shift);
+                    None
+                }
+                code::ORIGIN => {
+                    let origin = (read_u32(&mut reader)?, read_u32(&mut reader)?);
+                    let _ = self.recenter(origin);
+                    None
+                }
+                code::OVERFLOW => {
+                    let mut policy = [0];
+                    reader.read_exact(&mut policy)?;
+                    self.set_overflow_policy(match policy[0] {
+                        0 => OverflowPolicy::Error,
+                        1 => OverflowPolicy::EvictOldest,
+                        2 => OverflowPolicy::SpillToHeap,
+                        3 => OverflowPolicy::DropIncoming,
+                        code => return Err(ReplayError::InvalidOp { step, code }),
+                    });
+                    None
+                }
+                code => return Err(ReplayError::InvalidOp { step, code }),
+            };
+            if let Some((id, before)) = id {
+                if !self.is_consistent(id, &before) {
+                    return Err(ReplayError::Invariant { step, id });
+                }
+            }
+            step += 1;
+        }
+    }
+
+    /// Get the cells an entity is mapped to and whether they are in the static layer.
+    fn cells_of(&self, id: u32) -> (Vec<(u32, u32)>, bool) {
+        let map = self.maps.get_scalar(id);
+        if map.id != id {
+            return (Vec::new(), false);
+        }
+        (map.cells.to_vec(), map.is_static)
+    }
+
+    /// Check that an entity is stored once per mapped cell, and gone from the cells it was mapped to `before`.
+    fn is_consistent(&self, id: u32, before: &(Vec<(u32, u32)>, bool)) -> bool {
+        let (cells, is_static) = self.cells_of(id);
+        let count = |is_static: bool, slot: usize| {
+            let buckets = if is_static { &self.statics } else { &self.grid };
+            buckets
+                .iter(slot)
+                .filter(|&&stored| stored & !(1 << 31) == id)
+                .count()
+        };
+        let slot = |is_static: bool, (x, y): (u32, u32)| {
+            let buckets = if is_static { &self.statics } else { &self.grid };
+            buckets.slot(x, y)
+        };
+        let slots: Vec<usize> = cells.iter().map(|&cell| slot(is_static, cell)).collect();
+        // cells of one entity can share a slot, in which case the slot holds the id once per cell.
+        let mapped = slots
+            .iter()
+            .all(|&s| count(is_static, s) == slots.iter().filter(|&&other| other == s).count());
+        let (old, was_static) = before;
+        let left = old.iter().all(|&cell| {
+            let s = slot(*was_static, cell);
+            (*was_static == is_static && slots.contains(&s)) || count(*was_static, s) == 0
+        });
+        mapped && left
+    }
+}
+
+#[cfg(all(test, feature = "record"))]
+mod tests {
+    use super::ReplayError;
+    use crate::{Entity, Grid, OverflowPolicy, Query};
+
+    fn square(id: u32, x: u32) -> Entity {
+        Entity {
+            id,
+            x,
+            y: 0,
+            width: 4,
+            height: 4,
+        }
+    }
+
+    fn everything(grid: &Grid) -> Vec<u32> {
+        let mut ids = grid
+            .query(&Query {
+                x: 0,
+                y: 0,
+                width: 1000,
+                height: 100,
+            })
+            .into_vec();
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    }
+
+    #[test]
+    fn recording_logs_only_while_enabled() {
+        let mut grid = Grid::new(64, 4);
+        grid.insert(&square(1, 0)).unwrap();
+        assert!(!grid.is_recording());
+        assert!(grid.take_recording().is_empty());
+
+        grid.start_recording();
+        assert!(grid.is_recording());
+        let opening = grid.take_recording();
+        assert!(!opening.is_empty());
+        assert!(grid.is_recording());
+        grid.delete(1);
+        assert!(!grid.stop_recording().is_empty());
+        assert!(!grid.is_recording());
+        grid.insert(&square(2, 0)).unwrap();
+        assert!(grid.stop_recording().is_empty());
+    }
+
+    #[test]
+    fn replay_reproduces_the_recorded_grid() {
+        let mut grid = Grid::new(64, 4);
+        grid.insert(&square(1, 0)).unwrap();
+        grid.set_overflow_policy(OverflowPolicy::SpillToHeap);
+        grid.start_recording();
+        grid.insert(&square(2, 100)).unwrap();
+        grid.insert_static(&square(3, 200)).unwrap();
+        grid.insert_pinned(&square(4, 300)).unwrap();
+        grid.update(&square(2, 500)).unwrap();
+        grid.delete(1);
+        let log = grid.stop_recording();
+
+        let mut replayed = Grid::new(64, 4);
+        // the opening overflow policy, clear, shift, origin and insert of 1, then the recorded operations,
+        // with the update that changed cells logged as the delete and insert it performs.
+        assert_eq!(replayed.replay(&log[..]).unwrap(), 11);
+        assert_eq!(everything(&replayed), everything(&grid));
+        assert_eq!(everything(&replayed), [2, 3, 4]);
+        assert_eq!(replayed.overflow_policy(), OverflowPolicy::SpillToHeap);
+    }
+
+    #[test]
+    fn replay_rejects_unknown_opcodes() {
+        let mut grid = Grid::new(64, 4);
+        assert!(matches!(
+            grid.replay(&[0xff][..]),
+            Err(ReplayError::InvalidOp {
+                step: 0,
+                code: 0xff
+            })
+        ));
+    }
+}
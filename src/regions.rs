@@ -0,0 +1,59 @@
+//! Connected regions of occupied cells, such as walls enclosing a base.
+
+use std::collections::{HashSet, VecDeque};
+
+use crate::{Grid, LayerMask};
+
+impl Grid {
+    /// Group the cells occupied by entities in the layers in `mask` into regions of cells sharing an edge.
+    /// Each region lists its cells sorted by row, then column, and regions are ordered by their first cell.
+    /// Cells come from the entities' maps like with `hotspots`, so large entities are not counted. This iterates over every entry of the internal tables.
+    pub fn connected_regions(&self, mask: LayerMask) -> Vec<Vec<(u32, u32)>> {
+        let mut occupied: HashSet<(u32, u32)> = HashSet::new();
+        for map in self.maps.entries.iter() {
+            if map.is_registered() && mask.contains(map.meta.layer) {
+                occupied.extend(map.cells.iter().copied());
+            }
+        }
+        if mask.contains(0) {
+            for entity in self.pinned.values() {
+                let sx = entity.x >> self.shift;
+                let sy = entity.y >> self.shift;
+                let ex = entity.x.saturating_add(entity.width) >> self.shift;
+                let ey = entity.y.saturating_add(entity.height) >> self.shift;
+                for y in sy..=ey {
+                    occupied.extend((sx..=ex).map(|x| (x, y)));
+                }
+            }
+        }
+
+        let mut starts: Vec<(u32, u32)> = occupied.iter().copied().collect();
+        starts.sort_unstable_by_key(|&(x, y)| (y, x));
+        let mut regions = Vec::new();
+        let mut open = VecDeque::new();
+        for start in starts {
+            if !occupied.remove(&start) {
+                continue;
+            }
+            let mut region = vec![start];
+            open.push_back(start);
+            while let Some((x, y)) = open.pop_front() {
+                let neighbors = [
+                    x.checked_sub(1).map(|x| (x, y)),
+                    x.checked_add(1).map(|x| (x, y)),
+                    y.checked_sub(1).map(|y| (x, y)),
+                    y.checked_add(1).map(|y| (x, y)),
+                ];
+                for cell in neighbors.into_iter().flatten() {
+                    if occupied.remove(&cell) {
+                        region.push(cell);
+                        open.push_back(cell);
+                    }
+                }
+            }
+            region.sort_unstable_by_key(|&(x, y)| (y, x));
+            regions.push(region);
+        }
+        regions
+    }
+}
@@ -0,0 +1,46 @@
+//! Text rendering of cell occupancy, for eyeballing the grid from a terminal.
+
+use crate::{Grid, Query};
+
+impl Grid {
+    /// Render how full the cells under a region are, one character per cell and one line per row of cells.
+    /// `.` is an empty cell, `:` is under half full, `#` is half full or more and `!` is at least seven eighths of
+    /// `bucket_capacity` full, spilled ids included. Cells sharing a slot show that slot's fill.
+    /// Regions more than `max_cols` cells wide are scaled down, each character showing the fullest cell of a square block.
+    pub fn render_ascii(&self, region: &Query, max_cols: usize) -> String {
+        let sx = region.x >> self.shift;
+        let sy = region.y >> self.shift;
+        let ex = region.x.saturating_add(region.width) >> self.shift;
+        let ey = region.y.saturating_add(region.height) >> self.shift;
+        let step = ((ex - sx) as usize / max_cols.max(1) + 1) as u32;
+
+        let capacity = self.grid.capacity;
+        let fill = |x: u32, y: u32| {
+            let mut len = self.grid.len(self.grid.slot(x, y));
+            if self.has_statics {
+                len = len.max(self.statics.len(self.statics.slot(x, y)));
+            }
+            len
+        };
+
+        let mut out = String::new();
+        for by in (sy..=ey).step_by(step as usize) {
+            for bx in (sx..=ex).step_by(step as usize) {
+                let mut len = 0;
+                for y in by..=by.saturating_add(step - 1).min(ey) {
+                    for x in bx..=bx.saturating_add(step - 1).min(ex) {
+                        len = len.max(fill(x, y));
+                    }
+                }
+                out.push(match len {
+                    0 => '.',
+                    _ if len * 8 >= capacity * 7 => '!',
+                    _ if len * 2 >= capacity => '#',
+                    _ => ':',
+                });
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
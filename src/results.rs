@@ -0,0 +1,143 @@
+//! The ids found by a query, in a buffer that can be handed back to the grid for the next query.
+
+use std::ops::{Deref, DerefMut};
+use std::sync::Mutex;
+use std::{slice, vec};
+
+use crate::memory::vec_bytes;
+use crate::{Entity, Grid};
+
+/// Most buffers the grid keeps for reuse; buffers given back past this are dropped.
+const POOLED_BUFFERS: usize = 8;
+
+/// The ids of the entities found by a query, in no particular order. Dereferences to a slice of ids, so they can be sorted in place.
+/// Ids are unique except where cells share a slot under the fixed layouts, which can repeat ids of single-cell entities;
+/// the `Sparse` layout never repeats one.
+/// Give it back with `Grid::recycle` once done to let the next query reuse its buffer.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QueryResults {
+    ids: Vec<u32>,
+}
+
+impl QueryResults {
+    pub(crate) fn new(ids: Vec<u32>) -> Self {
+        Self { ids }
+    }
+
+    /// Get the ids.
+    pub fn ids(&self) -> &[u32] {
+        &self.ids
+    }
+
+    /// Iterate over the ids.
+    pub fn iter(&self) -> slice::Iter<'_, u32> {
+        self.ids.iter()
+    }
+
+    /// Take the ids out as a vector.
+    pub fn into_vec(self) -> Vec<u32> {
+        self.ids
+    }
+
+    /// Iterate over the rectangles of the found entities as they are stored in a grid, skipping ids it no longer holds.
+    pub fn entities<'a>(&'a self, grid: &'a Grid) -> impl Iterator<Item = Entity> + 'a {
+        self.ids.iter().filter_map(|&id| grid.rect(id))
+    }
+}
+
+impl Deref for QueryResults {
+    type Target = [u32];
+
+    fn deref(&self) -> &[u32] {
+        &self.ids
+    }
+}
+
+impl DerefMut for QueryResults {
+    fn deref_mut(&mut self) -> &mut [u32] {
+        &mut self.ids
+    }
+}
+
+impl IntoIterator for QueryResults {
+    type Item = u32;
+    type IntoIter = vec::IntoIter<u32>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.ids.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a QueryResults {
+    type Item = &'a u32;
+    type IntoIter = slice::Iter<'a, u32>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl From<QueryResults> for Vec<u32> {
+    fn from(value: QueryResults) -> Self {
+        value.ids
+    }
+}
+
+/// Buffers of recycled results. Queries only take `&self`, so the pool sits behind a lock;
+/// a query that finds it busy allocates a new buffer.
+#[derive(Debug, Default)]
+pub(crate) struct ResultPool(Mutex<Vec<Vec<u32>>>);
+
+impl ResultPool {
+    /// Take an empty buffer, reusing a recycled one if there is one.
+    pub(crate) fn take(&self) -> Vec<u32> {
+        self.0
+            .try_lock()
+            .ok()
+            .and_then(|mut buffers| buffers.pop())
+            .unwrap_or_default()
+    }
+
+    /// Keep a buffer for reuse, unless the pool is full or busy.
+    fn put(&self, mut buffer: Vec<u32>) {
+        let Ok(mut buffers) = self.0.try_lock() else {
+            return;
+        };
+        if buffers.len() < POOLED_BUFFERS && buffer.capacity() > 0 {
+            buffer.clear();
+            buffers.push(buffer);
+        }
+    }
+
+    /// Drop every pooled buffer.
+    pub(crate) fn clear(&mut self) {
+        self.0
+            .get_mut()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clear();
+    }
+
+    /// Get the heap memory taken by the pooled buffers, waiting for a running query to take its own.
+    pub(crate) fn bytes_used(&self) -> usize {
+        let buffers = self
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        vec_bytes(&buffers) + buffers.iter().map(vec_bytes).sum::<usize>()
+    }
+}
+
+// pooled buffers hold no results, so a clone starts with none.
+impl Clone for ResultPool {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl Grid {
+    /// Give the buffer of finished query results back to the grid, so a later query can fill it without allocating.
+    /// Takes `&self` so readers sharing the grid can recycle too.
+    pub fn recycle(&self, results: QueryResults) {
+        self.results.put(results.ids);
+    }
+}
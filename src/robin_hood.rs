@@ -0,0 +1,193 @@
+//! Open-addressing alternative to `Table` using Robin Hood probing.
+
+use crate::{hash_u64, mix, vector_hash};
+
+/// A hash map with the same keys as `Table`, but which stores its keys so distinct keys never share an entry.
+/// Collisions are resolved by linear probing where entries far from their home slot displace closer ones, keeping
+/// probe lengths short at high load. Deletions shift the following entries back instead of leaving tombstones.
+/// The table grows once it is seven eighths full, so it needs far fewer slots than `Table` for the same keys.
+/// 2D and scalar keys share one key space, so a table should only be used with one kind of key.
+#[derive(Debug, Clone)]
+pub struct RobinHoodTable<T> {
+    entries: Vec<Option<(u64, T)>>,
+    len: usize,
+}
+
+impl<T> RobinHoodTable<T> {
+    /// Create a new table with room for `size` entries before it grows.
+    pub fn new(size: usize) -> Self {
+        let slots = (size.max(1) * 8 / 7 + 1).next_power_of_two();
+        Self {
+            entries: (0..slots).map(|_| None).collect(),
+            len: 0,
+        }
+    }
+
+    /// Get the number of stored entries.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Check whether the table is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Get the number of slots.
+    pub fn count(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[inline(always)]
+    fn mask(&self) -> usize {
+        self.entries.len() - 1
+    }
+
+    #[inline(always)]
+    fn home(&self, key: u64) -> usize {
+        mix(key) as usize & self.mask()
+    }
+
+    /// Get how far the entry in a slot is from its home slot.
+    #[inline(always)]
+    fn distance(&self, slot: usize, key: u64) -> usize {
+        slot.wrapping_sub(self.home(key)) & self.mask()
+    }
+
+    fn find(&self, key: u64) -> Option<usize> {
+        let mut slot = self.home(key);
+        for distance in 0..self.entries.len() {
+            match &self.entries[slot] {
+                Some((k, _)) if *k == key => return Some(slot),
+                // any entry of ours would have displaced one closer to its home.
+                Some((k, _)) if self.distance(slot, *k) >= distance => {}
+                _ => return None,
+            }
+            slot = (slot + 1) & self.mask();
+        }
+        None
+    }
+
+    fn get(&self, key: u64) -> Option<&T> {
+        let slot = self.find(key)?;
+        self.entries[slot].as_ref().map(|(_, value)| value)
+    }
+
+    fn get_mut(&mut self, key: u64) -> Option<&mut T> {
+        let slot = self.find(key)?;
+        self.entries[slot].as_mut().map(|(_, value)| value)
+    }
+
+    fn insert(&mut self, key: u64, value: T) -> Option<T> {
+        if let Some(slot) = self.find(key) {
+            let (_, old) = self.entries[slot].replace((key, value))?;
+            return Some(old);
+        }
+        if (self.len + 1) * 8 > self.entries.len() * 7 {
+            self.grow();
+        }
+        self.len += 1;
+        self.place((key, value));
+        None
+    }
+
+    /// Place an entry known to be missing, swapping it with any entry closer to its home slot.
+    fn place(&mut self, mut entry: (u64, T)) {
+        let mask = self.mask();
+        let mut slot = self.home(entry.0);
+        let mut distance = 0;
+        loop {
+            match &mut self.entries[slot] {
+                None => {
+                    self.entries[slot] = Some(entry);
+                    return;
+                }
+                Some(resident) => {
+                    let resident_distance = slot.wrapping_sub(mix(resident.0) as usize) & mask;
+                    if resident_distance < distance {
+                        std::mem::swap(resident, &mut entry);
+                        distance = resident_distance;
+                    }
+                }
+            }
+            slot = (slot + 1) & mask;
+            distance += 1;
+        }
+    }
+
+    /// Double the table and re-place every entry.
+    fn grow(&mut self) {
+        let size = self.entries.len() * 2;
+        let old = std::mem::replace(&mut self.entries, (0..size).map(|_| None).collect());
+        for entry in old.into_iter().flatten() {
+            self.place(entry);
+        }
+    }
+
+    fn remove(&mut self, key: u64) -> Option<T> {
+        let mut slot = self.find(key)?;
+        let (_, value) = self.entries[slot].take()?;
+        self.len -= 1;
+        // shift the run after the hole back by one until an empty slot or an entry already at home.
+        loop {
+            let next = (slot + 1) & self.mask();
+            match &self.entries[next] {
+                Some((k, _)) if self.distance(next, *k) > 0 => {
+                    self.entries[slot] = self.entries[next].take();
+                    slot = next;
+                }
+                _ => return Some(value),
+            }
+        }
+    }
+
+    /// Get a reference to the value of a 2D key.
+    #[inline(always)]
+    pub fn get_vector(&self, x: u32, y: u32) -> Option<&T> {
+        self.get(vector_hash(x, y))
+    }
+
+    /// Get a mutable reference to the value of a 2D key.
+    #[inline(always)]
+    pub fn get_vector_mut(&mut self, x: u32, y: u32) -> Option<&mut T> {
+        self.get_mut(vector_hash(x, y))
+    }
+
+    /// Get a reference to the value of a scalar key.
+    #[inline(always)]
+    pub fn get_scalar(&self, s: u32) -> Option<&T> {
+        self.get(hash_u64(s as u64))
+    }
+
+    /// Get a mutable reference to the value of a scalar key.
+    #[inline(always)]
+    pub fn get_scalar_mut(&mut self, s: u32) -> Option<&mut T> {
+        self.get_mut(hash_u64(s as u64))
+    }
+
+    /// Set the value of a 2D key, returning the previous value.
+    pub fn insert_vector(&mut self, x: u32, y: u32, value: T) -> Option<T> {
+        self.insert(vector_hash(x, y), value)
+    }
+
+    /// Set the value of a scalar key, returning the previous value.
+    pub fn insert_scalar(&mut self, s: u32, value: T) -> Option<T> {
+        self.insert(hash_u64(s as u64), value)
+    }
+
+    /// Remove a 2D key, returning its value.
+    pub fn remove_vector(&mut self, x: u32, y: u32) -> Option<T> {
+        self.remove(vector_hash(x, y))
+    }
+
+    /// Remove a scalar key, returning its value.
+    pub fn remove_scalar(&mut self, s: u32) -> Option<T> {
+        self.remove(hash_u64(s as u64))
+    }
+
+    /// Remove every entry, keeping the allocated slots.
+    pub fn clear(&mut self) {
+        self.entries.iter_mut().for_each(|entry| *entry = None);
+        self.len = 0;
+    }
+}
@@ -0,0 +1,72 @@
+//! Random sampling of entities within a region.
+
+use std::collections::HashSet;
+
+use rand::Rng;
+
+use crate::{Grid, Query};
+
+impl Grid {
+    /// Pick up to `k` entities uniformly at random from a region, without building the full result list.
+    /// Uses reservoir sampling over the cells of the region.
+    pub fn sample_region(&self, query: &Query, k: usize, rng: &mut impl Rng) -> Vec<u32> {
+        let sx = query.x >> self.shift;
+        let sy = query.y >> self.shift;
+
+        let ex = query.x.saturating_add(query.width) >> self.shift;
+        let ey = query.y.saturating_add(query.height) >> self.shift;
+
+        let is_ideal = sx == ex && sy == ey;
+        let mut reservoir = Vec::with_capacity(k);
+        let mut seen = HashSet::new();
+        let mut count = 0;
+        if k == 0 {
+            return reservoir;
+        }
+
+        let layers = if self.has_statics {
+            &[&self.grid, &self.statics][..]
+        } else {
+            &[&self.grid][..]
+        };
+        for buckets in layers {
+            for y in sy..=ey {
+                for x in sx..=ex {
+                    let slot = buckets.slot(x, y);
+                    if buckets.is_empty(slot) {
+                        continue;
+                    }
+                    for id in buckets.iter(slot) {
+                        // entities spanning several cells must only be counted once.
+                        if id & (1 << 31) == 0 && !is_ideal && !seen.insert(*id) {
+                            continue;
+                        }
+
+                        let id = id & !(1 << 31);
+                        if count < k {
+                            reservoir.push(id);
+                        } else {
+                            let j = rng.gen_range(0..=count);
+                            if j < k {
+                                reservoir[j] = id;
+                            }
+                        }
+                        count += 1;
+                    }
+                }
+            }
+        }
+        for id in self.large_in(sx, sy, ex, ey) {
+            if count < k {
+                reservoir.push(id);
+            } else {
+                let j = rng.gen_range(0..=count);
+                if j < k {
+                    reservoir[j] = id;
+                }
+            }
+            count += 1;
+        }
+        reservoir
+    }
+}
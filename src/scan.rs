@@ -0,0 +1,39 @@
+//! Bucket scanning. With the `simd` feature on x86_64, ids are compared eight at a time using SSE2.
+
+/// Find the first id in `ids` equal to `id` once the reserved high bit is masked off.
+#[cfg(not(all(feature = "simd", not(feature = "safe"), target_arch = "x86_64")))]
+#[inline(always)]
+pub(crate) fn position(ids: &[u32], id: u32) -> Option<usize> {
+    ids.iter().position(|x| (*x & !(1 << 31)) == id)
+}
+
+/// Find the first id in `ids` equal to `id` once the reserved high bit is masked off.
+#[cfg(all(feature = "simd", not(feature = "safe"), target_arch = "x86_64"))]
+#[inline(always)]
+pub(crate) fn position(ids: &[u32], id: u32) -> Option<usize> {
+    use std::arch::x86_64::*;
+
+    let mut i = 0;
+    // SSE2 is part of the x86_64 baseline, so no runtime detection is needed.
+    unsafe {
+        let mask = _mm_set1_epi32(0x7fff_ffff);
+        let needle = _mm_set1_epi32(id as i32);
+        while i + 8 <= ids.len() {
+            let a = _mm_loadu_si128(ids.as_ptr().add(i) as *const __m128i);
+            let b = _mm_loadu_si128(ids.as_ptr().add(i + 4) as *const __m128i);
+            let a = _mm_cmpeq_epi32(_mm_and_si128(a, mask), needle);
+            let b = _mm_cmpeq_epi32(_mm_and_si128(b, mask), needle);
+            let bits =
+                _mm_movemask_ps(_mm_castsi128_ps(a)) | (_mm_movemask_ps(_mm_castsi128_ps(b)) << 4);
+            if bits != 0 {
+                return Some(i + bits.trailing_zeros() as usize);
+            }
+            i += 8;
+        }
+    }
+
+    ids[i..]
+        .iter()
+        .position(|x| (*x & !(1 << 31)) == id)
+        .map(|p| p + i)
+}
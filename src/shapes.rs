@@ -0,0 +1,508 @@
+//! Non-rectangular entities, stored out-of-line and tested exactly by `Grid::query_exact`.
+
+use crate::{overlaps, Entity, Error, Grid, Layer, Query, QueryResults};
+
+/// The exact shape of an entity. The grid registers the entity in the cells the shape touches.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Shape {
+    /// A circle around a center point.
+    Circle { cx: f32, cy: f32, radius: f32 },
+    /// The points within `radius` of the segment from `(ax, ay)` to `(bx, by)`.
+    Capsule {
+        ax: f32,
+        ay: f32,
+        bx: f32,
+        by: f32,
+        radius: f32,
+    },
+    /// A rectangle rotated by `angle` radians around its center. It covers every cell of its bounding box.
+    Obb {
+        cx: f32,
+        cy: f32,
+        half_width: f32,
+        half_height: f32,
+        angle: f32,
+    },
+    /// A segment from `(x0, y0)` to `(x1, y1)`, such as a thin wall.
+    Segment { x0: f32, y0: f32, x1: f32, y1: f32 },
+    /// A convex polygon given by its vertices in either winding order. It covers every cell of its bounding box.
+    Polygon(Vec<(f32, f32)>),
+}
+
+/// A narrow-phase test deciding whether a convex polygon overlaps a query region.
+pub type PolygonTest = fn(&[(f32, f32)], &Query) -> bool;
+
+/// A query region as a continuous box, inclusive of its far edge like the grid's rectangles.
+struct Aabb {
+    min: (f32, f32),
+    max: (f32, f32),
+}
+
+impl Aabb {
+    fn of(query: &Query) -> Self {
+        Self {
+            min: (query.x as f32, query.y as f32),
+            max: (
+                query.x as f32 + query.width as f32,
+                query.y as f32 + query.height as f32,
+            ),
+        }
+    }
+
+    /// The box of a cell, including the far edge that already maps to the next cell.
+    fn cell(x: u32, y: u32, shift: u32) -> Self {
+        let size = (1u64 << shift) as f32;
+        Self {
+            min: (x as f32 * size, y as f32 * size),
+            max: ((x as f32 + 1.0) * size, (y as f32 + 1.0) * size),
+        }
+    }
+
+    /// Get the squared distance from a point to the box, which is zero inside it.
+    fn distance_squared(&self, (x, y): (f32, f32)) -> f32 {
+        let dx = x - x.clamp(self.min.0, self.max.0);
+        let dy = y - y.clamp(self.min.1, self.max.1);
+        dx * dx + dy * dy
+    }
+
+    /// Get the squared distance from a segment to the box, which is zero if they intersect.
+    fn segment_distance_squared(&self, a: (f32, f32), b: (f32, f32)) -> f32 {
+        if self.clips(a, b) {
+            return 0.0;
+        }
+        // a separate segment and box are closest at an endpoint of one or a corner of the other.
+        let corners = [
+            self.min,
+            (self.max.0, self.min.1),
+            (self.min.0, self.max.1),
+            self.max,
+        ];
+        corners
+            .iter()
+            .map(|&corner| point_segment_distance_squared(corner, a, b))
+            .chain([self.distance_squared(a), self.distance_squared(b)])
+            .fold(f32::INFINITY, f32::min)
+    }
+
+    /// Check whether a segment passes through the box by clipping it against both slabs.
+    fn clips(&self, a: (f32, f32), b: (f32, f32)) -> bool {
+        let (mut enter, mut exit) = (0.0f32, 1.0f32);
+        for (start, delta, min, max) in [
+            (a.0, b.0 - a.0, self.min.0, self.max.0),
+            (a.1, b.1 - a.1, self.min.1, self.max.1),
+        ] {
+            if delta == 0.0 {
+                if start < min || start > max {
+                    return false;
+                }
+                continue;
+            }
+            let (t0, t1) = ((min - start) / delta, (max - start) / delta);
+            enter = enter.max(t0.min(t1));
+            exit = exit.min(t0.max(t1));
+        }
+        enter <= exit
+    }
+
+    fn center(&self) -> (f32, f32) {
+        (
+            (self.min.0 + self.max.0) / 2.0,
+            (self.min.1 + self.max.1) / 2.0,
+        )
+    }
+
+    fn half_extents(&self) -> (f32, f32) {
+        (
+            (self.max.0 - self.min.0) / 2.0,
+            (self.max.1 - self.min.1) / 2.0,
+        )
+    }
+}
+
+impl Shape {
+    /// Get the bounding box of the shape as `(min_x, min_y, max_x, max_y)`.
+    pub fn bounds(&self) -> (f32, f32, f32, f32) {
+        match *self {
+            Shape::Circle { cx, cy, radius } => {
+                (cx - radius, cy - radius, cx + radius, cy + radius)
+            }
+            Shape::Capsule {
+                ax,
+                ay,
+                bx,
+                by,
+                radius,
+            } => (
+                ax.min(bx) - radius,
+                ay.min(by) - radius,
+                ax.max(bx) + radius,
+                ay.max(by) + radius,
+            ),
+            Shape::Obb {
+                cx,
+                cy,
+                half_width,
+                half_height,
+                angle,
+            } => {
+                let (sin, cos) = angle.sin_cos();
+                let ex = (cos * half_width).abs() + (sin * half_height).abs();
+                let ey = (sin * half_width).abs() + (cos * half_height).abs();
+                (cx - ex, cy - ey, cx + ex, cy + ey)
+            }
+            Shape::Segment { x0, y0, x1, y1 } => (x0.min(x1), y0.min(y1), x0.max(x1), y0.max(y1)),
+            Shape::Polygon(ref points) => points.iter().fold(
+                (
+                    f32::INFINITY,
+                    f32::INFINITY,
+                    f32::NEG_INFINITY,
+                    f32::NEG_INFINITY,
+                ),
+                |(min_x, min_y, max_x, max_y), &(x, y)| {
+                    (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y))
+                },
+            ),
+        }
+    }
+
+    /// Check that every coordinate and size is finite, radii and half extents are not negative, a polygon has a vertex,
+    /// and the bounding box lies within the `u32` coordinate space.
+    pub fn is_valid(&self) -> bool {
+        let (finite, sizes) = match *self {
+            Shape::Circle { cx, cy, radius } => (
+                [cx, cy, radius].iter().all(|v| v.is_finite()),
+                radius >= 0.0,
+            ),
+            Shape::Capsule {
+                ax,
+                ay,
+                bx,
+                by,
+                radius,
+            } => (
+                [ax, ay, bx, by, radius].iter().all(|v| v.is_finite()),
+                radius >= 0.0,
+            ),
+            Shape::Obb {
+                cx,
+                cy,
+                half_width,
+                half_height,
+                angle,
+            } => (
+                [cx, cy, half_width, half_height, angle]
+                    .iter()
+                    .all(|v| v.is_finite()),
+                half_width >= 0.0 && half_height >= 0.0,
+            ),
+            Shape::Segment { x0, y0, x1, y1 } => {
+                ([x0, y0, x1, y1].iter().all(|v| v.is_finite()), true)
+            }
+            Shape::Polygon(ref points) => (
+                points.iter().all(|(x, y)| x.is_finite() && y.is_finite()),
+                !points.is_empty(),
+            ),
+        };
+        if !finite || !sizes {
+            return false;
+        }
+        let (min_x, min_y, max_x, max_y) = self.bounds();
+        let limit = u32::MAX as f32;
+        min_x >= 0.0 && min_y >= 0.0 && max_x <= limit && max_y <= limit
+    }
+
+    /// Get the smallest entity rectangle containing the shape, cut off at the edges of the coordinate space.
+    pub fn bounding_entity(&self, id: u32) -> Entity {
+        let (min_x, min_y, max_x, max_y) = self.bounds();
+        let x = min_x.floor().clamp(0.0, u32::MAX as f32) as u32;
+        let y = min_y.floor().clamp(0.0, u32::MAX as f32) as u32;
+        let right = max_x.ceil().clamp(0.0, u32::MAX as f32) as u32;
+        let bottom = max_y.ceil().clamp(0.0, u32::MAX as f32) as u32;
+        Entity {
+            id,
+            x,
+            y,
+            width: right.saturating_sub(x),
+            height: bottom.saturating_sub(y),
+        }
+    }
+
+    /// Check whether the shape overlaps a query region.
+    pub fn overlaps(&self, query: &Query) -> bool {
+        self.overlaps_box(&Aabb::of(query))
+    }
+
+    /// Check whether the shape must be registered in a cell.
+    fn touches_cell(&self, x: u32, y: u32, shift: u32) -> bool {
+        match self {
+            Shape::Obb { .. } | Shape::Polygon(_) => true,
+            _ => self.overlaps_box(&Aabb::cell(x, y, shift)),
+        }
+    }
+
+    fn overlaps_box(&self, aabb: &Aabb) -> bool {
+        match *self {
+            Shape::Circle { cx, cy, radius } => aabb.distance_squared((cx, cy)) <= radius * radius,
+            Shape::Capsule {
+                ax,
+                ay,
+                bx,
+                by,
+                radius,
+            } => aabb.segment_distance_squared((ax, ay), (bx, by)) <= radius * radius,
+            Shape::Obb {
+                cx,
+                cy,
+                half_width,
+                half_height,
+                angle,
+            } => {
+                // separating axis test on the box's two axes and the query's two axes.
+                let (min_x, min_y, max_x, max_y) = self.bounds();
+                if max_x < aabb.min.0
+                    || min_x > aabb.max.0
+                    || max_y < aabb.min.1
+                    || min_y > aabb.max.1
+                {
+                    return false;
+                }
+                let (sin, cos) = angle.sin_cos();
+                let (qx, qy) = aabb.center();
+                let (hx, hy) = aabb.half_extents();
+                let (dx, dy) = (qx - cx, qy - cy);
+                for (axis, half) in [((cos, sin), half_width), ((-sin, cos), half_height)] {
+                    let distance = (dx * axis.0 + dy * axis.1).abs();
+                    let reach = half + hx * axis.0.abs() + hy * axis.1.abs();
+                    if distance > reach {
+                        return false;
+                    }
+                }
+                true
+            }
+            Shape::Segment { x0, y0, x1, y1 } => aabb.clips((x0, y0), (x1, y1)),
+            Shape::Polygon(ref points) => polygon_overlaps(points, aabb),
+        }
+    }
+}
+
+/// Separating axis test between a convex polygon and a box.
+fn polygon_overlaps(points: &[(f32, f32)], aabb: &Aabb) -> bool {
+    if points.is_empty() {
+        return false;
+    }
+    let corners = [
+        aabb.min,
+        (aabb.max.0, aabb.min.1),
+        (aabb.min.0, aabb.max.1),
+        aabb.max,
+    ];
+    let edges = points
+        .iter()
+        .zip(points.iter().cycle().skip(1))
+        .map(|(a, b)| (a.1 - b.1, b.0 - a.0));
+    [(1.0, 0.0), (0.0, 1.0)]
+        .into_iter()
+        .chain(edges)
+        .all(|axis| {
+            let project = |&(x, y): &(f32, f32)| x * axis.0 + y * axis.1;
+            let (min, max) = points
+                .iter()
+                .map(project)
+                .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), p| {
+                    (min.min(p), max.max(p))
+                });
+            let (box_min, box_max) = corners
+                .iter()
+                .map(project)
+                .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), p| {
+                    (min.min(p), max.max(p))
+                });
+            min <= box_max && box_min <= max
+        })
+}
+
+/// Get the squared distance from a point to a segment.
+fn point_segment_distance_squared(p: (f32, f32), a: (f32, f32), b: (f32, f32)) -> f32 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let length = dx * dx + dy * dy;
+    let t = if length > 0.0 {
+        (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / length).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let (x, y) = (a.0 + t * dx - p.0, a.1 + t * dy - p.1);
+    x * x + y * y
+}
+
+impl Grid {
+    /// Insert an entity with an exact shape into the dynamic layer, registering it in the cells the shape touches.
+    /// Its rectangle becomes the bounding box. Updating it with a plain rectangle, or rolling it back, drops the shape.
+    /// Shapes failing `Shape::is_valid` are rejected with `Error::InvalidShape`.
+    /// Fails with `Error::TooManyCells`, leaving the grid untouched, if the shape touches more than `FIXED_SIZE` cells,
+    /// unless its bounding box goes to the large list or the `spill-cells` feature is enabled.
+    pub fn insert_shape(&mut self, id: u32, shape: Shape) -> Result<(), Error> {
+        if !shape.is_valid() {
+            return Err(Error::InvalidShape(id));
+        }
+        let entity = shape.bounding_entity(id);
+        let sx = entity.x >> self.shift;
+        let sy = entity.y >> self.shift;
+
+        let ex = entity.x.saturating_add(entity.width) >> self.shift;
+        let ey = entity.y.saturating_add(entity.height) >> self.shift;
+
+        let shift = self.shift;
+        let result = self.insert_cells_where(&entity, sx, sy, ex, ey, Layer::Dynamic, |x, y| {
+            shape.touches_cell(x, y, shift)
+        });
+        // an insert that failed part way still registers the entity in the cells written so far.
+        let map = self.maps.get_scalar(id);
+        if !result.as_ref().is_err_and(Error::is_rejection) && map.is_registered() && map.id == id {
+            self.shapes.insert(id, shape);
+        }
+        result
+    }
+
+    /// Insert a circle into the dynamic layer, registering it only in the cells it touches.
+    pub fn insert_circle(&mut self, id: u32, cx: f32, cy: f32, radius: f32) -> Result<(), Error> {
+        self.insert_shape(id, Shape::Circle { cx, cy, radius })
+    }
+
+    /// Insert a capsule, the segment from `(ax, ay)` to `(bx, by)` widened by `radius`, into the dynamic layer.
+    /// It is registered only in the cells along the capsule.
+    pub fn insert_capsule(
+        &mut self,
+        id: u32,
+        ax: f32,
+        ay: f32,
+        bx: f32,
+        by: f32,
+        radius: f32,
+    ) -> Result<(), Error> {
+        self.insert_shape(
+            id,
+            Shape::Capsule {
+                ax,
+                ay,
+                bx,
+                by,
+                radius,
+            },
+        )
+    }
+
+    /// Insert a segment into the dynamic layer, registering it only in the cells it crosses.
+    /// Walls crossing more than `FIXED_SIZE` cells need a large threshold or the `spill-cells` feature, see `insert_shape`.
+    pub fn insert_segment(
+        &mut self,
+        id: u32,
+        x0: f32,
+        y0: f32,
+        x1: f32,
+        y1: f32,
+    ) -> Result<(), Error> {
+        self.insert_shape(id, Shape::Segment { x0, y0, x1, y1 })
+    }
+
+    /// Insert a convex polygon into the dynamic layer, registering it in the cells its bounding box covers.
+    /// The vertices are stored out-of-line and tested by `query_exact`.
+    pub fn insert_polygon(&mut self, id: u32, points: Vec<(f32, f32)>) -> Result<(), Error> {
+        self.insert_shape(id, Shape::Polygon(points))
+    }
+
+    /// Set the narrow-phase test `query_exact` uses for polygons, or `None` for the built-in separating axis test.
+    pub fn set_polygon_test(&mut self, test: Option<PolygonTest>) {
+        self.polygon_test = test;
+    }
+
+    /// Get the exact shape of an entity, if it was inserted with one.
+    pub fn shape(&self, id: u32) -> Option<&Shape> {
+        self.shapes.get(&id)
+    }
+
+    /// Retrieve entities whose exact shape, or rectangle if they have none, overlaps a region.
+    pub fn query_exact(&self, query: &Query) -> QueryResults {
+        let mut result = self.query(query).into_vec();
+        result.retain(|&id| self.overlaps_exact(id, query));
+        QueryResults::new(result)
+    }
+
+    /// Check whether an entity's exact shape, or rectangle if it has none, overlaps a region.
+    pub(crate) fn overlaps_exact(&self, id: u32, query: &Query) -> bool {
+        match self.shapes.get(&id) {
+            Some(Shape::Polygon(points)) => match self.polygon_test {
+                Some(test) => test(points, query),
+                None => polygon_overlaps(points, &Aabb::of(query)),
+            },
+            Some(shape) => shape.overlaps(query),
+            None => self.rect(id).is_some_and(|entity| overlaps(&entity, query)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Entity, Error, Grid, Query, Shape};
+
+    fn everything() -> Query {
+        Query {
+            x: 0,
+            y: 0,
+            width: 1000,
+            height: 1000,
+        }
+    }
+
+    fn near_square() -> Query {
+        Query {
+            x: 90,
+            y: 90,
+            width: 20,
+            height: 20,
+        }
+    }
+
+    fn square(id: u32) -> Entity {
+        Entity {
+            id,
+            x: 100,
+            y: 100,
+            width: 5,
+            height: 5,
+        }
+    }
+
+    #[test]
+    fn failed_shape_insert_stores_no_shape() {
+        let mut grid = Grid::with_bounds(64, 4, everything());
+        assert_eq!(
+            grid.insert_circle(7, 5000.0, 5000.0, 5.0),
+            Err(Error::OutOfBounds)
+        );
+        assert!(grid.shape(7).is_none());
+        grid.insert(&square(7)).unwrap();
+        assert_eq!(grid.query_exact(&near_square()).into_vec(), [7]);
+    }
+
+    #[test]
+    fn duplicate_shape_insert_keeps_existing_shape() {
+        let mut grid = Grid::new(64, 4);
+        grid.insert_circle(7, 50.0, 50.0, 5.0).unwrap();
+        assert_eq!(
+            grid.insert_circle(7, 80.0, 80.0, 1.0),
+            Err(Error::DuplicateId(7))
+        );
+        assert!(matches!(grid.shape(7), Some(Shape::Circle { radius, .. }) if *radius == 5.0));
+    }
+
+    #[test]
+    fn plain_insert_after_delete_has_no_shape() {
+        let mut grid = Grid::new(64, 4);
+        grid.insert_circle(7, 50.0, 50.0, 5.0).unwrap();
+        grid.delete(7);
+        assert!(grid.shape(7).is_none());
+        grid.insert(&square(7)).unwrap();
+        assert!(grid.shape(7).is_none());
+        assert_eq!(grid.query_exact(&near_square()).into_vec(), [7]);
+    }
+}
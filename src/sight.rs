@@ -0,0 +1,99 @@
+//! Line-of-sight checks walking the cells a segment crosses and testing them for blocking entities.
+
+use crate::{Entity, Grid, LayerMask};
+
+impl Grid {
+    /// Check whether nothing in the layers in `mask` blocks the segment from `(x0, y0)` to `(x1, y1)`. A cell the segment
+    /// crosses is blocking when an entity of those layers occupies it, so a blocker anywhere in the cell hides the view,
+    /// including in the cells of both ends; keep the viewers themselves out of `mask`. See `line_of_sight_exact` for an exact test.
+    pub fn line_of_sight(&self, x0: u32, y0: u32, x1: u32, y1: u32, mask: LayerMask) -> bool {
+        self.walk_segment((x0, y0), (x1, y1), |cell| !self.is_cell_blocked(cell, mask))
+    }
+
+    /// Check line of sight like `line_of_sight`, but only an entity whose rectangle the segment touches blocks it.
+    /// Slower per cell, it does not report blockers that merely share a cell with the segment.
+    pub fn line_of_sight_exact(&self, x0: u32, y0: u32, x1: u32, y1: u32, mask: LayerMask) -> bool {
+        let (a, b) = ((x0, y0), (x1, y1));
+        let accept = |id| {
+            mask.contains(self.layer(id))
+                && self
+                    .rect(id)
+                    .is_some_and(|entity| segment_touches(a, b, &entity))
+        };
+        let mut found = Vec::new();
+        self.walk_segment(a, b, |(x, y)| {
+            self.collect_cells(x, y, x, y, 1, accept, &mut found);
+            found.is_empty()
+        })
+    }
+
+    /// Visit every cell the segment crosses from the first end to the second until `visit` returns false, and return
+    /// whether every visit returned true. Where the segment passes exactly through a corner, both side cells are visited too.
+    fn walk_segment(
+        &self,
+        (x0, y0): (u32, u32),
+        (x1, y1): (u32, u32),
+        mut visit: impl FnMut((u32, u32)) -> bool,
+    ) -> bool {
+        let size = 1u64 << self.shift;
+        let (mut x, mut y) = (x0 >> self.shift, y0 >> self.shift);
+        let (ex, ey) = (x1 >> self.shift, y1 >> self.shift);
+        let (dx, dy) = (x1.abs_diff(x0) as u128, y1.abs_diff(y0) as u128);
+        // distance along each axis to the next cell boundary; the ratio to the axis length orders the crossings exactly.
+        let boundary = |start: u32, cell: u32, forward: bool| -> u128 {
+            if forward {
+                ((cell as u64 + 1) * size - start as u64) as u128
+            } else {
+                (start as u64 - cell as u64 * size) as u128
+            }
+        };
+        let (forward_x, forward_y) = (x1 >= x0, y1 >= y0);
+        let (mut tx, mut ty) = (boundary(x0, x, forward_x), boundary(y0, y, forward_y));
+        let next = |cell: u32, forward: bool| if forward { cell + 1 } else { cell - 1 };
+
+        loop {
+            if !visit((x, y)) {
+                return false;
+            }
+            if (x, y) == (ex, ey) {
+                return true;
+            }
+            let step_x = x != ex && (y == ey || tx * dy <= ty * dx);
+            let step_y = y != ey && (x == ex || ty * dx <= tx * dy);
+            if step_x
+                && step_y
+                && (!visit((next(x, forward_x), y)) || !visit((x, next(y, forward_y))))
+            {
+                return false;
+            }
+            if step_x {
+                x = next(x, forward_x);
+                tx += size as u128;
+            }
+            if step_y {
+                y = next(y, forward_y);
+                ty += size as u128;
+            }
+        }
+    }
+}
+
+/// Check whether a segment touches a rectangle, including its far edge, with exact integer math.
+fn segment_touches(a: (u32, u32), b: (u32, u32), entity: &Entity) -> bool {
+    let (min_x, min_y) = (entity.x as i128, entity.y as i128);
+    let max_x = min_x + entity.width as i128;
+    let max_y = min_y + entity.height as i128;
+    let (ax, ay, bx, by) = (a.0 as i128, a.1 as i128, b.0 as i128, b.1 as i128);
+    if ax.max(bx) < min_x || ax.min(bx) > max_x || ay.max(by) < min_y || ay.min(by) > max_y {
+        return false;
+    }
+    // with overlapping bounds, the segment misses the rectangle only if every corner lies strictly on one side of it.
+    let side = |x: i128, y: i128| ((bx - ax) * (y - ay) - (by - ay) * (x - ax)).signum();
+    let sides = [
+        side(min_x, min_y),
+        side(max_x, min_y),
+        side(min_x, max_y),
+        side(max_x, max_y),
+    ];
+    !(sides.iter().all(|&s| s > 0) || sides.iter().all(|&s| s < 0))
+}
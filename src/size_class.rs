@@ -0,0 +1,132 @@
+//! Several grids of different cell sizes, each holding the entities whose size suits its cells.
+
+use std::collections::HashMap;
+
+use crate::{Entity, Error, Grid, Query};
+
+/// Grids with different shifts behind one interface, like the levels of a hierarchical grid. Each entity is stored in
+/// the grid with the smallest cells it fits in, so it covers at most four cells there; entities too big for every grid
+/// go to the one with the largest cells. Queries visit every grid; each id lives in one grid, so no grid repeats another's results.
+#[derive(Debug, Clone)]
+pub struct SizeClassGrid {
+    grids: Vec<Grid>,
+    /// The index of the grid each entity is stored in.
+    homes: HashMap<u32, usize>,
+}
+
+impl SizeClassGrid {
+    /// Create a grid for each of `shifts`, each with a fixed bucket size. Panics if `shifts` is empty.
+    pub fn new(size: usize, shifts: &[u32]) -> Self {
+        assert!(!shifts.is_empty(), "at least one shift is required");
+        let mut shifts = shifts.to_vec();
+        shifts.sort_unstable();
+        shifts.dedup();
+        Self {
+            grids: shifts
+                .into_iter()
+                .map(|shift| Grid::new(size, shift))
+                .collect(),
+            homes: HashMap::new(),
+        }
+    }
+
+    /// Get the shifts of the grids, smallest first.
+    pub fn shifts(&self) -> Vec<u32> {
+        self.grids.iter().map(Grid::shift).collect()
+    }
+
+    /// Get the shift of the grid an entity is stored in.
+    pub fn shift_of(&self, id: u32) -> Option<u32> {
+        self.homes.get(&id).map(|&home| self.grids[home].shift())
+    }
+
+    /// Get the number of entities.
+    pub fn len(&self) -> usize {
+        self.homes.len()
+    }
+
+    /// Check whether there are no entities.
+    pub fn is_empty(&self) -> bool {
+        self.homes.is_empty()
+    }
+
+    /// Pick the grid with the smallest cells that are at least as big as the entity.
+    fn class(&self, entity: &Entity) -> usize {
+        let extent = entity.width.max(entity.height) as u64;
+        self.grids
+            .iter()
+            .position(|grid| extent <= 1 << grid.shift())
+            .unwrap_or(self.grids.len() - 1)
+    }
+
+    /// Insert an entity into the grid of its size class. Ids already stored in any grid are rejected with `Error::DuplicateId`.
+    pub fn insert(&mut self, entity: &Entity) -> Result<(), Error> {
+        if self.homes.contains_key(&entity.id) {
+            return Err(Error::DuplicateId(entity.id));
+        }
+        let home = self.class(entity);
+        let result = self.grids[home].insert(entity);
+        // a failed insert can still leave the entity in some of its cells, depending on the overflow policy.
+        if self.grids[home].rect(entity.id).is_some() {
+            self.homes.insert(entity.id, home);
+        }
+        result
+    }
+
+    /// Move or resize an entity, moving it to another grid if its size class changed. Unknown entities are inserted.
+    pub fn update(&mut self, entity: &Entity) -> Result<(), Error> {
+        let home = self.class(entity);
+        match self.homes.get(&entity.id) {
+            Some(&old) if old == home => self.grids[home].update(entity),
+            Some(&old) => {
+                // the new placement is inserted first, so a rejected one leaves the entity where it was.
+                let result = self.grids[home].insert(entity);
+                if self.grids[home].rect(entity.id).is_some() {
+                    self.grids[old].delete(entity.id);
+                    self.homes.insert(entity.id, home);
+                }
+                result
+            }
+            None => self.insert(entity),
+        }
+    }
+
+    /// Delete an entity by ID. Unknown ids are ignored.
+    pub fn delete(&mut self, id: u32) {
+        if let Some(home) = self.homes.remove(&id) {
+            self.grids[home].delete(id);
+        }
+    }
+
+    /// Delete an entity by ID, failing with `Error::NotFound` if it is not stored.
+    pub fn try_delete(&mut self, id: u32) -> Result<(), Error> {
+        let home = self.homes.remove(&id).ok_or(Error::NotFound(id))?;
+        self.grids[home].try_delete(id)
+    }
+
+    /// Retrieve entities in a region from every grid, in no particular order.
+    pub fn query(&self, query: &Query) -> Vec<u32> {
+        let mut result = Vec::new();
+        for grid in &self.grids {
+            result.extend(grid.query(query));
+        }
+        result
+    }
+
+    /// Retrieve entities in the cells containing a point in every grid.
+    pub fn query_point(&self, x: u32, y: u32) -> Vec<u32> {
+        let mut result = Vec::new();
+        for grid in &self.grids {
+            result.extend(grid.query_point(x, y));
+        }
+        result
+    }
+
+    /// Remove every entity.
+    pub fn clear(&mut self) {
+        for grid in &mut self.grids {
+            grid.clear();
+        }
+        self.homes.clear();
+    }
+}
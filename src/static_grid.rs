@@ -0,0 +1,176 @@
+//! Fixed-capacity grid that never allocates.
+
+use crate::{mix, scan, vector_hash, Entity, Error, Query};
+
+/// A spatial hash grid whose storage is entirely inline: `CELLS` slots of `BUCKET` ids each.
+/// It never allocates, so it suits embedded targets tracking a bounded set of obstacles or zones.
+/// There is no per-entity bookkeeping, so deletes and updates take the rectangle the entity was inserted with.
+/// The whole table lives in the struct, so large instances belong in a `static` rather than on the stack.
+#[derive(Debug, Clone)]
+pub struct StaticGrid<const CELLS: usize, const BUCKET: usize> {
+    lens: [u16; CELLS],
+    ids: [[u32; BUCKET]; CELLS],
+    shift: u32,
+}
+
+impl<const CELLS: usize, const BUCKET: usize> StaticGrid<CELLS, BUCKET> {
+    /// Create an empty grid with the given bitshift cell size.
+    pub const fn new(shift: u32) -> Self {
+        assert!(CELLS > 0, "a static grid needs at least one cell");
+        assert!(
+            BUCKET <= u16::MAX as usize,
+            "bucket capacity must fit in a u16"
+        );
+        Self {
+            lens: [0; CELLS],
+            ids: [[0; BUCKET]; CELLS],
+            shift,
+        }
+    }
+
+    /// Get the bitshift cell size.
+    pub fn shift(&self) -> u32 {
+        self.shift
+    }
+
+    #[inline(always)]
+    fn slot(&self, x: u32, y: u32) -> usize {
+        // the table size is arbitrary, so the key is mixed to keep rows from sharing slots.
+        (mix(vector_hash(x, y)) % CELLS as u64) as usize
+    }
+
+    fn cells(
+        &self,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<(u32, u32, u32, u32), Error> {
+        let right = x.checked_add(width).ok_or(Error::CoordinateOverflow)?;
+        let bottom = y.checked_add(height).ok_or(Error::CoordinateOverflow)?;
+        Ok((
+            x >> self.shift,
+            y >> self.shift,
+            right >> self.shift,
+            bottom >> self.shift,
+        ))
+    }
+
+    /// Insert an entity. If a cell is full, the cells filled so far are rolled back and `Error::CellFull` is returned.
+    pub fn insert(&mut self, entity: &Entity) -> Result<(), Error> {
+        if entity.id & (1 << 31) != 0 {
+            return Err(Error::InvalidId(entity.id));
+        }
+        let (sx, sy, ex, ey) = self.cells(entity.x, entity.y, entity.width, entity.height)?;
+        let tagged = entity.id | (((sx == ex && sy == ey) as u32) << 31);
+
+        for y in sy..=ey {
+            for x in sx..=ex {
+                let slot = self.slot(x, y);
+                let len = self.lens[slot] as usize;
+                if len == BUCKET {
+                    self.remove_cells(entity.id, sx, sy, ex, ey, Some((x, y)));
+                    return Err(Error::CellFull {
+                        cell: (x, y),
+                        id: entity.id,
+                        written: (y - sy) as usize * (ex - sx + 1) as usize + (x - sx) as usize,
+                        rect: (entity.x, entity.y, entity.width, entity.height),
+                    });
+                }
+                self.ids[slot][len] = tagged;
+                self.lens[slot] += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove an entity from the cells covered by the rectangle it was inserted with.
+    /// Returns `Error::NotFound` if it was missing from any of them.
+    pub fn delete(&mut self, entity: &Entity) -> Result<(), Error> {
+        let (sx, sy, ex, ey) = self.cells(entity.x, entity.y, entity.width, entity.height)?;
+        if self.remove_cells(entity.id, sx, sy, ex, ey, None) {
+            Ok(())
+        } else {
+            Err(Error::NotFound(entity.id))
+        }
+    }
+
+    /// Move an entity from its `old` rectangle to `new`. If it no longer fits, it is left out of the grid.
+    pub fn update(&mut self, old: &Entity, new: &Entity) -> Result<(), Error> {
+        self.delete(old)?;
+        self.insert(new)
+    }
+
+    /// Remove an id from a range of cells, stopping before `until` if given. Returns whether every cell held it.
+    fn remove_cells(
+        &mut self,
+        id: u32,
+        sx: u32,
+        sy: u32,
+        ex: u32,
+        ey: u32,
+        until: Option<(u32, u32)>,
+    ) -> bool {
+        let mut found = true;
+        for y in sy..=ey {
+            for x in sx..=ex {
+                if until == Some((x, y)) {
+                    return found;
+                }
+                let slot = self.slot(x, y);
+                let len = self.lens[slot] as usize;
+                match scan::position(&self.ids[slot][..len], id) {
+                    Some(index) => {
+                        self.ids[slot].copy_within(index + 1..len, index);
+                        self.lens[slot] -= 1;
+                    }
+                    None => found = false,
+                }
+            }
+        }
+        found
+    }
+
+    /// Write the unique ids in a region into `out`, returning how many were written.
+    /// The traversal stops once `out` is full.
+    pub fn query(&self, query: &Query, out: &mut [u32]) -> Result<usize, Error> {
+        let (sx, sy, ex, ey) = self.cells(query.x, query.y, query.width, query.height)?;
+        let is_ideal = sx == ex && sy == ey;
+        let mut count = 0;
+
+        for y in sy..=ey {
+            for x in sx..=ex {
+                let slot = self.slot(x, y);
+                for &id in &self.ids[slot][..self.lens[slot] as usize] {
+                    if count == out.len() {
+                        return Ok(count);
+                    }
+                    if id & (1 << 31) != 0 || is_ideal {
+                        out[count] = id & !(1 << 31);
+                        count += 1;
+                    } else if scan::position(&out[..count], id).is_none() {
+                        out[count] = id;
+                        count += 1;
+                    }
+                }
+            }
+        }
+        Ok(count)
+    }
+
+    /// Write the ids in the cell containing a point into `out`, returning how many were written.
+    pub fn query_point(&self, x: u32, y: u32, out: &mut [u32]) -> usize {
+        let slot = self.slot(x >> self.shift, y >> self.shift);
+        let ids = &self.ids[slot][..self.lens[slot] as usize];
+        let count = ids.len().min(out.len());
+        for (out, id) in out.iter_mut().zip(ids) {
+            *out = id & !(1 << 31);
+        }
+        count
+    }
+
+    /// Remove every entity.
+    pub fn clear(&mut self) {
+        self.lens = [0; CELLS];
+    }
+}
@@ -0,0 +1,101 @@
+//! Point-pattern statistics over the entities in a region, such as for ecology simulations. Each entity counts as the
+//! point at its center.
+
+use crate::{Grid, Query};
+
+impl Grid {
+    /// Split a region into `columns` by `rows` equal quadrats and count the entities centered in each, in row order.
+    /// Panics if `columns` or `rows` is zero.
+    pub fn quadrat_counts(&self, region: &Query, columns: u32, rows: u32) -> Vec<usize> {
+        assert!(columns > 0 && rows > 0, "no quadrats");
+        let mut counts = vec![0; columns as usize * rows as usize];
+        // the region includes its far edge, so it spans one unit more than its size.
+        let (span_x, span_y) = (region.width as u64 + 1, region.height as u64 + 1);
+        for (x, y) in self.centers_in(region) {
+            let column = (x - region.x) as u64 * columns as u64 / span_x;
+            let row = (y - region.y) as u64 * rows as u64 / span_y;
+            counts[row as usize * columns as usize + column as usize] += 1;
+        }
+        counts
+    }
+
+    /// Compute Morisita's index of dispersion over the quadrats of `quadrat_counts`: about 1 for randomly placed entities,
+    /// above 1 for clumped and below 1 for evenly spaced ones. Returns `None` for fewer than two entities.
+    pub fn morisita_index(&self, region: &Query, columns: u32, rows: u32) -> Option<f64> {
+        let counts = self.quadrat_counts(region, columns, rows);
+        let total: f64 = counts.iter().map(|&n| n as f64).sum();
+        if total < 2.0 {
+            return None;
+        }
+        let pairs: f64 = counts.iter().map(|&n| n as f64 * (n as f64 - 1.0)).sum();
+        Some(counts.len() as f64 * pairs / (total * (total - 1.0)))
+    }
+
+    /// Get the distance from each entity centered in a region to the nearest other one, in no particular order.
+    /// Entities outside the region are not considered neighbors, so distances near its edges come out longer.
+    pub fn nearest_neighbor_distances(&self, region: &Query) -> Vec<f64> {
+        let mut points = self.centers_in(region);
+        if points.len() < 2 {
+            return Vec::new();
+        }
+        points.sort_unstable();
+        // walk outwards from each point in x order, stopping once the x distance alone exceeds the nearest found.
+        (0..points.len())
+            .map(|i| {
+                let (x, y) = points[i];
+                let mut best = u64::MAX;
+                let mut visit = |(ox, oy): (u32, u32)| {
+                    let dx = x.abs_diff(ox) as u64;
+                    if dx * dx > best {
+                        return false;
+                    }
+                    let dy = y.abs_diff(oy) as u64;
+                    best = best.min(dx * dx + dy * dy);
+                    true
+                };
+                for &point in points[i + 1..].iter() {
+                    if !visit(point) {
+                        break;
+                    }
+                }
+                for &point in points[..i].iter().rev() {
+                    if !visit(point) {
+                        break;
+                    }
+                }
+                (best as f64).sqrt()
+            })
+            .collect()
+    }
+
+    /// Compute the Clark-Evans ratio: the mean nearest-neighbor distance over the one expected for as many randomly placed
+    /// entities. It is about 1 for random, below 1 for clumped and up to about 2.15 for evenly spaced entities.
+    /// Returns `None` for fewer than two entities.
+    pub fn clark_evans_ratio(&self, region: &Query) -> Option<f64> {
+        let distances = self.nearest_neighbor_distances(region);
+        if distances.is_empty() {
+            return None;
+        }
+        let area = (region.width as f64 + 1.0) * (region.height as f64 + 1.0);
+        let mean = distances.iter().sum::<f64>() / distances.len() as f64;
+        let expected = 0.5 / (distances.len() as f64 / area).sqrt();
+        Some(mean / expected)
+    }
+
+    /// Get the centers of the unique entities centered in a region.
+    fn centers_in(&self, region: &Query) -> Vec<(u32, u32)> {
+        let inside = |point: u32, start: u32, size: u32| {
+            start <= point && point as u64 <= start as u64 + size as u64
+        };
+        let mut ids = self.query(region).into_vec();
+        ids.sort_unstable();
+        ids.dedup();
+        ids.into_iter()
+            .filter_map(|id| self.rect(id))
+            .map(|entity| entity.center())
+            .filter(|&(x, y)| {
+                inside(x, region.x, region.width) && inside(y, region.y, region.height)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,44 @@
+//! Steering vectors computed from the neighbors stored in the grid, such as to keep crowds of agents apart.
+
+use crate::{Grid, Query};
+
+impl Grid {
+    /// Sum the directions pointing away from each entity whose center lies within `radius` of an entity's center, weighted
+    /// from 1 for neighbors at the same spot down to 0 at `radius`, so nearer neighbors push harder. Neighbors are visited
+    /// while traversing the cells, without collecting them. Neighbors centered exactly on the entity have no direction and
+    /// are skipped. Returns a zero vector if the entity is not registered.
+    pub fn separation(&self, id: u32, radius: u32) -> (f32, f32) {
+        let entity = match self.rect(id) {
+            Some(entity) => entity,
+            None => return (0.0, 0.0),
+        };
+        let center = |x: u32, y: u32, width: u32, height: u32| {
+            (
+                x as f64 + width as f64 / 2.0,
+                y as f64 + height as f64 / 2.0,
+            )
+        };
+        let (cx, cy) = center(entity.x, entity.y, entity.width, entity.height);
+        let region = Query::around_point(cx as u32, cy as u32, radius.saturating_add(1));
+
+        let (mut push_x, mut push_y) = (0.0, 0.0);
+        self.visit_unique(&region, |other| {
+            if other == id {
+                return;
+            }
+            let Some(neighbor) = self.rect(other) else {
+                return;
+            };
+            let (ox, oy) = center(neighbor.x, neighbor.y, neighbor.width, neighbor.height);
+            let (dx, dy) = (cx - ox, cy - oy);
+            let distance = (dx * dx + dy * dy).sqrt();
+            if distance == 0.0 || distance >= radius as f64 {
+                return;
+            }
+            let weight = 1.0 - distance / radius as f64;
+            push_x += dx / distance * weight;
+            push_y += dy / distance * weight;
+        });
+        (push_x as f32, push_y as f32)
+    }
+}
@@ -0,0 +1,55 @@
+//! Asynchronous queries that hand control back to the executor between chunks.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use crate::{Grid, Query, QueryCursor};
+
+/// A query over a region that is read in bounded chunks, yielding to the executor after each one
+/// so huge scans don't starve other tasks. Created by `Grid::query_stream`.
+#[derive(Debug, Clone)]
+pub struct QueryStream<'a> {
+    cursor: QueryCursor<'a>,
+    chunk: usize,
+}
+
+impl<'a> QueryStream<'a> {
+    /// Get the next chunk of at most `chunk` ids, or `None` once the region was scanned.
+    /// Each chunk is read after a yield point.
+    pub async fn next_chunk(&mut self) -> Option<Vec<u32>> {
+        if self.cursor.is_done() {
+            return None;
+        }
+        YieldNow(false).await;
+        let batch = self.cursor.next_batch(self.chunk);
+        (!batch.is_empty()).then_some(batch)
+    }
+}
+
+/// A future that is pending once, waking itself so the executor can run other tasks first.
+struct YieldNow(bool);
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.0 {
+            return Poll::Ready(());
+        }
+        self.0 = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+impl Grid {
+    /// Start an asynchronous query over a region, read `chunk` ids at a time with a yield point between chunks.
+    /// Like `query_cursor`, the grid is borrowed for as long as the stream lives.
+    pub fn query_stream(&self, query: &Query, chunk: usize) -> QueryStream<'_> {
+        QueryStream {
+            cursor: self.query_cursor(query),
+            chunk: chunk.max(1),
+        }
+    }
+}
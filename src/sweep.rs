@@ -0,0 +1,177 @@
+//! Sweep-and-prune index for long, thin worlds.
+
+use std::collections::HashMap;
+
+use crate::{check_entity, overlaps, Entity, Error, Query, SpatialIndex};
+
+/// The axis a `SweepPrune` index keeps its entities sorted along.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Axis {
+    #[default]
+    X,
+    Y,
+}
+
+impl Axis {
+    #[inline]
+    fn start(self, x: u32, y: u32) -> u32 {
+        match self {
+            Axis::X => x,
+            Axis::Y => y,
+        }
+    }
+
+    #[inline]
+    fn extent(self, width: u32, height: u32) -> u32 {
+        match self {
+            Axis::X => width,
+            Axis::Y => height,
+        }
+    }
+}
+
+/// Sweep-and-prune spatial index. Entities are kept sorted by where they start along one axis,
+/// so a query only scans the entities whose interval on that axis can reach it.
+/// Best suited to worlds that are much longer along one axis than the other.
+#[derive(Debug, Clone, Default)]
+pub struct SweepPrune {
+    axis: Axis,
+    sorted: Vec<(u32, u32)>,
+    entities: HashMap<u32, Entity>,
+    max_extent: u32,
+}
+
+impl SweepPrune {
+    /// Create an index sorted along `axis`.
+    pub fn new(axis: Axis) -> Self {
+        Self {
+            axis,
+            ..Default::default()
+        }
+    }
+
+    /// Get the number of entities in the index.
+    pub fn len(&self) -> usize {
+        self.sorted.len()
+    }
+
+    /// Check whether the index holds no entities.
+    pub fn is_empty(&self) -> bool {
+        self.sorted.is_empty()
+    }
+
+    /// Insert an entity. Inserting an id that is already present moves it; `SpatialIndex::insert` rejects it instead.
+    pub fn insert(&mut self, entity: &Entity) {
+        if self.entities.contains_key(&entity.id) {
+            self.delete(entity.id);
+        }
+
+        let key = (self.axis.start(entity.x, entity.y), entity.id);
+        let index = self.sorted.partition_point(|&k| k < key);
+        self.sorted.insert(index, key);
+        self.max_extent = self
+            .max_extent
+            .max(self.axis.extent(entity.width, entity.height));
+        self.entities.insert(entity.id, entity.clone());
+    }
+
+    /// Delete an entity by ID.
+    pub fn delete(&mut self, id: u32) {
+        if let Some(entity) = self.entities.remove(&id) {
+            let key = (self.axis.start(entity.x, entity.y), id);
+            if let Ok(index) = self.sorted.binary_search(&key) {
+                self.sorted.remove(index);
+            }
+        }
+    }
+
+    /// Move or resize an entity.
+    pub fn update(&mut self, entity: &Entity) {
+        self.insert(entity);
+    }
+
+    /// Retrieve entities whose rectangles overlap a region.
+    pub fn query(&self, query: &Query) -> Vec<u32> {
+        // the largest extent ever inserted bounds how far back an overlapping entity can start.
+        let start = self.axis.start(query.x, query.y);
+        let from = start.saturating_sub(self.max_extent);
+        let to = start as u64 + self.axis.extent(query.width, query.height) as u64;
+
+        let first = self.sorted.partition_point(|&(s, _)| s < from);
+        self.sorted[first..]
+            .iter()
+            .take_while(|&&(s, _)| s as u64 <= to)
+            .map(|&(_, id)| id)
+            .filter(|id| overlaps(&self.entities[id], query))
+            .collect()
+    }
+
+    /// Retrieve entities whose rectangles contain a point.
+    pub fn query_point(&self, x: u32, y: u32) -> Vec<u32> {
+        self.query(&Query {
+            x,
+            y,
+            width: 0,
+            height: 0,
+        })
+    }
+
+    /// Find every pair of overlapping entities, smallest id first, in a single sweep.
+    pub fn find_pairs(&self) -> Vec<(u32, u32)> {
+        let mut pairs = Vec::new();
+        let mut active: Vec<(u64, &Entity)> = Vec::new();
+        for (start, id) in self.sorted.iter() {
+            let entity = &self.entities[id];
+            active.retain(|&(end, _)| end >= *start as u64);
+            for &(_, other) in active.iter() {
+                if overlaps(other, &entity.into()) {
+                    pairs.push((entity.id.min(other.id), entity.id.max(other.id)));
+                }
+            }
+            let end = *start as u64 + self.axis.extent(entity.width, entity.height) as u64;
+            active.push((end, entity));
+        }
+        pairs.sort_unstable();
+        pairs
+    }
+
+    /// Remove every entity.
+    pub fn clear(&mut self) {
+        self.sorted.clear();
+        self.entities.clear();
+        self.max_extent = 0;
+    }
+}
+
+impl SpatialIndex for SweepPrune {
+    fn insert(&mut self, entity: &Entity) -> Result<(), Error> {
+        check_entity(entity)?;
+        if self.entities.contains_key(&entity.id) {
+            return Err(Error::DuplicateId(entity.id));
+        }
+        SweepPrune::insert(self, entity);
+        Ok(())
+    }
+
+    fn delete(&mut self, id: u32) {
+        SweepPrune::delete(self, id)
+    }
+
+    fn update(&mut self, entity: &Entity) -> Result<(), Error> {
+        check_entity(entity)?;
+        SweepPrune::update(self, entity);
+        Ok(())
+    }
+
+    fn query(&self, query: &Query) -> Vec<u32> {
+        SweepPrune::query(self, query)
+    }
+
+    fn query_point(&self, x: u32, y: u32) -> Vec<u32> {
+        SweepPrune::query_point(self, x, y)
+    }
+
+    fn find_pairs(&self) -> Vec<(u32, u32)> {
+        SweepPrune::find_pairs(self)
+    }
+}
@@ -0,0 +1,112 @@
+//! Dynamic tags such as "burning" or "stealthed", any number of which an entity can carry at once.
+
+use crate::{Error, Grid, Query, QueryResults};
+
+/// The number of distinct tags.
+pub const MAX_TAGS: u8 = 64;
+
+/// A tag, identified by its index below `MAX_TAGS`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Tag(u8);
+
+impl Tag {
+    /// Create a tag from its index. Panics if `index` is not below `MAX_TAGS`.
+    pub const fn new(index: u8) -> Self {
+        assert!(index < MAX_TAGS, "tag out of range");
+        Self(index)
+    }
+
+    /// Get the index of the tag.
+    pub const fn index(self) -> u8 {
+        self.0
+    }
+
+    /// Get the tag's bit in a mask of tags, for `Grid::set_tags` and `Grid::query_flags`.
+    pub const fn bit(self) -> u64 {
+        1 << self.0
+    }
+}
+
+impl Grid {
+    /// Add a tag to an entity. Like priorities, `update` keeps tags and `delete` removes them. Pinned entities cannot be tagged.
+    pub fn tag(&mut self, id: u32, tag: Tag) -> Result<(), Error> {
+        let map = self.maps.get_scalar_mut(id);
+        if !map.is_registered() || map.id != id {
+            return Err(Error::NotFound(id));
+        }
+        map.meta.tags |= tag.bit();
+        Ok(())
+    }
+
+    /// Remove a tag from an entity.
+    pub fn untag(&mut self, id: u32, tag: Tag) -> Result<(), Error> {
+        let map = self.maps.get_scalar_mut(id);
+        if !map.is_registered() || map.id != id {
+            return Err(Error::NotFound(id));
+        }
+        map.meta.tags &= !tag.bit();
+        Ok(())
+    }
+
+    /// Check whether an entity carries a tag.
+    pub fn has_tag(&self, id: u32, tag: Tag) -> bool {
+        let map = self.maps.get_scalar(id);
+        map.is_registered() && map.id == id && map.meta.tags & tag.bit() != 0
+    }
+
+    /// Replace all of an entity's tags at once with a mask of tag bits.
+    pub fn set_tags(&mut self, id: u32, tags: u64) -> Result<(), Error> {
+        let map = self.maps.get_scalar_mut(id);
+        if !map.is_registered() || map.id != id {
+            return Err(Error::NotFound(id));
+        }
+        map.meta.tags = tags;
+        Ok(())
+    }
+
+    /// Get the mask of an entity's tags, which is empty for entities that are not registered.
+    pub fn tags(&self, id: u32) -> u64 {
+        let map = self.maps.get_scalar(id);
+        if !map.is_registered() || map.id != id {
+            return 0;
+        }
+        map.meta.tags
+    }
+
+    /// Retrieve the unique entities in a region carrying every tag in `required` and none in `excluded`, both masks of
+    /// tag bits. The tags are tested while the cells are visited, before deduplication; with both masks empty, no
+    /// entity's tags are read at all.
+    pub fn query_flags(&self, query: &Query, required: u64, excluded: u64) -> QueryResults {
+        let sx = query.x >> self.shift;
+        let sy = query.y >> self.shift;
+
+        let ex = query.x.saturating_add(query.width) >> self.shift;
+        let ey = query.y.saturating_add(query.height) >> self.shift;
+
+        let mut result = self.results.take();
+        if required == 0 && excluded == 0 {
+            self.collect_cells(sx, sy, ex, ey, usize::MAX, |_| true, &mut result);
+        } else {
+            let accept = |id| {
+                let tags = self.tags(id);
+                tags & required == required && tags & excluded == 0
+            };
+            self.collect_cells(sx, sy, ex, ey, usize::MAX, accept, &mut result);
+        }
+        QueryResults::new(result)
+    }
+
+    /// Retrieve the unique entities in a region carrying a tag. Untagged entities are skipped before deduplication.
+    pub fn query_tagged(&self, query: &Query, tag: Tag) -> QueryResults {
+        let sx = query.x >> self.shift;
+        let sy = query.y >> self.shift;
+
+        let ex = query.x.saturating_add(query.width) >> self.shift;
+        let ey = query.y.saturating_add(query.height) >> self.shift;
+
+        let mut result = self.results.take();
+        let accept = |id| self.has_tag(id, tag);
+        self.collect_cells(sx, sy, ex, ey, usize::MAX, accept, &mut result);
+        QueryResults::new(result)
+    }
+}
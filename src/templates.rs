@@ -0,0 +1,65 @@
+//! Entity templates sharing a size and tags, for spawning many identical entities such as bullets.
+
+use crate::{Entity, Error, Grid, Layer};
+
+/// A template registered with `Grid::register_template`. It is only valid for the grid that returned it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TemplateId(u32);
+
+/// The size and tags shared by the entities of a template.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Template {
+    width: u32,
+    height: u32,
+    tags: u64,
+}
+
+impl Grid {
+    /// Register a size and a mask of tag bits for `insert_from_template`. Templates stay registered for the grid's lifetime,
+    /// so register each kind of entity once rather than per spawn.
+    pub fn register_template(&mut self, width: u32, height: u32, tags: u64) -> TemplateId {
+        self.templates.push(Template {
+            width,
+            height,
+            tags,
+        });
+        TemplateId(self.templates.len() as u32 - 1)
+    }
+
+    /// Insert a dynamic entity at a position with a template's size and tags, like `insert` followed by `set_tags`.
+    /// Panics if the template was not registered with this grid.
+    pub fn insert_from_template(
+        &mut self,
+        id: u32,
+        x: u32,
+        y: u32,
+        template: TemplateId,
+    ) -> Result<(), Error> {
+        let template = *self
+            .templates
+            .get(template.0 as usize)
+            .expect("template not registered with this grid");
+        let entity = Entity {
+            id,
+            x,
+            y,
+            width: template.width,
+            height: template.height,
+        };
+        let sx = x >> self.shift;
+        let sy = y >> self.shift;
+
+        let ex = x.saturating_add(template.width) >> self.shift;
+        let ey = y.saturating_add(template.height) >> self.shift;
+
+        let result = self.insert_cells(&entity, sx, sy, ex, ey, Layer::Dynamic);
+        if result.as_ref().is_err_and(Error::is_rejection) {
+            return result;
+        }
+        let map = self.maps.get_scalar_mut(id);
+        if map.is_registered() && map.id == id {
+            map.meta.tags = template.tags;
+        }
+        result
+    }
+}
@@ -0,0 +1,92 @@
+//! Bulk insertion of solid tiles as static entities.
+
+use crate::{too_many_cells, Entity, Error, Grid, Layer, FIXED_SIZE};
+
+impl Grid {
+    /// Insert the solid tiles of a row-major tilemap with `columns` tiles per row as static entities.
+    /// Adjacent solid tiles are merged into rectangles, each covering at most `FIXED_SIZE` cells, which get consecutive ids from `first_id`.
+    /// Each rectangle spans exactly the pixels of its tiles, so its far edge is the last pixel of the last tile.
+    /// A partial last row is read as if padded with empty tiles. Returns how many entities were inserted.
+    /// Fails with `Error::TooManyCells` before the grid is touched if a single tile covers more cells than an entity can hold
+    /// and would not go to the large list. If an insert fails, for example on a full cell or an id that is already taken,
+    /// the rectangles inserted before it are deleted again and its error is returned.
+    pub fn insert_tilemap(
+        &mut self,
+        first_id: u32,
+        x: u32,
+        y: u32,
+        tile_size: u32,
+        columns: usize,
+        tiles: &[bool],
+    ) -> Result<u32, Error> {
+        if columns == 0 || tile_size == 0 {
+            return Ok(0);
+        }
+        let rows = tiles.len().div_ceil(columns);
+        let mut taken = vec![false; rows * columns];
+        let free = |taken: &[bool], row: usize, column: usize| {
+            let index = row * columns + column;
+            tiles.get(index) == Some(&true) && !taken[index]
+        };
+        let rect = |column: usize, row: usize, width: usize, height: usize| Entity {
+            id: 0,
+            x: x.saturating_add((column as u32).saturating_mul(tile_size)),
+            y: y.saturating_add((row as u32).saturating_mul(tile_size)),
+            width: (width as u32).saturating_mul(tile_size) - 1,
+            height: (height as u32).saturating_mul(tile_size) - 1,
+        };
+        let cells = |entity: &Entity| {
+            let sx = entity.x >> self.shift;
+            let sy = entity.y >> self.shift;
+            let ex = entity.x.saturating_add(entity.width) >> self.shift;
+            let ey = entity.y.saturating_add(entity.height) >> self.shift;
+            (ex - sx + 1) as u64 * (ey - sy + 1) as u64
+        };
+        let fits = |entity: &Entity| cells(entity) <= FIXED_SIZE as u64;
+
+        let mut rects = Vec::new();
+        for row in 0..rows {
+            for column in 0..columns {
+                if !free(&taken, row, column) {
+                    continue;
+                }
+                let tile = cells(&rect(column, row, 1, 1));
+                if too_many_cells(tile) && !self.goes_large(tile, Layer::Static) {
+                    return Err(Error::TooManyCells(first_id + rects.len() as u32));
+                }
+                // grow a run along the row, then extend it down while the whole run stays solid.
+                let mut width = 1;
+                while column + width < columns
+                    && free(&taken, row, column + width)
+                    && fits(&rect(column, row, width + 1, 1))
+                {
+                    width += 1;
+                }
+                let mut height = 1;
+                while row + height < rows
+                    && (column..column + width).all(|c| free(&taken, row + height, c))
+                    && fits(&rect(column, row, width, height + 1))
+                {
+                    height += 1;
+                }
+                for r in row..row + height {
+                    taken[r * columns + column..r * columns + column + width].fill(true);
+                }
+                rects.push(rect(column, row, width, height));
+            }
+        }
+
+        let mut id = first_id;
+        for mut entity in rects {
+            entity.id = id;
+            if let Err(err) = self.insert_static(&entity) {
+                for inserted in first_id..id {
+                    self.delete(inserted);
+                }
+                return Err(err);
+            }
+            id += 1;
+        }
+        Ok(id - first_id)
+    }
+}
@@ -0,0 +1,44 @@
+//! Reporting which cells an entity moved between.
+
+use crate::{Entity, Error, Grid};
+
+/// The cells an entity entered and left during an update.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CellTransition<'a> {
+    pub id: u32,
+    /// Cells the entity now occupies but did not before.
+    pub entered: &'a [(u32, u32)],
+    /// Cells the entity occupied before but no longer does.
+    pub left: &'a [(u32, u32)],
+}
+
+impl CellTransition<'_> {
+    /// Check whether the entity stayed in the same cells.
+    pub fn is_empty(&self) -> bool {
+        self.entered.is_empty() && self.left.is_empty()
+    }
+}
+
+impl Grid {
+    /// Move or resize an entity like `update`, also reporting which cells it entered and left.
+    /// The cell lists live in buffers reused across calls, so this does not allocate once warmed up.
+    pub fn update_with_transition(&mut self, entity: &Entity) -> Result<CellTransition<'_>, Error> {
+        let before = self.maps.get_scalar(entity.id).cells.clone();
+        let result = self.update(entity);
+
+        self.entered.clear();
+        self.left.clear();
+        let after = &self.maps.get_scalar(entity.id).cells;
+        self.entered
+            .extend(after.iter().filter(|cell| !before.contains(cell)));
+        self.left
+            .extend(before.iter().filter(|cell| !after.contains(cell)));
+        result?;
+
+        Ok(CellTransition {
+            id: entity.id,
+            entered: &self.entered,
+            left: &self.left,
+        })
+    }
+}
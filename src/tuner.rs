@@ -0,0 +1,93 @@
+//! Cell size analysis based on the entities currently in the grid.
+
+use std::collections::HashMap;
+
+use crate::{Error, Grid, FIXED_SIZE};
+
+/// Statistics describing how the current population would be laid out with a given shift.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShiftStats {
+    pub shift: u32,
+    /// Average number of cells each entity covers.
+    pub cells_per_entity: f64,
+    /// Average number of ids in the cells an entity covers, weighted by registration.
+    pub mean_occupancy: f64,
+    /// Number of ids in the most crowded cell.
+    pub max_occupancy: usize,
+    /// Number of cells holding more ids than the bucket capacity.
+    pub overflowing_cells: usize,
+}
+
+impl ShiftStats {
+    /// Expected number of cells and ids touched when querying an entity's own footprint.
+    pub fn cost(&self) -> f64 {
+        self.cells_per_entity * (1.0 + self.mean_occupancy)
+    }
+}
+
+impl Grid {
+    /// Compute layout statistics for the current population as if the grid used `shift`.
+    /// Returns `None` if the grid is empty or an entity would cover more than `FIXED_SIZE` cells.
+    pub fn shift_stats(&self, shift: u32) -> Option<ShiftStats> {
+        let mut occupancy: HashMap<(u32, u32), usize> = HashMap::new();
+        let mut entities = 0;
+        let mut registrations = 0;
+        for (entity, _) in self.entities() {
+            let sx = entity.x >> shift;
+            let sy = entity.y >> shift;
+            let ex = (entity.x as u64 + entity.width as u64).min(u32::MAX as u64) as u32 >> shift;
+            let ey = (entity.y as u64 + entity.height as u64).min(u32::MAX as u64) as u32 >> shift;
+            let cells = ((ex - sx) as usize + 1) * ((ey - sy) as usize + 1);
+            if cells > FIXED_SIZE {
+                return None;
+            }
+            for y in sy..=ey {
+                for x in sx..=ex {
+                    *occupancy.entry((x, y)).or_default() += 1;
+                }
+            }
+            entities += 1;
+            registrations += cells;
+        }
+        if entities == 0 {
+            return None;
+        }
+
+        let squares: usize = occupancy.values().map(|c| c * c).sum();
+        Some(ShiftStats {
+            shift,
+            cells_per_entity: registrations as f64 / entities as f64,
+            mean_occupancy: squares as f64 / registrations as f64,
+            max_occupancy: occupancy.values().copied().max().unwrap_or(0),
+            overflowing_cells: occupancy
+                .values()
+                .filter(|&&c| c > self.grid.capacity)
+                .count(),
+        })
+    }
+
+    /// Recommend the shift that minimizes the expected query cost without overflowing any bucket.
+    /// If every shift overflows, the one with the fewest overflowing cells is returned. `None` if the grid is empty.
+    /// This walks the whole population once per candidate shift.
+    pub fn suggest_shift(&self) -> Option<u32> {
+        (0..32)
+            .filter_map(|shift| self.shift_stats(shift))
+            .min_by(|a, b| {
+                a.overflowing_cells
+                    .cmp(&b.overflowing_cells)
+                    .then(a.cost().total_cmp(&b.cost()))
+            })
+            .map(|stats| stats.shift)
+    }
+
+    /// Rebuild the grid with the suggested shift, returning it. Does nothing if the grid is empty.
+    pub fn apply_suggested_shift(&mut self) -> Result<Option<u32>, Error> {
+        let shift = self.suggest_shift();
+        if let Some(shift) = shift {
+            if shift != self.shift {
+                self.rehash_with_shift(shift)?;
+            }
+        }
+        Ok(shift)
+    }
+}
@@ -0,0 +1,64 @@
+//! Early warnings for cells filling up before inserts start failing, and reports of the most crowded cells.
+
+use std::collections::HashMap;
+
+use crate::Grid;
+
+impl Grid {
+    /// Set the number of ids at which a cell counts as hot, or `None` to stop watching cells.
+    /// A cell is reported each time an insert brings it up to the watermark, so set it below `bucket_capacity`
+    /// to hear about cells before they overflow.
+    pub fn set_watermark(&mut self, watermark: Option<usize>) {
+        self.watermark = watermark;
+        if watermark.is_none() {
+            self.hot.clear();
+        }
+    }
+
+    /// Get the watermark, if any.
+    pub fn watermark(&self) -> Option<usize> {
+        self.watermark
+    }
+
+    /// Take the cells that reached the watermark since the last call, sorted and deduplicated.
+    /// The cells may have emptied since; query them to check.
+    pub fn take_hot_cells(&mut self) -> Vec<(u32, u32)> {
+        let mut hot = std::mem::take(&mut self.hot);
+        hot.sort_unstable();
+        hot.dedup();
+        hot
+    }
+
+    /// Get the `n` cells holding the most entities with their counts, most crowded first, such as to spot players piling up.
+    /// Counts come from the entities' maps, so cells sharing a bucket are told apart; large entities occupy no cells and are
+    /// not counted and pinned entities count in every cell of their rectangle. This iterates over every entry of the internal tables.
+    pub fn hotspots(&self, n: usize) -> Vec<((u32, u32), usize)> {
+        let mut counts: HashMap<(u32, u32), usize> = HashMap::new();
+        for map in self.maps.entries.iter() {
+            for &cell in map.cells.iter() {
+                *counts.entry(cell).or_default() += 1;
+            }
+        }
+        for entity in self.pinned.values() {
+            let sx = entity.x >> self.shift;
+            let sy = entity.y >> self.shift;
+            let ex = entity.x.saturating_add(entity.width) >> self.shift;
+            let ey = entity.y.saturating_add(entity.height) >> self.shift;
+            for y in sy..=ey {
+                for x in sx..=ex {
+                    *counts.entry((x, y)).or_default() += 1;
+                }
+            }
+        }
+
+        let mut cells: Vec<((u32, u32), usize)> = counts.into_iter().collect();
+        let crowded =
+            |a: &((u32, u32), usize), b: &((u32, u32), usize)| b.1.cmp(&a.1).then(a.0.cmp(&b.0));
+        if n < cells.len() {
+            cells.select_nth_unstable_by(n, crowded);
+            cells.truncate(n);
+        }
+        cells.sort_unstable_by(crowded);
+        cells
+    }
+}